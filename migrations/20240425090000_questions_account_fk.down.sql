@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE questions DROP CONSTRAINT IF EXISTS questions_account_id_fkey;
+ALTER TABLE accounts DROP CONSTRAINT IF EXISTS accounts_id_key;
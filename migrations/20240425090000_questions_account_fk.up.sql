@@ -0,0 +1,11 @@
+-- Add up migration script here
+-- account_id was added as a serial, so existing rows hold counter values
+-- that do not point at any account.
+ALTER TABLE questions ALTER COLUMN account_id DROP DEFAULT;
+ALTER TABLE questions ALTER COLUMN account_id DROP NOT NULL;
+DROP SEQUENCE IF EXISTS questions_account_id_seq;
+UPDATE questions SET account_id = NULL;
+
+ALTER TABLE accounts ADD CONSTRAINT accounts_id_key UNIQUE (id);
+ALTER TABLE questions
+ADD CONSTRAINT questions_account_id_fkey FOREIGN KEY (account_id) REFERENCES accounts(id);
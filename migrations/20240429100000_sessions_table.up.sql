@@ -0,0 +1,11 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS sessions (
+    id UUID PRIMARY KEY,
+    account_id integer NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    refresh_token_hash VARCHAR(255) NOT NULL,
+    created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_on TIMESTAMPTZ NOT NULL,
+    revoked_on TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS sessions_account_id_idx ON sessions (account_id);
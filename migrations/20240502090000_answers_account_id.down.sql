@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE answers
+DROP COLUMN account_id;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE answers DROP COLUMN score;
+ALTER TABLE questions DROP COLUMN score;
+DROP TABLE IF EXISTS votes;
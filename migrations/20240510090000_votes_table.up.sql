@@ -0,0 +1,19 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS votes (
+    id serial PRIMARY KEY,
+    account_id integer NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    question_id integer REFERENCES questions(id) ON DELETE CASCADE,
+    answer_id integer REFERENCES answers(id) ON DELETE CASCADE,
+    value SMALLINT NOT NULL CHECK (value IN (-1, 1)),
+    created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+    CHECK ((question_id IS NULL) <> (answer_id IS NULL))
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS votes_account_question_idx
+    ON votes (account_id, question_id) WHERE question_id IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS votes_account_answer_idx
+    ON votes (account_id, answer_id) WHERE answer_id IS NOT NULL;
+
+-- sum of votes, kept in sync by the store so listings can sort on it
+ALTER TABLE questions ADD COLUMN score integer NOT NULL DEFAULT 0;
+ALTER TABLE answers ADD COLUMN score integer NOT NULL DEFAULT 0;
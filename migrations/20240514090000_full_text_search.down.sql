@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS questions_tags_idx;
+ALTER TABLE answers DROP COLUMN search_vector;
+ALTER TABLE questions DROP COLUMN search_vector;
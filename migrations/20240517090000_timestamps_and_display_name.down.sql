@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE accounts DROP COLUMN display_name;
+ALTER TABLE answers DROP COLUMN updated_on;
+ALTER TABLE questions DROP COLUMN updated_on;
@@ -0,0 +1,16 @@
+-- Add up migration script here
+ALTER TABLE questions ADD COLUMN updated_on TIMESTAMP;
+UPDATE questions SET updated_on = created_on;
+ALTER TABLE questions
+    ALTER COLUMN updated_on SET NOT NULL,
+    ALTER COLUMN updated_on SET DEFAULT NOW();
+
+ALTER TABLE answers ADD COLUMN updated_on TIMESTAMP;
+UPDATE answers SET updated_on = created_on;
+ALTER TABLE answers
+    ALTER COLUMN updated_on SET NOT NULL,
+    ALTER COLUMN updated_on SET DEFAULT NOW();
+
+ALTER TABLE accounts ADD COLUMN display_name VARCHAR(255);
+UPDATE accounts SET display_name = split_part(email, '@', 1);
+ALTER TABLE accounts ALTER COLUMN display_name SET NOT NULL;
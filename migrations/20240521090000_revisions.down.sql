@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS answer_revisions;
+DROP TABLE IF EXISTS question_revisions;
+ALTER TABLE answers DROP COLUMN revision;
+ALTER TABLE questions DROP COLUMN revision;
@@ -0,0 +1,34 @@
+-- Add up migration script here
+-- number of the latest revision, bumped by every edit
+ALTER TABLE questions ADD COLUMN revision integer NOT NULL DEFAULT 1;
+ALTER TABLE answers ADD COLUMN revision integer NOT NULL DEFAULT 1;
+
+CREATE TABLE IF NOT EXISTS question_revisions (
+    id serial PRIMARY KEY,
+    question_id integer NOT NULL REFERENCES questions(id) ON DELETE CASCADE,
+    revision integer NOT NULL,
+    title VARCHAR (255) NOT NULL,
+    content TEXT NOT NULL,
+    tags TEXT [],
+    account_id integer REFERENCES accounts(id) ON DELETE SET NULL,
+    comment TEXT,
+    created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+    UNIQUE (question_id, revision)
+);
+
+CREATE TABLE IF NOT EXISTS answer_revisions (
+    id serial PRIMARY KEY,
+    answer_id integer NOT NULL REFERENCES answers(id) ON DELETE CASCADE,
+    revision integer NOT NULL,
+    content TEXT NOT NULL,
+    account_id integer REFERENCES accounts(id) ON DELETE SET NULL,
+    comment TEXT,
+    created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+    UNIQUE (answer_id, revision)
+);
+
+-- earlier edits were not kept, the current text becomes the first revision
+INSERT INTO question_revisions (question_id, revision, title, content, tags, account_id, created_on)
+SELECT id, 1, title, content, tags, account_id, updated_on FROM questions;
+INSERT INTO answer_revisions (answer_id, revision, content, account_id, created_on)
+SELECT id, 1, content, account_id, updated_on FROM answers;
@@ -0,0 +1,11 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS answers_deleted_at_idx;
+DROP INDEX IF EXISTS questions_deleted_at_idx;
+
+ALTER TABLE answers DROP CONSTRAINT IF EXISTS answers_corresponding_question_fkey;
+ALTER TABLE answers
+ADD CONSTRAINT answers_corresponding_question_fkey
+FOREIGN KEY (corresponding_question) REFERENCES questions(id);
+
+ALTER TABLE answers DROP COLUMN deleted_by, DROP COLUMN deleted_at;
+ALTER TABLE questions DROP COLUMN deleted_by, DROP COLUMN deleted_at;
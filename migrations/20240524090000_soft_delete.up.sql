@@ -0,0 +1,18 @@
+-- Add up migration script here
+ALTER TABLE questions
+ADD COLUMN deleted_at TIMESTAMP,
+ADD COLUMN deleted_by integer REFERENCES accounts(id) ON DELETE SET NULL;
+ALTER TABLE answers
+ADD COLUMN deleted_at TIMESTAMP,
+ADD COLUMN deleted_by integer REFERENCES accounts(id) ON DELETE SET NULL;
+
+-- purging a question takes its answers along
+ALTER TABLE answers DROP CONSTRAINT IF EXISTS answers_corresponding_question_fkey;
+ALTER TABLE answers
+ADD CONSTRAINT answers_corresponding_question_fkey
+FOREIGN KEY (corresponding_question) REFERENCES questions(id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS questions_deleted_at_idx ON questions (deleted_at)
+WHERE deleted_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS answers_deleted_at_idx ON answers (deleted_at)
+WHERE deleted_at IS NOT NULL;
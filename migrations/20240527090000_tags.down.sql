@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS tag_synonyms;
+DROP TABLE IF EXISTS tags;
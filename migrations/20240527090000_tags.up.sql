@@ -0,0 +1,33 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS tags (
+    slug TEXT PRIMARY KEY,
+    description TEXT NOT NULL DEFAULT '',
+    created_on TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- alternative spellings, replaced by `tag_slug` whenever a post is saved
+CREATE TABLE IF NOT EXISTS tag_synonyms (
+    synonym TEXT PRIMARY KEY,
+    tag_slug TEXT NOT NULL REFERENCES tags(slug) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS tag_synonyms_tag_slug_idx ON tag_synonyms (tag_slug);
+
+-- same rules as `slugify`: lower case, whitespace and underscores become
+-- dashes, other punctuation except + # . is dropped
+UPDATE questions SET tags = ARRAY(
+    SELECT slug FROM (
+        SELECT trim(BOTH '-' FROM regexp_replace(
+                   regexp_replace(
+                       regexp_replace(lower(trim(tag)), '[[:space:]_]+', '-', 'g'),
+                       '[^[:alnum:]+#.-]', '', 'g'),
+                   '-{2,}', '-', 'g')) AS slug,
+               min(position) AS position
+          FROM unnest(tags) WITH ORDINALITY AS tag_list(tag, position)
+         GROUP BY 1) slugs
+     WHERE slug <> ''
+     ORDER BY position)
+WHERE tags IS NOT NULL;
+
+INSERT INTO tags (slug)
+SELECT DISTINCT unnest(tags) FROM questions WHERE tags IS NOT NULL
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE answers DROP COLUMN IF EXISTS comment_count;
+ALTER TABLE questions DROP COLUMN IF EXISTS comment_count;
+DROP TABLE IF EXISTS comments;
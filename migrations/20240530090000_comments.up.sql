@@ -0,0 +1,22 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS comments (
+    id serial PRIMARY KEY,
+    question_id integer REFERENCES questions(id) ON DELETE CASCADE,
+    answer_id integer REFERENCES answers(id) ON DELETE CASCADE,
+    -- replies go away with the comment they reply to
+    parent_id integer REFERENCES comments(id) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    account_id integer REFERENCES accounts(id) ON DELETE SET NULL,
+    created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_on TIMESTAMP NOT NULL DEFAULT NOW(),
+    CHECK ((question_id IS NULL) <> (answer_id IS NULL))
+);
+
+CREATE INDEX IF NOT EXISTS comments_question_idx
+    ON comments (question_id) WHERE question_id IS NOT NULL;
+CREATE INDEX IF NOT EXISTS comments_answer_idx
+    ON comments (answer_id) WHERE answer_id IS NOT NULL;
+
+-- number of comments, kept in sync by the store like score
+ALTER TABLE questions ADD COLUMN comment_count integer NOT NULL DEFAULT 0;
+ALTER TABLE answers ADD COLUMN comment_count integer NOT NULL DEFAULT 0;
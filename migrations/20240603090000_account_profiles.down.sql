@@ -0,0 +1,7 @@
+-- Add down migration script here
+ALTER TABLE accounts
+    DROP COLUMN IF EXISTS bio,
+    DROP COLUMN IF EXISTS avatar_url,
+    DROP COLUMN IF EXISTS email_verified,
+    DROP COLUMN IF EXISTS created_on,
+    DROP COLUMN IF EXISTS deleted_at;
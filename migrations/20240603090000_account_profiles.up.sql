@@ -0,0 +1,12 @@
+-- Add up migration script here
+ALTER TABLE accounts
+    ADD COLUMN bio TEXT NOT NULL DEFAULT '',
+    ADD COLUMN avatar_url TEXT,
+    ADD COLUMN email_verified BOOLEAN NOT NULL DEFAULT false,
+    -- unknown for older accounts, they get the migration time
+    ADD COLUMN created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+    -- deleted accounts stay as anonymized rows so posts keep their author
+    ADD COLUMN deleted_at TIMESTAMP;
+
+-- accounts predating verification are trusted
+UPDATE accounts SET email_verified = true;
@@ -0,0 +1,14 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS account_tokens (
+    id UUID PRIMARY KEY,
+    account_id integer NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    purpose VARCHAR(16) NOT NULL CHECK (purpose IN ('verify_email', 'reset_password')),
+    secret_hash VARCHAR(255) NOT NULL,
+    -- address the token was mailed to, a verification only counts for it
+    email VARCHAR(255) NOT NULL,
+    created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_on TIMESTAMPTZ NOT NULL,
+    used_on TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS account_tokens_account_id_idx ON account_tokens (account_id, purpose);
@@ -0,0 +1,7 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS recovery_codes;
+
+ALTER TABLE accounts
+    DROP COLUMN IF EXISTS totp_secret,
+    DROP COLUMN IF EXISTS totp_enabled,
+    DROP COLUMN IF EXISTS totp_last_step;
@@ -0,0 +1,17 @@
+-- Add up migration script here
+ALTER TABLE accounts
+    -- base32, set on enrollment and only trusted once totp_enabled
+    ADD COLUMN totp_secret VARCHAR(64),
+    ADD COLUMN totp_enabled BOOLEAN NOT NULL DEFAULT false,
+    -- last accepted time step, a code is never accepted twice
+    ADD COLUMN totp_last_step BIGINT;
+
+CREATE TABLE IF NOT EXISTS recovery_codes (
+    id serial PRIMARY KEY,
+    account_id integer NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    code_hash VARCHAR(255) NOT NULL,
+    created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+    used_on TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS recovery_codes_account_id_idx ON recovery_codes (account_id);
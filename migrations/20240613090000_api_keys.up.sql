@@ -0,0 +1,13 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS api_keys (
+    id UUID PRIMARY KEY,
+    account_id integer NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    scope VARCHAR(8) NOT NULL CHECK (scope IN ('read', 'write')),
+    secret_hash VARCHAR(255) NOT NULL,
+    created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+    last_used_on TIMESTAMPTZ,
+    revoked_on TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS api_keys_account_id_idx ON api_keys (account_id);
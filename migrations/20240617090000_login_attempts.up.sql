@@ -0,0 +1,8 @@
+-- Add up migration script here
+-- keyed by 'email:<address>' or 'ip:<address>', unknown emails are tracked too
+CREATE TABLE IF NOT EXISTS login_attempts (
+    key VARCHAR(320) PRIMARY KEY,
+    failures integer NOT NULL,
+    last_failure_on TIMESTAMPTZ NOT NULL,
+    locked_until TIMESTAMPTZ
+);
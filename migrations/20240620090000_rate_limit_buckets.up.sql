@@ -0,0 +1,7 @@
+-- Add up migration script here
+-- token buckets shared by every instance when rate_limit.backend = "postgres"
+CREATE TABLE IF NOT EXISTS rate_limit_buckets (
+    key TEXT PRIMARY KEY,
+    tokens DOUBLE PRECISION NOT NULL,
+    updated_on TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,2 @@
+-- Add down migration script here
+-- revoked keys stay revoked, their secrets are gone
@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- key secrets are stored as SHA-256 digests now, keys with an argon2 hash
+-- cannot be checked any more and have to be created again
+UPDATE api_keys SET revoked_on = NOW()
+ WHERE revoked_on IS NULL AND secret_hash LIKE '$argon2%';
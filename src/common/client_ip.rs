@@ -0,0 +1,145 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRef, FromRequestParts},
+    http::{request::Parts, Extensions, HeaderMap},
+};
+
+use crate::common::config::Config;
+
+/// Address of the client, `None` when the server runs without connect info
+/// (e.g. in tests) and no trusted header names one.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub Option<IpAddr>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    Arc<Config>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let config = Arc::<Config>::from_ref(state);
+        Ok(ClientIp(client_ip(
+            &parts.headers,
+            &parts.extensions,
+            &config,
+        )))
+    }
+}
+
+/// The `X-Forwarded-For` entry `server.trusted_proxy_hops` from the right
+/// when `server.trust_forwarded_for` is set, the peer address otherwise.
+/// Entries further left come from the client and prove nothing.
+pub fn client_ip(headers: &HeaderMap, extensions: &Extensions, config: &Config) -> Option<IpAddr> {
+    if config.server.trust_forwarded_for {
+        // every proxy appends, so all instances of the header count in order
+        let forwarded: Vec<&str> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .collect();
+        let hop = forwarded
+            .iter()
+            .rev()
+            .nth(config.server.trusted_proxy_hops.saturating_sub(1))
+            .and_then(|ip| ip.trim().parse().ok());
+        if hop.is_some() {
+            return hop;
+        }
+    }
+    extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(forwarded: &[&str]) -> (HeaderMap, Extensions) {
+        let mut headers = HeaderMap::new();
+        for value in forwarded {
+            headers.append("x-forwarded-for", value.parse().unwrap());
+        }
+        let mut extensions = Extensions::new();
+        extensions.insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 4000))));
+        (headers, extensions)
+    }
+
+    fn config(trust_forwarded_for: bool, trusted_proxy_hops: usize) -> Config {
+        let mut config = Config::default();
+        config.server.trust_forwarded_for = trust_forwarded_for;
+        config.server.trusted_proxy_hops = trusted_proxy_hops;
+        config
+    }
+
+    fn ip(ip: &str) -> Option<IpAddr> {
+        Some(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn ignores_forwarded_for_unless_trusted() {
+        let (headers, extensions) = request(&["203.0.113.7"]);
+        assert_eq!(
+            client_ip(&headers, &extensions, &config(false, 1)),
+            ip("10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn takes_the_entry_added_by_the_proxy() {
+        let (headers, extensions) = request(&["203.0.113.7"]);
+        assert_eq!(
+            client_ip(&headers, &extensions, &config(true, 1)),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn spoofed_entries_are_ignored() {
+        // the client sent "1.2.3.4, 5.6.7.8", the proxy appended the real address
+        let (headers, extensions) = request(&["1.2.3.4, 5.6.7.8, 203.0.113.7"]);
+        assert_eq!(
+            client_ip(&headers, &extensions, &config(true, 1)),
+            ip("203.0.113.7")
+        );
+        let (headers, extensions) = request(&["1.2.3.4", "203.0.113.7"]);
+        assert_eq!(
+            client_ip(&headers, &extensions, &config(true, 1)),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn counts_trusted_hops_from_the_right() {
+        // CDN in front of a load balancer, each appending one entry
+        let (headers, extensions) = request(&["1.2.3.4, 203.0.113.7, 198.51.100.2"]);
+        assert_eq!(
+            client_ip(&headers, &extensions, &config(true, 2)),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_peer_without_enough_entries() {
+        let (headers, extensions) = request(&["203.0.113.7"]);
+        assert_eq!(
+            client_ip(&headers, &extensions, &config(true, 2)),
+            ip("10.0.0.1")
+        );
+        let (headers, extensions) = request(&["not an address"]);
+        assert_eq!(
+            client_ip(&headers, &extensions, &config(true, 1)),
+            ip("10.0.0.1")
+        );
+    }
+}
@@ -0,0 +1,957 @@
+use std::{fs, path::PathBuf, str::FromStr};
+
+use axum::http::Method;
+use clap::Parser;
+use lettre::message::Mailbox;
+use serde::Deserialize;
+
+use crate::models::account::Role;
+
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+const ENV_PREFIX: &str = "APP_";
+/// Keys published with earlier versions of this repository, anyone can
+/// sign tokens with them.
+const WELL_KNOWN_TOKEN_KEYS: [&str; 1] = ["RANDOM WORDS WINTER MACINTOSH PC"];
+
+/// Axum web demo API server. Command line flags override `APP_*`
+/// environment variables, which override the config file.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Args {
+    /// Path of the TOML config file
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+    #[arg(long)]
+    pub host: Option<String>,
+    #[arg(short, long)]
+    pub port: Option<u16>,
+    /// Storage backend: postgres or memory
+    #[arg(long)]
+    pub database_backend: Option<StorageBackend>,
+    #[arg(long)]
+    pub database_url: Option<String>,
+    #[arg(long)]
+    pub max_connections: Option<u32>,
+    #[arg(long)]
+    pub token_key: Option<String>,
+    /// Access token lifetime in seconds
+    #[arg(long)]
+    pub token_ttl: Option<i64>,
+    /// Refresh token lifetime in seconds
+    #[arg(long)]
+    pub refresh_token_ttl: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub auth: AuthConfig,
+    pub validation: ValidationConfig,
+    pub content_filter: ContentFilterConfig,
+    pub trash: TrashConfig,
+    pub mail: MailConfig,
+    pub rate_limit: RateLimitConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    /// Take the client address from `X-Forwarded-For`, only safe behind a
+    /// proxy that sets it
+    pub trust_forwarded_for: bool,
+    /// Proxies in front of the server that append to `X-Forwarded-For`, the
+    /// client address is this many entries from the right
+    pub trusted_proxy_hops: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            host: String::from("0.0.0.0"),
+            port: 42001,
+            trust_forwarded_for: false,
+            trusted_proxy_hops: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub backend: StorageBackend,
+    pub url: String,
+    pub max_connections: u32,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            backend: StorageBackend::Postgres,
+            url: String::new(),
+            max_connections: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    Postgres,
+    Memory,
+}
+
+impl FromStr for StorageBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "postgres" => Ok(StorageBackend::Postgres),
+            "memory" => Ok(StorageBackend::Memory),
+            _ => Err(format!("unknown storage backend {:?}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// PASETO v2 local key, must be exactly 32 bytes. Only taken from
+    /// `APP_TOKEN_KEY` or `--token-key`, never from the config file.
+    #[serde(skip)]
+    pub token_key: String,
+    /// Access token lifetime in seconds
+    pub token_ttl: i64,
+    /// Refresh token lifetime in seconds, renewed on every refresh
+    pub refresh_token_ttl: i64,
+    /// Accounts get the admin role once they verify one of these emails
+    pub admin_emails: Vec<String>,
+    /// Unverified accounts may only read and manage their own account
+    pub require_verified_email: bool,
+    /// Email verification link lifetime in seconds
+    pub verification_token_ttl: i64,
+    /// Password reset link lifetime in seconds
+    pub reset_token_ttl: i64,
+    /// Time to enter the second factor after the password, in seconds
+    pub two_factor_challenge_ttl: i64,
+    /// Shown as issuer in authenticator apps
+    pub totp_issuer: String,
+    /// Accounts with these roles keep the restrictions of unverified
+    /// accounts until they sign in with two-factor authentication
+    pub two_factor_roles: Vec<Role>,
+    /// Failed logins for one email before it is locked
+    pub login_max_failures: i32,
+    /// Failed logins from one client address before it is locked
+    pub login_ip_max_failures: i32,
+    /// Lockout length in seconds, failures older than this are forgotten
+    pub login_lockout_secs: i64,
+    /// Wait after a failed login, doubled with every further failure
+    pub login_delay_base_ms: i64,
+    pub login_delay_max_ms: i64,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            token_key: String::new(),
+            token_ttl: 15 * 60,
+            refresh_token_ttl: 30 * 24 * 60 * 60,
+            admin_emails: Vec::new(),
+            require_verified_email: true,
+            verification_token_ttl: 24 * 60 * 60,
+            reset_token_ttl: 60 * 60,
+            two_factor_challenge_ttl: 5 * 60,
+            totp_issuer: String::from("RustWebDev"),
+            two_factor_roles: Vec::new(),
+            login_max_failures: 5,
+            login_ip_max_failures: 50,
+            login_lockout_secs: 15 * 60,
+            login_delay_base_ms: 500,
+            login_delay_max_ms: 30_000,
+        }
+    }
+}
+
+impl AuthConfig {
+    /// Emails compare without regard to case, as mail servers treat them.
+    pub fn is_admin_email(&self, email: &str) -> bool {
+        let email = email.trim().to_lowercase();
+        self.admin_emails
+            .iter()
+            .any(|admin_email| admin_email.trim().to_lowercase() == email)
+    }
+}
+
+/// Limits applied to request payloads before they reach the store.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ValidationConfig {
+    /// Characters, at most 255 to fit the column
+    pub title_max_length: usize,
+    pub content_max_length: usize,
+    pub comment_max_length: usize,
+    pub max_tags: usize,
+    pub tag_max_length: usize,
+    pub password_min_length: usize,
+    pub password_require_letter: bool,
+    pub password_require_digit: bool,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        ValidationConfig {
+            title_max_length: 255,
+            content_max_length: 30_000,
+            comment_max_length: 600,
+            max_tags: 5,
+            tag_max_length: 32,
+            password_min_length: 8,
+            password_require_letter: true,
+            password_require_digit: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContentFilterConfig {
+    pub backend: ContentFilterBackend,
+    pub mode: FilterMode,
+    /// Used by the word_list backend and as fallback of the http backend
+    pub words: Vec<String>,
+    pub api_url: String,
+    /// Sent as `apikey` header when set
+    pub api_key: String,
+    /// Per attempt
+    pub timeout_ms: u64,
+    pub retries: u32,
+    /// Consecutive failures before the API is skipped for a cooldown
+    pub breaker_threshold: u32,
+    pub breaker_cooldown_secs: u64,
+    /// Screen with `words` while the API is failing instead of answering 502/503
+    pub fallback_to_word_list: bool,
+}
+
+impl Default for ContentFilterConfig {
+    fn default() -> Self {
+        ContentFilterConfig {
+            backend: ContentFilterBackend::WordList,
+            mode: FilterMode::Censor,
+            words: Vec::new(),
+            api_url: String::from("https://api.apilayer.com/bad_words?censor_character=*"),
+            api_key: String::new(),
+            timeout_ms: 2000,
+            retries: 2,
+            breaker_threshold: 5,
+            breaker_cooldown_secs: 30,
+            fallback_to_word_list: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentFilterBackend {
+    WordList,
+    Http,
+}
+
+impl FromStr for ContentFilterBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "word_list" => Ok(ContentFilterBackend::WordList),
+            "http" => Ok(ContentFilterBackend::Http),
+            _ => Err(format!("unknown content filter backend {:?}", s)),
+        }
+    }
+}
+
+/// What happens to posts with flagged words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterMode {
+    /// Store them with the words masked
+    Censor,
+    /// Refuse them with a validation error
+    Reject,
+}
+
+impl FromStr for FilterMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "censor" => Ok(FilterMode::Censor),
+            "reject" => Ok(FilterMode::Reject),
+            _ => Err(format!("unknown content filter mode {:?}", s)),
+        }
+    }
+}
+
+/// Deleted posts stay restorable for `retention_days`, then the purge job
+/// removes them for good.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrashConfig {
+    pub retention_days: u32,
+    pub purge_interval_secs: u64,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        TrashConfig {
+            retention_days: 30,
+            purge_interval_secs: 60 * 60,
+        }
+    }
+}
+
+/// Outgoing mail, used for verification and password reset links.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MailConfig {
+    pub transport: MailTransport,
+    /// Sender, e.g. `Q&A <no-reply@example.com>`
+    pub from: String,
+    /// Links in mails point here
+    pub public_url: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_username: String,
+    pub smtp_password: String,
+    /// Turn off for local mail catchers that do not speak TLS
+    pub smtp_starttls: bool,
+    /// Where the file transport drops `.eml` files
+    pub file_dir: PathBuf,
+}
+
+impl Default for MailConfig {
+    fn default() -> Self {
+        MailConfig {
+            transport: MailTransport::File,
+            from: String::from("Q&A <no-reply@localhost>"),
+            public_url: String::from("http://localhost:42001"),
+            smtp_host: String::new(),
+            smtp_port: 587,
+            smtp_username: String::new(),
+            smtp_password: String::new(),
+            smtp_starttls: true,
+            file_dir: PathBuf::from("mail"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MailTransport {
+    Smtp,
+    /// Writes every mail to `file_dir`
+    File,
+    /// Keeps mails in memory and logs them
+    Memory,
+}
+
+impl FromStr for MailTransport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "smtp" => Ok(MailTransport::Smtp),
+            "file" => Ok(MailTransport::File),
+            "memory" => Ok(MailTransport::Memory),
+            _ => Err(format!("unknown mail transport {:?}", s)),
+        }
+    }
+}
+
+/// Token buckets per client, see `common::rate_limit`. Each route policy
+/// gets its own buckets, other routes share the default one.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    pub backend: RateLimitBackend,
+    /// Requests per `window_secs` on routes without a policy
+    pub requests: u32,
+    pub window_secs: u64,
+    /// Rejected tokens and API keys per `failed_auth_window_secs` and client
+    /// address, checked before the credential is
+    pub failed_auth_requests: u32,
+    pub failed_auth_window_secs: u64,
+    /// Stale buckets are dropped this often
+    pub purge_interval_secs: u64,
+    pub routes: Vec<RoutePolicy>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        let policy = |method: &str, path: &str, requests, window_secs| RoutePolicy {
+            method: Some(String::from(method)),
+            path: String::from(path),
+            requests,
+            window_secs,
+        };
+        RateLimitConfig {
+            enabled: true,
+            backend: RateLimitBackend::Memory,
+            requests: 300,
+            window_secs: 60,
+            failed_auth_requests: 30,
+            failed_auth_window_secs: 60,
+            purge_interval_secs: 10 * 60,
+            routes: vec![
+                policy("POST", "/api/login", 10, 60),
+                policy("POST", "/api/login/2fa", 10, 60),
+                policy("POST", "/api/registration", 5, 60 * 60),
+                policy("POST", "/api/password/forgot", 5, 60 * 60),
+                policy("POST", "/api/questions", 10, 10 * 60),
+            ],
+        }
+    }
+}
+
+/// Limit for requests to `path`, a route as declared in the router (e.g.
+/// `/api/questions/:id`), and `method` when given.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoutePolicy {
+    pub method: Option<String>,
+    pub path: String,
+    pub requests: u32,
+    pub window_secs: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitBackend {
+    /// Buckets live in this process, each instance counts on its own
+    Memory,
+    /// Buckets shared through the database, for several instances
+    Postgres,
+}
+
+impl FromStr for RateLimitBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "memory" => Ok(RateLimitBackend::Memory),
+            "postgres" => Ok(RateLimitBackend::Postgres),
+            _ => Err(format!("unknown rate limit backend {:?}", s)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    CannotReadFile(PathBuf, std::io::Error),
+    CannotParseFile(PathBuf, toml::de::Error),
+    InvalidEnvVar(String, String),
+    InvalidValue(&'static str, String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::CannotReadFile(path, err) => {
+                write!(f, "Cannot read config file {}: {}", path.display(), err)
+            }
+            ConfigError::CannotParseFile(path, err) => {
+                write!(f, "Cannot parse config file {}: {}", path.display(), err)
+            }
+            ConfigError::InvalidEnvVar(var, value) => {
+                write!(
+                    f,
+                    "Invalid value {:?} for environment variable {}",
+                    value, var
+                )
+            }
+            ConfigError::InvalidValue(key, reason) => write!(f, "Invalid {}: {}", key, reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Builds the config from, in increasing order of precedence: built-in
+    /// defaults, the TOML file, `APP_*` environment variables and CLI flags.
+    pub fn load() -> Result<Config, ConfigError> {
+        let args = Args::parse();
+        let config_path = match args.config.clone() {
+            Some(path) => Some(path),
+            None => std::env::var(format!("{}CONFIG", ENV_PREFIX))
+                .ok()
+                .map(PathBuf::from),
+        };
+
+        let mut config = Config::from_file(config_path)?;
+        config.apply_env()?;
+        config.apply_args(args);
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// An explicitly given file must exist, the default `config.toml` is optional.
+    fn from_file(path: Option<PathBuf>) -> Result<Config, ConfigError> {
+        let (path, required) = match path {
+            Some(path) => (path, true),
+            None => (PathBuf::from(DEFAULT_CONFIG_FILE), false),
+        };
+
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Config::default())
+            }
+            Err(e) => return Err(ConfigError::CannotReadFile(path, e)),
+        };
+
+        toml::from_str(&content).map_err(|e| ConfigError::CannotParseFile(path, e))
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        self.apply_vars(&|var| std::env::var(var).ok())
+    }
+
+    /// `vars` looks up an environment variable by its full name.
+    fn apply_vars(&mut self, vars: &dyn Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        override_from_env(vars, "HOST", &mut self.server.host)?;
+        override_from_env(vars, "PORT", &mut self.server.port)?;
+        override_from_env(vars, "DATABASE_BACKEND", &mut self.database.backend)?;
+        override_from_env(vars, "DATABASE_URL", &mut self.database.url)?;
+        override_from_env(
+            vars,
+            "DATABASE_MAX_CONNECTIONS",
+            &mut self.database.max_connections,
+        )?;
+        override_from_env(vars, "TOKEN_KEY", &mut self.auth.token_key)?;
+        override_from_env(vars, "TOKEN_TTL", &mut self.auth.token_ttl)?;
+        override_from_env(vars, "REFRESH_TOKEN_TTL", &mut self.auth.refresh_token_ttl)?;
+        override_from_env(
+            vars,
+            "LOGIN_MAX_FAILURES",
+            &mut self.auth.login_max_failures,
+        )?;
+        override_from_env(
+            vars,
+            "LOGIN_IP_MAX_FAILURES",
+            &mut self.auth.login_ip_max_failures,
+        )?;
+        override_from_env(
+            vars,
+            "LOGIN_LOCKOUT_SECS",
+            &mut self.auth.login_lockout_secs,
+        )?;
+        override_from_env(
+            vars,
+            "TRUST_FORWARDED_FOR",
+            &mut self.server.trust_forwarded_for,
+        )?;
+        override_from_env(
+            vars,
+            "TRUSTED_PROXY_HOPS",
+            &mut self.server.trusted_proxy_hops,
+        )?;
+        override_from_env(
+            vars,
+            "REQUIRE_VERIFIED_EMAIL",
+            &mut self.auth.require_verified_email,
+        )?;
+        override_from_env(
+            vars,
+            "TITLE_MAX_LENGTH",
+            &mut self.validation.title_max_length,
+        )?;
+        override_from_env(
+            vars,
+            "CONTENT_MAX_LENGTH",
+            &mut self.validation.content_max_length,
+        )?;
+        override_from_env(
+            vars,
+            "COMMENT_MAX_LENGTH",
+            &mut self.validation.comment_max_length,
+        )?;
+        override_from_env(vars, "MAX_TAGS", &mut self.validation.max_tags)?;
+        override_from_env(
+            vars,
+            "CONTENT_FILTER_BACKEND",
+            &mut self.content_filter.backend,
+        )?;
+        override_from_env(vars, "CONTENT_FILTER_MODE", &mut self.content_filter.mode)?;
+        override_from_env(vars, "CONTENT_FILTER_URL", &mut self.content_filter.api_url)?;
+        override_from_env(
+            vars,
+            "CONTENT_FILTER_API_KEY",
+            &mut self.content_filter.api_key,
+        )?;
+        override_from_env(
+            vars,
+            "PASSWORD_MIN_LENGTH",
+            &mut self.validation.password_min_length,
+        )?;
+        override_from_env(vars, "TRASH_RETENTION_DAYS", &mut self.trash.retention_days)?;
+        override_from_env(
+            vars,
+            "TRASH_PURGE_INTERVAL_SECS",
+            &mut self.trash.purge_interval_secs,
+        )?;
+        override_from_env(vars, "RATE_LIMIT_ENABLED", &mut self.rate_limit.enabled)?;
+        override_from_env(vars, "RATE_LIMIT_BACKEND", &mut self.rate_limit.backend)?;
+        override_from_env(vars, "MAIL_TRANSPORT", &mut self.mail.transport)?;
+        override_from_env(vars, "MAIL_FROM", &mut self.mail.from)?;
+        override_from_env(vars, "MAIL_PUBLIC_URL", &mut self.mail.public_url)?;
+        override_from_env(vars, "SMTP_HOST", &mut self.mail.smtp_host)?;
+        override_from_env(vars, "SMTP_PORT", &mut self.mail.smtp_port)?;
+        override_from_env(vars, "SMTP_USERNAME", &mut self.mail.smtp_username)?;
+        override_from_env(vars, "SMTP_PASSWORD", &mut self.mail.smtp_password)?;
+        Ok(())
+    }
+
+    fn apply_args(&mut self, args: Args) {
+        if let Some(host) = args.host {
+            self.server.host = host;
+        }
+        if let Some(port) = args.port {
+            self.server.port = port;
+        }
+        if let Some(backend) = args.database_backend {
+            self.database.backend = backend;
+        }
+        if let Some(url) = args.database_url {
+            self.database.url = url;
+        }
+        if let Some(max_connections) = args.max_connections {
+            self.database.max_connections = max_connections;
+        }
+        if let Some(token_key) = args.token_key {
+            self.auth.token_key = token_key;
+        }
+        if let Some(token_ttl) = args.token_ttl {
+            self.auth.token_ttl = token_ttl;
+        }
+        if let Some(refresh_token_ttl) = args.refresh_token_ttl {
+            self.auth.refresh_token_ttl = refresh_token_ttl;
+        }
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.server.host.is_empty() {
+            return Err(ConfigError::InvalidValue(
+                "server.host",
+                "must not be empty".into(),
+            ));
+        }
+        if self.server.trust_forwarded_for && self.server.trusted_proxy_hops == 0 {
+            return Err(ConfigError::InvalidValue(
+                "server.trusted_proxy_hops",
+                "must be greater than 0 when trust_forwarded_for is set".into(),
+            ));
+        }
+        if self.database.backend == StorageBackend::Postgres && self.database.url.is_empty() {
+            return Err(ConfigError::InvalidValue(
+                "database.url",
+                "must be set".into(),
+            ));
+        }
+        if self.database.max_connections == 0 {
+            return Err(ConfigError::InvalidValue(
+                "database.max_connections",
+                "must be greater than 0".into(),
+            ));
+        }
+        if self.auth.token_key.is_empty() {
+            return Err(ConfigError::InvalidValue(
+                "auth.token_key",
+                "must be set through APP_TOKEN_KEY or --token-key".into(),
+            ));
+        }
+        if WELL_KNOWN_TOKEN_KEYS.contains(&self.auth.token_key.as_str()) {
+            return Err(ConfigError::InvalidValue(
+                "auth.token_key",
+                "is publicly known, generate a random key".into(),
+            ));
+        }
+        if self.auth.token_key.len() != 32 {
+            return Err(ConfigError::InvalidValue(
+                "auth.token_key",
+                format!("must be 32 bytes long, got {}", self.auth.token_key.len()),
+            ));
+        }
+        if self.auth.token_ttl <= 0 {
+            return Err(ConfigError::InvalidValue(
+                "auth.token_ttl",
+                "must be greater than 0".into(),
+            ));
+        }
+        if self.auth.refresh_token_ttl <= self.auth.token_ttl {
+            return Err(ConfigError::InvalidValue(
+                "auth.refresh_token_ttl",
+                "must be greater than auth.token_ttl".into(),
+            ));
+        }
+        if self.auth.verification_token_ttl <= 0 || self.auth.reset_token_ttl <= 0 {
+            return Err(ConfigError::InvalidValue(
+                "auth",
+                "verification_token_ttl and reset_token_ttl must be greater than 0".into(),
+            ));
+        }
+        if self.auth.login_max_failures <= 0
+            || self.auth.login_ip_max_failures <= 0
+            || self.auth.login_lockout_secs <= 0
+        {
+            return Err(ConfigError::InvalidValue(
+                "auth",
+                "login_max_failures, login_ip_max_failures and login_lockout_secs must be greater than 0"
+                    .into(),
+            ));
+        }
+        if self.auth.login_delay_base_ms < 0 || self.auth.login_delay_max_ms < 0 {
+            return Err(ConfigError::InvalidValue(
+                "auth",
+                "login_delay_base_ms and login_delay_max_ms must not be negative".into(),
+            ));
+        }
+        if self.auth.two_factor_challenge_ttl <= 0 {
+            return Err(ConfigError::InvalidValue(
+                "auth.two_factor_challenge_ttl",
+                "must be greater than 0".into(),
+            ));
+        }
+        if self.auth.totp_issuer.is_empty() || self.auth.totp_issuer.contains(':') {
+            return Err(ConfigError::InvalidValue(
+                "auth.totp_issuer",
+                "must be set and must not contain ':'".into(),
+            ));
+        }
+        if !(1..=255).contains(&self.validation.title_max_length) {
+            return Err(ConfigError::InvalidValue(
+                "validation.title_max_length",
+                "must be between 1 and 255".into(),
+            ));
+        }
+        if self.validation.content_max_length == 0
+            || self.validation.comment_max_length == 0
+            || self.validation.tag_max_length == 0
+        {
+            return Err(ConfigError::InvalidValue(
+                "validation",
+                "content_max_length, comment_max_length and tag_max_length must be greater than 0"
+                    .into(),
+            ));
+        }
+        if self.content_filter.backend == ContentFilterBackend::Http {
+            if self.content_filter.api_url.is_empty() {
+                return Err(ConfigError::InvalidValue(
+                    "content_filter.api_url",
+                    "must be set for the http backend".into(),
+                ));
+            }
+            if self.content_filter.timeout_ms == 0 || self.content_filter.breaker_threshold == 0 {
+                return Err(ConfigError::InvalidValue(
+                    "content_filter",
+                    "timeout_ms and breaker_threshold must be greater than 0".into(),
+                ));
+            }
+        }
+        if self.trash.purge_interval_secs == 0 {
+            return Err(ConfigError::InvalidValue(
+                "trash.purge_interval_secs",
+                "must be greater than 0".into(),
+            ));
+        }
+        if let Err(e) = self.mail.from.parse::<Mailbox>() {
+            return Err(ConfigError::InvalidValue("mail.from", e.to_string()));
+        }
+        if self.mail.transport == MailTransport::Smtp && self.mail.smtp_host.is_empty() {
+            return Err(ConfigError::InvalidValue(
+                "mail.smtp_host",
+                "must be set for the smtp transport".into(),
+            ));
+        }
+        if self.rate_limit.backend == RateLimitBackend::Postgres
+            && self.database.backend != StorageBackend::Postgres
+        {
+            return Err(ConfigError::InvalidValue(
+                "rate_limit.backend",
+                "postgres needs the postgres database backend".into(),
+            ));
+        }
+        if self.rate_limit.requests == 0
+            || self.rate_limit.window_secs == 0
+            || self.rate_limit.failed_auth_requests == 0
+            || self.rate_limit.failed_auth_window_secs == 0
+            || self.rate_limit.purge_interval_secs == 0
+        {
+            return Err(ConfigError::InvalidValue(
+                "rate_limit",
+                "requests, window_secs, failed_auth_requests, failed_auth_window_secs and purge_interval_secs must be greater than 0".into(),
+            ));
+        }
+        for route in &self.rate_limit.routes {
+            if !route.path.starts_with('/') {
+                return Err(ConfigError::InvalidValue(
+                    "rate_limit.routes",
+                    format!("path {:?} must start with '/'", route.path),
+                ));
+            }
+            if let Some(method) = &route.method {
+                if method.parse::<Method>().is_err() {
+                    return Err(ConfigError::InvalidValue(
+                        "rate_limit.routes",
+                        format!("invalid method {:?}", method),
+                    ));
+                }
+            }
+            if route.requests == 0 || route.window_secs == 0 {
+                return Err(ConfigError::InvalidValue(
+                    "rate_limit.routes",
+                    format!(
+                        "requests and window_secs of {} must be greater than 0",
+                        route.path
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn listen_addr(&self) -> String {
+        format!("{}:{}", self.server.host, self.server.port)
+    }
+}
+
+fn override_from_env<T: FromStr>(
+    vars: &dyn Fn(&str) -> Option<String>,
+    name: &str,
+    target: &mut T,
+) -> Result<(), ConfigError> {
+    let var = format!("{}{}", ENV_PREFIX, name);
+    if let Some(value) = vars(&var) {
+        *target = value
+            .parse()
+            .map_err(|_| ConfigError::InvalidEnvVar(var, value))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    const TOKEN_KEY: &str = "0123456789abcdef0123456789abcdef";
+
+    fn valid_config() -> Config {
+        let mut config = Config::default();
+        config.database.url = String::from("postgres://localhost/test");
+        config.auth.token_key = String::from(TOKEN_KEY);
+        config
+    }
+
+    fn invalid_key(config: &Config) -> Option<&'static str> {
+        match config.validate() {
+            Err(ConfigError::InvalidValue(key, _)) => Some(key),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn env_overrides_file_and_flags_override_env() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [server]
+            host = "127.0.0.1"
+            port = 1000
+
+            [auth]
+            token_ttl = 60
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.server.host, "127.0.0.1");
+        assert_eq!(config.database.max_connections, 5);
+
+        let vars = HashMap::from([("APP_PORT", "2000"), ("APP_TOKEN_TTL", "120")]);
+        config
+            .apply_vars(&|var| vars.get(var).map(|value| value.to_string()))
+            .unwrap();
+        config.apply_args(Args::try_parse_from(["axum-web-demo", "--port", "3000"]).unwrap());
+
+        assert_eq!(config.server.host, "127.0.0.1");
+        assert_eq!(config.server.port, 3000);
+        assert_eq!(config.auth.token_ttl, 120);
+    }
+
+    #[test]
+    fn unparsable_env_vars_are_reported() {
+        let mut config = Config::default();
+        let result = config.apply_vars(&|var| (var == "APP_PORT").then(|| String::from("http")));
+        assert!(matches!(
+            result,
+            Err(ConfigError::InvalidEnvVar(var, value)) if var == "APP_PORT" && value == "http"
+        ));
+    }
+
+    #[test]
+    fn token_key_is_not_read_from_the_file() {
+        let result = toml::from_str::<Config>(&format!("[auth]\ntoken_key = \"{}\"", TOKEN_KEY));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn token_key_must_be_a_private_32_byte_key() {
+        assert!(valid_config().validate().is_ok());
+
+        let mut config = valid_config();
+        config.auth.token_key = String::new();
+        assert_eq!(invalid_key(&config), Some("auth.token_key"));
+        config.auth.token_key = String::from("RANDOM WORDS WINTER MACINTOSH PC");
+        assert_eq!(invalid_key(&config), Some("auth.token_key"));
+        config.auth.token_key = String::from("too short");
+        assert_eq!(invalid_key(&config), Some("auth.token_key"));
+    }
+
+    #[test]
+    fn validate_checks_dependent_settings() {
+        let mut config = valid_config();
+        config.database.url = String::new();
+        assert_eq!(invalid_key(&config), Some("database.url"));
+
+        let mut config = valid_config();
+        config.auth.refresh_token_ttl = config.auth.token_ttl;
+        assert_eq!(invalid_key(&config), Some("auth.refresh_token_ttl"));
+
+        let mut config = valid_config();
+        config.server.trust_forwarded_for = true;
+        config.server.trusted_proxy_hops = 0;
+        assert_eq!(invalid_key(&config), Some("server.trusted_proxy_hops"));
+
+        let mut config = valid_config();
+        config.database.backend = StorageBackend::Memory;
+        config.rate_limit.backend = RateLimitBackend::Postgres;
+        assert_eq!(invalid_key(&config), Some("rate_limit.backend"));
+
+        let mut config = valid_config();
+        config.rate_limit.routes[0].method = Some(String::from("GET POST"));
+        assert_eq!(invalid_key(&config), Some("rate_limit.routes"));
+    }
+
+    #[test]
+    fn admin_emails_ignore_case_and_spaces() {
+        let config = AuthConfig {
+            admin_emails: vec![String::from(" Boss@Example.com")],
+            ..AuthConfig::default()
+        };
+        assert!(config.is_admin_email("boss@example.COM "));
+        assert!(!config.is_admin_email("other@example.com"));
+    }
+}
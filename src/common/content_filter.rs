@@ -0,0 +1,513 @@
+use std::{
+    collections::HashSet,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tracing::{event, Level};
+
+use crate::common::{
+    config::{ContentFilterBackend, ContentFilterConfig, FilterMode},
+    error::{Error, FieldError},
+};
+
+/// Result of screening a piece of text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screened {
+    /// The text with offending words masked
+    pub censored: String,
+    pub flagged: bool,
+}
+
+#[async_trait]
+pub trait ContentFilter: Debug + Send + Sync {
+    async fn screen(&self, text: &str) -> Result<Screened, Error>;
+}
+
+/// Filter plus what to do with flagged text, shared by the post handlers.
+#[derive(Debug, Clone)]
+pub struct ContentPolicy {
+    filter: Arc<dyn ContentFilter>,
+    mode: FilterMode,
+}
+
+impl ContentPolicy {
+    pub fn new(filter: Arc<dyn ContentFilter>, mode: FilterMode) -> Self {
+        ContentPolicy { filter, mode }
+    }
+
+    pub fn from_config(config: &ContentFilterConfig) -> Self {
+        let word_list = WordListFilter::new(&config.words);
+        let filter: Arc<dyn ContentFilter> = match config.backend {
+            ContentFilterBackend::WordList => Arc::new(word_list),
+            ContentFilterBackend::Http => Arc::new(HttpFilter::new(
+                config,
+                config.fallback_to_word_list.then_some(word_list),
+            )),
+        };
+        ContentPolicy::new(filter, config.mode)
+    }
+
+    /// Returns the text to store for `field`, censored or rejected per mode.
+    pub async fn apply(&self, field: &str, text: String) -> Result<String, Error> {
+        let screened = self.filter.screen(&text).await?;
+        if !screened.flagged {
+            return Ok(text);
+        }
+        match self.mode {
+            FilterMode::Censor => Ok(screened.censored),
+            FilterMode::Reject => Err(Error::ValidationFailed(vec![FieldError::new(
+                field,
+                "contains inappropriate language",
+            )])),
+        }
+    }
+}
+
+/// Masks whole words found in a fixed list, ignoring case.
+#[derive(Debug, Clone)]
+pub struct WordListFilter {
+    words: HashSet<String>,
+}
+
+impl WordListFilter {
+    pub fn new(words: &[String]) -> Self {
+        WordListFilter {
+            words: words.iter().map(|word| word.to_lowercase()).collect(),
+        }
+    }
+
+    fn censor(&self, text: &str) -> Screened {
+        let mut censored = String::with_capacity(text.len());
+        let mut flagged = false;
+        let mut word = String::new();
+        let mut flush = |word: &mut String, censored: &mut String| {
+            if self.words.contains(&word.to_lowercase()) {
+                flagged = true;
+                censored.extend(word.chars().map(|_| '*'));
+            } else {
+                censored.push_str(word);
+            }
+            word.clear();
+        };
+        for c in text.chars() {
+            if c.is_alphanumeric() || c == '\'' {
+                word.push(c);
+            } else {
+                flush(&mut word, &mut censored);
+                censored.push(c);
+            }
+        }
+        flush(&mut word, &mut censored);
+
+        Screened { censored, flagged }
+    }
+}
+
+#[async_trait]
+impl ContentFilter for WordListFilter {
+    async fn screen(&self, text: &str) -> Result<Screened, Error> {
+        Ok(self.censor(text))
+    }
+}
+
+/// Client for an APILayer style bad words API: the text is POSTed as the
+/// body and the answer carries `bad_words_total` and `censored_content`.
+#[derive(Debug)]
+pub struct HttpFilter {
+    client: reqwest::Client,
+    url: String,
+    api_key: String,
+    retries: u32,
+    breaker: CircuitBreaker,
+    /// Used while the API is failing, instead of failing the request
+    fallback: Option<WordListFilter>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BadWordsResponse {
+    bad_words_total: i64,
+    censored_content: String,
+}
+
+impl HttpFilter {
+    pub fn new(config: &ContentFilterConfig, fallback: Option<WordListFilter>) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()
+            .expect("Cannot build content filter HTTP client");
+        HttpFilter {
+            client,
+            url: config.api_url.clone(),
+            api_key: config.api_key.clone(),
+            retries: config.retries,
+            breaker: CircuitBreaker::new(
+                config.breaker_threshold,
+                Duration::from_secs(config.breaker_cooldown_secs),
+            ),
+            fallback,
+        }
+    }
+
+    async fn call(&self, text: &str) -> Result<Screened, reqwest::Error> {
+        let mut request = self.client.post(&self.url).body(text.to_string());
+        if !self.api_key.is_empty() {
+            request = request.header("apikey", &self.api_key);
+        }
+        let res: BadWordsResponse = request.send().await?.error_for_status()?.json().await?;
+
+        Ok(Screened {
+            censored: res.censored_content,
+            flagged: res.bad_words_total > 0,
+        })
+    }
+
+    /// Retries timeouts, connection errors and 5xx answers with a short
+    /// linear backoff, other failures are returned right away.
+    async fn call_with_retries(&self, text: &str) -> Result<Screened, reqwest::Error> {
+        let mut attempt = 0;
+        loop {
+            match self.call(text).await {
+                Ok(screened) => return Ok(screened),
+                Err(e) if attempt < self.retries && is_transient(&e) => {
+                    attempt += 1;
+                    event!(target:"axum-web-dev", Level::WARN, attempt, "content filter call failed: {}", e);
+                    tokio::time::sleep(Duration::from_millis(100 * attempt as u64)).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ContentFilter for HttpFilter {
+    async fn screen(&self, text: &str) -> Result<Screened, Error> {
+        if self.breaker.allow() {
+            match self.call_with_retries(text).await {
+                Ok(screened) => {
+                    self.breaker.record_success();
+                    return Ok(screened);
+                }
+                Err(e) => {
+                    self.breaker.record_failure();
+                    event!(target:"axum-web-dev", Level::ERROR, "content filter unavailable: {}", e);
+                    if self.fallback.is_none() {
+                        return Err(Error::ExternalAPIError(e));
+                    }
+                }
+            }
+        }
+        match &self.fallback {
+            Some(fallback) => fallback.screen(text).await,
+            None => Err(Error::ExternalAPIUnavailable),
+        }
+    }
+}
+
+fn is_transient(error: &reqwest::Error) -> bool {
+    error.is_timeout()
+        || error.is_connect()
+        || error
+            .status()
+            .is_some_and(|status| status.is_server_error())
+}
+
+/// Stops calling a failing API for `cooldown` after `threshold` consecutive
+/// failures. Once the cooldown is over calls go through again, the next
+/// failure reopens the circuit immediately.
+#[derive(Debug)]
+struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    fn new(threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            threshold,
+            cooldown,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, BreakerState> {
+        self.state.lock().expect("circuit breaker lock poisoned")
+    }
+
+    fn allow(&self) -> bool {
+        let state = self.state();
+        state
+            .open_until
+            .is_none_or(|open_until| Instant::now() >= open_until)
+    }
+
+    fn record_success(&self) {
+        *self.state() = BreakerState::default();
+    }
+
+    fn record_failure(&self) {
+        let mut state = self.state();
+        state.failures += 1;
+        if state.failures >= self.threshold {
+            state.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use axum::{
+        extract::State,
+        http::StatusCode,
+        response::{IntoResponse, Response},
+        routing::post,
+        Json, Router,
+    };
+
+    use super::*;
+
+    #[derive(Debug, Clone, Copy)]
+    enum Reply {
+        Clean,
+        Flagged,
+        ServerError,
+        BadRequest,
+        Slow,
+    }
+
+    /// Stands in for the bad words API, answering with `replies` in order
+    /// and repeating the last one.
+    #[derive(Debug)]
+    struct MockApi {
+        calls: AtomicUsize,
+        replies: Mutex<VecDeque<Reply>>,
+    }
+
+    impl MockApi {
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+
+        fn next_reply(&self) -> Reply {
+            let mut replies = self.replies.lock().unwrap();
+            match replies.len() {
+                1 => replies[0],
+                _ => replies.pop_front().expect("no reply scripted"),
+            }
+        }
+    }
+
+    async fn bad_words(State(api): State<Arc<MockApi>>, text: String) -> Response {
+        api.calls.fetch_add(1, Ordering::SeqCst);
+        let flagged = |text: String| {
+            Json(serde_json::json!({
+                "bad_words_total": 1,
+                "censored_content": text.replace("darn", "****"),
+            }))
+        };
+        match api.next_reply() {
+            Reply::Clean => Json(serde_json::json!({
+                "bad_words_total": 0,
+                "censored_content": text,
+            }))
+            .into_response(),
+            Reply::Flagged => flagged(text).into_response(),
+            Reply::ServerError => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            Reply::BadRequest => StatusCode::BAD_REQUEST.into_response(),
+            Reply::Slow => {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                flagged(text).into_response()
+            }
+        }
+    }
+
+    async fn mock_api(replies: &[Reply]) -> (Arc<MockApi>, String) {
+        let api = Arc::new(MockApi {
+            calls: AtomicUsize::new(0),
+            replies: Mutex::new(replies.iter().copied().collect()),
+        });
+        let app = Router::new()
+            .route("/bad_words", post(bad_words))
+            .with_state(api.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/bad_words", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (api, url)
+    }
+
+    fn http_filter(url: &str, retries: u32, fallback: Option<WordListFilter>) -> HttpFilter {
+        let config = ContentFilterConfig {
+            backend: ContentFilterBackend::Http,
+            api_url: url.to_string(),
+            timeout_ms: 200,
+            retries,
+            breaker_threshold: 2,
+            ..ContentFilterConfig::default()
+        };
+        HttpFilter::new(&config, fallback)
+    }
+
+    fn word_list() -> WordListFilter {
+        WordListFilter::new(&[String::from("darn")])
+    }
+
+    #[tokio::test]
+    async fn censor_mode_masks_flagged_words() {
+        let (_, url) = mock_api(&[Reply::Flagged]).await;
+        let policy = ContentPolicy::new(Arc::new(http_filter(&url, 0, None)), FilterMode::Censor);
+
+        let text = policy.apply("content", String::from("well darn it")).await;
+        assert_eq!(text.unwrap(), "well **** it");
+    }
+
+    #[tokio::test]
+    async fn reject_mode_refuses_flagged_text() {
+        let (_, url) = mock_api(&[Reply::Flagged]).await;
+        let policy = ContentPolicy::new(Arc::new(http_filter(&url, 0, None)), FilterMode::Reject);
+
+        match policy.apply("content", String::from("well darn it")).await {
+            Err(Error::ValidationFailed(errors)) => assert_eq!(errors[0].field, "content"),
+            res => panic!("expected a validation error, got {:?}", res),
+        }
+    }
+
+    #[tokio::test]
+    async fn clean_text_is_kept_as_sent() {
+        let (_, url) = mock_api(&[Reply::Clean]).await;
+        let policy = ContentPolicy::new(Arc::new(http_filter(&url, 0, None)), FilterMode::Reject);
+
+        let text = policy.apply("content", String::from("all fine")).await;
+        assert_eq!(text.unwrap(), "all fine");
+    }
+
+    #[tokio::test]
+    async fn retries_transient_failures() {
+        let (api, url) = mock_api(&[Reply::ServerError, Reply::ServerError, Reply::Clean]).await;
+        let filter = http_filter(&url, 2, None);
+
+        assert!(!filter.screen("all fine").await.unwrap().flagged);
+        assert_eq!(api.calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_configured_retries() {
+        let (api, url) = mock_api(&[Reply::ServerError]).await;
+        let filter = http_filter(&url, 2, None);
+
+        let res = filter.screen("all fine").await;
+        assert!(matches!(res, Err(Error::ExternalAPIError(_))));
+        assert_eq!(api.calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let (api, url) = mock_api(&[Reply::BadRequest]).await;
+        let filter = http_filter(&url, 2, None);
+
+        let res = filter.screen("all fine").await;
+        assert!(matches!(res, Err(Error::ExternalAPIError(_))));
+        assert_eq!(api.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn slow_answers_time_out() {
+        let (api, url) = mock_api(&[Reply::Slow]).await;
+        let filter = http_filter(&url, 0, None);
+
+        let started = Instant::now();
+        match filter.screen("well darn it").await {
+            Err(Error::ExternalAPIError(e)) => assert!(e.is_timeout()),
+            res => panic!("expected a timeout, got {:?}", res),
+        }
+        assert!(started.elapsed() < Duration::from_millis(450));
+        assert_eq!(api.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn breaker_opens_after_consecutive_failures() {
+        let (api, url) = mock_api(&[Reply::ServerError]).await;
+        let filter = http_filter(&url, 0, None);
+
+        for _ in 0..2 {
+            let res = filter.screen("all fine").await;
+            assert!(matches!(res, Err(Error::ExternalAPIError(_))));
+        }
+        let res = filter.screen("all fine").await;
+        assert!(matches!(res, Err(Error::ExternalAPIUnavailable)));
+        assert_eq!(api.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn breaker_half_opens_after_the_cooldown() {
+        let (api, url) = mock_api(&[Reply::ServerError, Reply::ServerError, Reply::Clean]).await;
+        let mut filter = http_filter(&url, 0, None);
+        filter.breaker = CircuitBreaker::new(2, Duration::from_millis(200));
+
+        for _ in 0..2 {
+            assert!(filter.screen("all fine").await.is_err());
+        }
+        assert!(matches!(
+            filter.screen("all fine").await,
+            Err(Error::ExternalAPIUnavailable)
+        ));
+        assert_eq!(api.calls(), 2);
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert!(!filter.screen("all fine").await.unwrap().flagged);
+        assert_eq!(api.calls(), 3);
+        // closed again, calls keep going through
+        assert!(filter.screen("all fine").await.is_ok());
+        assert_eq!(api.calls(), 4);
+    }
+
+    #[tokio::test]
+    async fn failing_trial_call_reopens_the_breaker() {
+        let (api, url) = mock_api(&[Reply::ServerError]).await;
+        let mut filter = http_filter(&url, 0, None);
+        filter.breaker = CircuitBreaker::new(2, Duration::from_millis(200));
+
+        for _ in 0..2 {
+            assert!(filter.screen("all fine").await.is_err());
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert!(matches!(
+            filter.screen("all fine").await,
+            Err(Error::ExternalAPIError(_))
+        ));
+        assert!(matches!(
+            filter.screen("all fine").await,
+            Err(Error::ExternalAPIUnavailable)
+        ));
+        assert_eq!(api.calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_word_list() {
+        let (api, url) = mock_api(&[Reply::ServerError]).await;
+        let filter = http_filter(&url, 0, Some(word_list()));
+
+        // failing calls and an open breaker both end up at the word list
+        for _ in 0..3 {
+            let screened = filter.screen("well darn it").await.unwrap();
+            assert!(screened.flagged);
+            assert_eq!(screened.censored, "well **** it");
+        }
+        assert_eq!(api.calls(), 2);
+    }
+}
@@ -1,66 +1,303 @@
-use std::fmt::write;
-
 use argon2::Error as ArgonError;
-use axum::{response::IntoResponse, Json};
-use reqwest::{Error as ReqwestError, StatusCode};
-use serde_json::json;
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use reqwest::Error as ReqwestError;
+use serde::Serialize;
+use tracing::{event, Level};
+
+use crate::common::request_id;
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
-    ParseError(std::num::ParseIntError),
     MissingParameters,
+    InvalidParameter(String),
+    InvalidBody(String),
+    ValidationFailed(Vec<FieldError>),
     QuestionNotFound,
+    AnswerNotFound,
+    CommentNotFound,
+    AccountNotFound,
+    RevisionNotFound,
+    TagNotFound,
+    ApiKeyNotFound,
     DatabaseQueryError(sqlx::Error),
     ExternalAPIError(ReqwestError),
+    ExternalAPIUnavailable,
     WrongPassword,
-    Unahthorized,
+    Forbidden,
     ArgonLibraryError(ArgonError),
     CannotDecryptToken,
+    AccountAlreadyExists,
+    CannotVoteOwnPost,
+    InvalidOneTimeToken,
+    EmailNotVerified,
+    MailDeliveryFailed,
+    InvalidTwoFactorCode,
+    TwoFactorRequired,
+    InsufficientScope,
+    /// Seconds until the next attempt is allowed
+    TooManyAttempts(u64),
+    RateLimited(u64),
+}
+
+/// Why a single request field was rejected.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        FieldError {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// RFC 7807 problem details. `code` is stable, clients should match on it
+/// rather than on `title` or `detail`.
+#[derive(Debug, Serialize)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: &'static str,
+    pub status: u16,
+    pub detail: String,
+    pub code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &*self {
-            Error::ParseError(ref err) => {
-                write!(f, "Cannot parse parameter: {}", err)
-            }
+        match self {
             Error::MissingParameters => write!(f, "Missing parameters"),
+            Error::InvalidParameter(reason) => write!(f, "Invalid parameter: {}", reason),
+            Error::InvalidBody(reason) => write!(f, "Invalid request body: {}", reason),
+            Error::ValidationFailed(errors) => {
+                let fields: Vec<String> = errors
+                    .iter()
+                    .map(|e| format!("{} {}", e.field, e.message))
+                    .collect();
+                write!(f, "Validation failed: {}", fields.join(", "))
+            }
             Error::WrongPassword => write!(f, "Wrong password"),
             Error::ArgonLibraryError(_) => write!(f, "Cannot verify password"),
             Error::QuestionNotFound => write!(f, "Question not found"),
+            Error::AnswerNotFound => write!(f, "Answer not found"),
+            Error::CommentNotFound => write!(f, "Comment not found"),
+            Error::AccountNotFound => write!(f, "Account not found"),
+            Error::RevisionNotFound => write!(f, "Revision not found"),
+            Error::TagNotFound => write!(f, "Tag not found"),
+            Error::ApiKeyNotFound => write!(f, "API key not found"),
             Error::DatabaseQueryError(_) => write!(f, "Cannot update, invalid data."),
             Error::ExternalAPIError(err) => {
                 write!(f, "Cannot execute: {}", err)
             }
+            Error::ExternalAPIUnavailable => write!(f, "External API temporarily unavailable"),
             Error::CannotDecryptToken => write!(f, "Invalid token"),
-            Error::Unahthorized => write!(f, "No resource permission"),
+            Error::Forbidden => write!(f, "No resource permission"),
+            Error::AccountAlreadyExists => write!(f, "Account already exists"),
+            Error::CannotVoteOwnPost => write!(f, "Cannot vote on your own post"),
+            Error::InvalidOneTimeToken => write!(f, "Token is invalid, expired or already used"),
+            Error::EmailNotVerified => write!(f, "Email address is not verified"),
+            Error::MailDeliveryFailed => write!(f, "Cannot send mail"),
+            Error::InvalidTwoFactorCode => write!(f, "Invalid two-factor code"),
+            Error::TwoFactorRequired => write!(f, "Two-factor authentication required"),
+            Error::InsufficientScope => write!(f, "API key scope does not allow this request"),
+            Error::TooManyAttempts(secs) => {
+                write!(f, "Too many failed attempts, retry in {} seconds", secs)
+            }
+            Error::RateLimited(secs) => {
+                write!(f, "Rate limit exceeded, retry in {} seconds", secs)
+            }
         }
     }
 }
 
-impl IntoResponse for Error {
-    fn into_response(self) -> axum::response::Response {
-        let (status, err_msg) = match self {
-            Self::DatabaseQueryError(_) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, "internal server error")
+impl Error {
+    /// Status code, stable machine readable code and short title.
+    fn classify(&self) -> (StatusCode, &'static str, &'static str) {
+        match self {
+            Self::DatabaseQueryError(sqlx::Error::RowNotFound) => {
+                (StatusCode::NOT_FOUND, "not_found", "Resource not found")
             }
-            Self::MissingParameters => (StatusCode::BAD_REQUEST, "Missing parameters"),
-            Self::WrongPassword => (StatusCode::UNAUTHORIZED, "Invalid user name or password"),
-            Self::ArgonLibraryError(_) => {
-                (StatusCode::UNAUTHORIZED, "Invalid user name or password")
+            Self::DatabaseQueryError(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "Internal server error",
+            ),
+            Self::MissingParameters => (
+                StatusCode::BAD_REQUEST,
+                "missing_parameters",
+                "Missing parameters",
+            ),
+            Self::InvalidParameter(_) => (
+                StatusCode::BAD_REQUEST,
+                "invalid_parameter",
+                "Invalid parameter",
+            ),
+            Self::InvalidBody(_) => (
+                StatusCode::BAD_REQUEST,
+                "invalid_body",
+                "Invalid request body",
+            ),
+            Self::ValidationFailed(_) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "validation_failed",
+                "Validation failed",
+            ),
+            Self::WrongPassword | Self::ArgonLibraryError(_) => (
+                StatusCode::UNAUTHORIZED,
+                "invalid_credentials",
+                "Invalid user name or password",
+            ),
+            Self::QuestionNotFound => (
+                StatusCode::NOT_FOUND,
+                "question_not_found",
+                "Question not found",
+            ),
+            Self::AnswerNotFound => (
+                StatusCode::NOT_FOUND,
+                "answer_not_found",
+                "Answer not found",
+            ),
+            Self::CommentNotFound => (
+                StatusCode::NOT_FOUND,
+                "comment_not_found",
+                "Comment not found",
+            ),
+            Self::AccountNotFound => (
+                StatusCode::NOT_FOUND,
+                "account_not_found",
+                "Account not found",
+            ),
+            Self::RevisionNotFound => (
+                StatusCode::NOT_FOUND,
+                "revision_not_found",
+                "Revision not found",
+            ),
+            Self::TagNotFound => (StatusCode::NOT_FOUND, "tag_not_found", "Tag not found"),
+            Self::ApiKeyNotFound => (
+                StatusCode::NOT_FOUND,
+                "api_key_not_found",
+                "API key not found",
+            ),
+            Self::ExternalAPIError(_) => (
+                StatusCode::BAD_GATEWAY,
+                "external_api_error",
+                "External API call error",
+            ),
+            Self::ExternalAPIUnavailable => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "external_api_unavailable",
+                "External API temporarily unavailable",
+            ),
+            Self::CannotDecryptToken => {
+                (StatusCode::UNAUTHORIZED, "invalid_token", "Invalid token")
             }
+            Self::Forbidden => (StatusCode::FORBIDDEN, "forbidden", "No resource permission"),
+            Self::AccountAlreadyExists => (
+                StatusCode::CONFLICT,
+                "account_already_exists",
+                "Account already exists",
+            ),
+            Self::CannotVoteOwnPost => (
+                StatusCode::FORBIDDEN,
+                "cannot_vote_own_post",
+                "Cannot vote on your own post",
+            ),
+            Self::InvalidOneTimeToken => (
+                StatusCode::BAD_REQUEST,
+                "invalid_one_time_token",
+                "Invalid or expired token",
+            ),
+            Self::EmailNotVerified => (
+                StatusCode::FORBIDDEN,
+                "email_not_verified",
+                "Email address is not verified",
+            ),
+            Self::MailDeliveryFailed => (
+                StatusCode::BAD_GATEWAY,
+                "mail_delivery_failed",
+                "Cannot send mail",
+            ),
+            Self::InvalidTwoFactorCode => (
+                StatusCode::UNAUTHORIZED,
+                "invalid_two_factor_code",
+                "Invalid two-factor code",
+            ),
+            Self::TwoFactorRequired => (
+                StatusCode::FORBIDDEN,
+                "two_factor_required",
+                "Two-factor authentication required",
+            ),
+            Self::InsufficientScope => (
+                StatusCode::FORBIDDEN,
+                "insufficient_scope",
+                "Insufficient API key scope",
+            ),
+            Self::TooManyAttempts(_) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "too_many_attempts",
+                "Too many failed attempts",
+            ),
+            Self::RateLimited(_) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "rate_limited",
+                "Rate limit exceeded",
+            ),
+        }
+    }
+}
 
-            Self::QuestionNotFound => (StatusCode::BAD_REQUEST, "Question not found"),
-            Self::ExternalAPIError(_err) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, "External API call error")
-            }
-            Self::ParseError(ref err) => {
-                //let e_msg = format!("Cannot parse parameter: {}", err);
-                (StatusCode::BAD_REQUEST, "Parse parameter error")
-            }
-            Self::CannotDecryptToken => (StatusCode::UNAUTHORIZED, "Invalid token"),
-            Self::Unahthorized => (StatusCode::UNAUTHORIZED, "No resource permission"),
+impl IntoResponse for Error {
+    fn into_response(self) -> axum::response::Response {
+        let (status, code, title) = self.classify();
+        let request_id = request_id::current();
+        // internals stay in the log, the id lets support find them
+        let detail = if status.is_server_error() {
+            event!(target:"axum-web-dev", Level::ERROR, request_id, "{:?}", self);
+            String::from(title)
+        } else {
+            self.to_string()
+        };
+        let retry_after = match self {
+            Self::TooManyAttempts(secs) | Self::RateLimited(secs) => Some(secs),
+            _ => None,
         };
-        (status, Json(json!({"error": err_msg}))).into_response()
+        let problem = Problem {
+            problem_type: format!("/problems/{}", code.replace('_', "-")),
+            title,
+            status: status.as_u16(),
+            detail,
+            code,
+            request_id,
+            errors: match self {
+                Self::ValidationFailed(errors) => errors,
+                _ => Vec::new(),
+            },
+        };
+
+        let mut response = (
+            status,
+            [(header::CONTENT_TYPE, "application/problem+json")],
+            Json(problem),
+        )
+            .into_response();
+        if let Some(secs) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
     }
 }
@@ -0,0 +1,59 @@
+use std::marker::PhantomData;
+
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+
+use crate::{
+    common::error::Error,
+    models::account::{Role, Session},
+};
+
+pub trait RequiredRole {
+    const ROLE: Role;
+}
+
+#[derive(Debug)]
+pub struct Moderator;
+
+impl RequiredRole for Moderator {
+    const ROLE: Role = Role::Moderator;
+}
+
+#[derive(Debug)]
+pub struct Admin;
+
+impl RequiredRole for Admin {
+    const ROLE: Role = Role::Admin;
+}
+
+/// Extractor for handlers restricted to a role, e.g. `RequireRole<Admin>`.
+/// Rejects with `Forbidden` when the session role is lower than `R::ROLE`.
+#[derive(Debug)]
+pub struct RequireRole<R: RequiredRole> {
+    pub session: Session,
+    _role: PhantomData<R>,
+}
+
+#[async_trait]
+impl<S, R> FromRequestParts<S> for RequireRole<R>
+where
+    S: Send + Sync,
+    R: RequiredRole,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let session = parts
+            .extensions
+            .get::<Session>()
+            .cloned()
+            .ok_or(Error::CannotDecryptToken)?;
+        if !session.has_role(R::ROLE) {
+            return Err(Error::Forbidden);
+        }
+
+        Ok(RequireRole {
+            session,
+            _role: PhantomData,
+        })
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    fmt::Debug,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use tracing::{event, Level};
+use uuid::Uuid;
+
+use crate::common::{
+    config::{MailConfig, MailTransport},
+    error::Error,
+};
+
+/// Plain text mail to a single recipient.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait Mailer: Debug + Send + Sync {
+    async fn send(&self, email: &Email) -> Result<(), Error>;
+}
+
+pub fn from_config(config: &MailConfig) -> Arc<dyn Mailer> {
+    // Checked by Config::validate
+    let from: Mailbox = config.from.parse().expect("Invalid mail.from");
+    match config.transport {
+        MailTransport::Smtp => Arc::new(SmtpMailer::new(config, from)),
+        MailTransport::File => Arc::new(FileMailer::new(config.file_dir.clone(), from)),
+        MailTransport::Memory => Arc::new(MemoryMailer::default()),
+    }
+}
+
+fn build_message(from: &Mailbox, email: &Email) -> Result<Message, Error> {
+    let to: Mailbox = email.to.parse().map_err(|e| {
+        event!(target: "axum-web-dev", Level::ERROR, "Invalid recipient {:?}: {}", email.to, e);
+        Error::MailDeliveryFailed
+    })?;
+    Message::builder()
+        .from(from.clone())
+        .to(to)
+        .subject(email.subject.clone())
+        .header(ContentType::TEXT_PLAIN)
+        .body(email.body.clone())
+        .map_err(|e| {
+            event!(target: "axum-web-dev", Level::ERROR, "Cannot build mail: {}", e);
+            Error::MailDeliveryFailed
+        })
+}
+
+#[derive(Debug)]
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(config: &MailConfig, from: Mailbox) -> Self {
+        let mut builder = if config.smtp_starttls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host)
+                .expect("Cannot build SMTP transport")
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.smtp_host)
+        }
+        .port(config.smtp_port);
+        if !config.smtp_username.is_empty() {
+            builder = builder.credentials(Credentials::new(
+                config.smtp_username.clone(),
+                config.smtp_password.clone(),
+            ));
+        }
+        SmtpMailer {
+            transport: builder.build(),
+            from,
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: &Email) -> Result<(), Error> {
+        let message = build_message(&self.from, email)?;
+        match self.transport.send(message).await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                event!(target: "axum-web-dev", Level::ERROR, "SMTP delivery failed: {}", e);
+                Err(Error::MailDeliveryFailed)
+            }
+        }
+    }
+}
+
+/// Writes each mail as an `.eml` file, for development and staging.
+#[derive(Debug)]
+pub struct FileMailer {
+    dir: PathBuf,
+    from: Mailbox,
+}
+
+impl FileMailer {
+    pub fn new(dir: PathBuf, from: Mailbox) -> Self {
+        FileMailer { dir, from }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: &Email) -> Result<(), Error> {
+        let message = build_message(&self.from, email)?;
+        let path = self.dir.join(format!(
+            "{}-{}.eml",
+            Utc::now().format("%Y%m%dT%H%M%S"),
+            Uuid::new_v4()
+        ));
+        let written = match tokio::fs::create_dir_all(&self.dir).await {
+            Ok(()) => tokio::fs::write(&path, message.formatted()).await,
+            Err(e) => Err(e),
+        };
+        match written {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                event!(target: "axum-web-dev", Level::ERROR, "Cannot write mail to {}: {}", path.display(), e);
+                Err(Error::MailDeliveryFailed)
+            }
+        }
+    }
+}
+
+/// Keeps sent mails in memory and logs them.
+#[derive(Debug, Default)]
+pub struct MemoryMailer {
+    outbox: Mutex<Vec<Email>>,
+}
+
+impl MemoryMailer {
+    #[allow(dead_code)]
+    pub fn outbox(&self) -> Vec<Email> {
+        self.outbox.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Mailer for MemoryMailer {
+    async fn send(&self, email: &Email) -> Result<(), Error> {
+        event!(target: "axum-web-dev", Level::INFO, "Mail to {}: {}\n{}", email.to, email.subject, email.body);
+        self.outbox.lock().unwrap().push(email.clone());
+        Ok(())
+    }
+}
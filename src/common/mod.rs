@@ -1 +1,12 @@
+pub mod client_ip;
+pub mod config;
+pub mod content_filter;
 pub mod error;
+pub mod extract;
+pub mod guard;
+pub mod mailer;
+pub mod purge;
+pub mod rate_limit;
+pub mod request_id;
+pub mod state;
+pub mod validation;
@@ -0,0 +1,89 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use tokio::{task::JoinHandle, time::MissedTickBehavior};
+use tracing::{event, Level};
+
+use crate::{
+    common::{
+        config::{AuthConfig, RateLimitConfig, TrashConfig},
+        rate_limit::RateLimitStore,
+    },
+    repositories::store::Store,
+};
+
+/// Purges posts that stayed in the trash longer than the retention period,
+/// once at startup and then every `purge_interval_secs`. Failures are logged
+/// and retried on the next run.
+pub fn spawn_purge_job(store: Store, config: TrashConfig) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let retention = chrono::Duration::days(config.retention_days.into());
+        let mut interval = tokio::time::interval(Duration::from_secs(config.purge_interval_secs));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            match store.purge_deleted(Utc::now() - retention).await {
+                Ok(0) => (),
+                Ok(purged) => {
+                    event!(target:"axum-web-dev", Level::INFO, purged, "purged deleted posts");
+                }
+                Err(e) => {
+                    event!(target:"axum-web-dev", Level::ERROR, "cannot purge deleted posts: {}", e);
+                }
+            }
+        }
+    })
+}
+
+/// Drops failed login records that ended their window without a running
+/// lock, every `login_lockout_secs`.
+pub fn spawn_login_attempt_purge(store: Store, config: AuthConfig) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let window = chrono::Duration::seconds(config.login_lockout_secs);
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(config.login_lockout_secs as u64));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            match store.purge_login_attempts(Utc::now() - window).await {
+                Ok(0) => (),
+                Ok(purged) => {
+                    event!(target:"axum-web-dev", Level::INFO, purged, "purged login attempts");
+                }
+                Err(e) => {
+                    event!(target:"axum-web-dev", Level::ERROR, "cannot purge login attempts: {}", e);
+                }
+            }
+        }
+    })
+}
+
+/// Drops rate limit buckets idle for longer than the longest window, every
+/// `purge_interval_secs`.
+pub fn spawn_rate_limit_purge(
+    limits: Arc<dyn RateLimitStore>,
+    config: RateLimitConfig,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let longest_window = config
+            .routes
+            .iter()
+            .map(|route| route.window_secs)
+            .fold(config.window_secs, u64::max);
+        let idle = chrono::Duration::seconds(longest_window as i64);
+        let mut interval = tokio::time::interval(Duration::from_secs(config.purge_interval_secs));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            match limits.purge(Utc::now() - idle).await {
+                Ok(0) => (),
+                Ok(purged) => {
+                    event!(target:"axum-web-dev", Level::INFO, purged, "purged rate limit buckets");
+                }
+                Err(e) => {
+                    event!(target:"axum-web-dev", Level::ERROR, "cannot purge rate limit buckets: {}", e);
+                }
+            }
+        }
+    })
+}
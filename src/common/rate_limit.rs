@@ -0,0 +1,417 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use axum::{
+    body::Body,
+    extract::{MatchedPath, Request, State},
+    http::{HeaderName, HeaderValue, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use tracing::{event, Level};
+
+use crate::{
+    common::{
+        client_ip::client_ip,
+        config::{Config, RateLimitBackend, RateLimitConfig},
+        error::Error,
+    },
+    models::account::Session,
+    repositories::postgres::PgStore,
+};
+
+static RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+static RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+static RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+static RATELIMIT_POLICY: HeaderName = HeaderName::from_static("ratelimit-policy");
+
+/// `requests` per `window_secs`, which is also the burst a full bucket allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    pub requests: u32,
+    pub window_secs: u64,
+}
+
+impl Quota {
+    fn refill_per_sec(&self) -> f64 {
+        self.requests as f64 / self.window_secs as f64
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Bucket {
+    pub tokens: f64,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Outcome of one request against its bucket, in `RateLimit-*` header terms.
+#[derive(Debug, Clone, Copy)]
+pub struct Decision {
+    pub allowed: bool,
+    pub remaining: u32,
+    /// Seconds until the bucket is full again
+    pub reset_secs: u64,
+    /// Seconds until the next request is allowed, 0 when this one was
+    pub retry_after_secs: u64,
+}
+
+impl Bucket {
+    pub fn full(quota: Quota, now: DateTime<Utc>) -> Self {
+        Bucket {
+            tokens: quota.requests as f64,
+            updated_at: now,
+        }
+    }
+
+    /// Refills for the time since the last request, then takes a token when
+    /// one is left. Both stores go through here, so they count alike.
+    pub fn acquire(&mut self, quota: Quota, now: DateTime<Utc>) -> Decision {
+        self.refill(quota, now);
+        let allowed = self.tokens >= 1.0;
+        if allowed {
+            self.tokens -= 1.0;
+        }
+        self.decision(quota, allowed)
+    }
+
+    /// Whether `acquire` would allow a request now, without taking a token.
+    pub fn peek(mut self, quota: Quota, now: DateTime<Utc>) -> Decision {
+        self.refill(quota, now);
+        let allowed = self.tokens >= 1.0;
+        self.decision(quota, allowed)
+    }
+
+    fn refill(&mut self, quota: Quota, now: DateTime<Utc>) {
+        // another instance may run a little ahead of this clock
+        let elapsed = (now - self.updated_at).num_milliseconds().max(0) as f64 / 1000.0;
+        self.tokens = (self.tokens + elapsed * quota.refill_per_sec()).min(quota.requests as f64);
+        self.updated_at = now;
+    }
+
+    fn decision(&self, quota: Quota, allowed: bool) -> Decision {
+        let rate = quota.refill_per_sec();
+        Decision {
+            allowed,
+            remaining: self.tokens.floor() as u32,
+            reset_secs: ((quota.requests as f64 - self.tokens) / rate).ceil() as u64,
+            retry_after_secs: match allowed {
+                true => 0,
+                false => ((1.0 - self.tokens) / rate).ceil().max(1.0) as u64,
+            },
+        }
+    }
+}
+
+#[async_trait]
+pub trait RateLimitStore: Debug + Send + Sync {
+    /// Takes one request from the bucket under `key`, which starts out full.
+    async fn acquire(&self, key: &str, quota: Quota) -> Result<Decision, Error>;
+    /// Same answer as `acquire`, but leaves the bucket as it is.
+    async fn peek(&self, key: &str, quota: Quota) -> Result<Decision, Error>;
+    /// Drops buckets untouched since `before`, they would be full by now.
+    async fn purge(&self, before: DateTime<Utc>) -> Result<u64, Error>;
+}
+
+/// Shares the database with `pg_store` when buckets have to be seen by
+/// every instance.
+pub fn from_config(
+    config: &RateLimitConfig,
+    pg_store: Option<&PgStore>,
+) -> Arc<dyn RateLimitStore> {
+    match (config.backend, pg_store) {
+        (RateLimitBackend::Postgres, Some(pg_store)) => Arc::new(pg_store.clone()),
+        // Checked by Config::validate
+        (RateLimitBackend::Postgres, None) => panic!("Postgres rate limits need a Postgres store"),
+        (RateLimitBackend::Memory, _) => Arc::new(MemoryRateLimitStore::default()),
+    }
+}
+
+/// Buckets of this process only, each instance allows the full quota.
+#[derive(Debug, Default)]
+pub struct MemoryRateLimitStore {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn acquire(&self, key: &str, quota: Quota) -> Result<Decision, Error> {
+        let now = Utc::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets
+            .entry(key.to_string())
+            .or_insert_with(|| Bucket::full(quota, now));
+
+        Ok(bucket.acquire(quota, now))
+    }
+
+    async fn peek(&self, key: &str, quota: Quota) -> Result<Decision, Error> {
+        let now = Utc::now();
+        let buckets = self.buckets.lock().unwrap();
+        let bucket = match buckets.get(key) {
+            Some(bucket) => *bucket,
+            None => Bucket::full(quota, now),
+        };
+
+        Ok(bucket.peek(quota, now))
+    }
+
+    async fn purge(&self, before: DateTime<Utc>) -> Result<u64, Error> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let count = buckets.len();
+        buckets.retain(|_, bucket| bucket.updated_at >= before);
+
+        Ok((count - buckets.len()) as u64)
+    }
+}
+
+/// Throttles requests per client: the account for signed in requests, the
+/// client address otherwise. Runs inside `auth`, so it sees the session.
+pub async fn rate_limit(
+    State(config): State<Arc<Config>>,
+    State(limits): State<Arc<dyn RateLimitStore>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    if !config.rate_limit.enabled {
+        return next.run(req).await;
+    }
+    let client = match req.extensions().get::<Session>() {
+        Some(session) => format!("account:{}", session.account_id.0),
+        None => match client_ip(req.headers(), req.extensions(), &config) {
+            Some(ip) => format!("ip:{}", ip),
+            None => return next.run(req).await,
+        },
+    };
+    let path = match req.extensions().get::<MatchedPath>() {
+        Some(path) => path.as_str(),
+        None => req.uri().path(),
+    };
+    let (policy, quota) = route_quota(&config.rate_limit, req.method(), path);
+
+    let decision = match limits
+        .acquire(&format!("{}|{}", policy, client), quota)
+        .await
+    {
+        Ok(decision) => decision,
+        // better unthrottled than down when the store is unavailable
+        Err(e) => {
+            event!(target:"axum-web-dev", Level::WARN, "rate limit skipped: {}", e);
+            return next.run(req).await;
+        }
+    };
+    let mut response = match decision.allowed {
+        true => next.run(req).await,
+        false => Error::RateLimited(decision.retry_after_secs).into_response(),
+    };
+
+    let headers = response.headers_mut();
+    headers.insert(RATELIMIT_LIMIT.clone(), HeaderValue::from(quota.requests));
+    headers.insert(
+        RATELIMIT_REMAINING.clone(),
+        HeaderValue::from(decision.remaining),
+    );
+    headers.insert(
+        RATELIMIT_RESET.clone(),
+        HeaderValue::from(decision.reset_secs),
+    );
+    if let Ok(value) = HeaderValue::try_from(format!("{};w={}", quota.requests, quota.window_secs))
+    {
+        headers.insert(RATELIMIT_POLICY.clone(), value);
+    }
+    response
+}
+
+/// Refuses to check credentials from an address that had too many rejected
+/// lately, so guessing keys and tokens stays slow and cheap to answer.
+pub async fn check_failed_auth(
+    limits: &dyn RateLimitStore,
+    config: &Config,
+    ip: Option<IpAddr>,
+) -> Result<(), Error> {
+    let Some((key, quota)) = failed_auth_bucket(config, ip) else {
+        return Ok(());
+    };
+    match limits.peek(&key, quota).await {
+        Ok(decision) if !decision.allowed => Err(Error::RateLimited(decision.retry_after_secs)),
+        Ok(_) => Ok(()),
+        Err(e) => {
+            event!(target:"axum-web-dev", Level::WARN, "failed auth limit skipped: {}", e);
+            Ok(())
+        }
+    }
+}
+
+/// Counts a rejected token or API key against the client address.
+pub async fn record_failed_auth(limits: &dyn RateLimitStore, config: &Config, ip: Option<IpAddr>) {
+    if let Some((key, quota)) = failed_auth_bucket(config, ip) {
+        if let Err(e) = limits.acquire(&key, quota).await {
+            event!(target:"axum-web-dev", Level::WARN, "failed auth not counted: {}", e);
+        }
+    }
+}
+
+fn failed_auth_bucket(config: &Config, ip: Option<IpAddr>) -> Option<(String, Quota)> {
+    if !config.rate_limit.enabled {
+        return None;
+    }
+    let quota = Quota {
+        requests: config.rate_limit.failed_auth_requests,
+        window_secs: config.rate_limit.failed_auth_window_secs,
+    };
+    ip.map(|ip| (format!("failed auth|ip:{}", ip), quota))
+}
+
+/// First route policy matching the request, named after it so each policy
+/// counts in buckets of its own, or the default quota.
+fn route_quota(config: &RateLimitConfig, method: &Method, path: &str) -> (String, Quota) {
+    let route = config.routes.iter().find(|route| {
+        route.path == path
+            && route
+                .method
+                .as_deref()
+                .is_none_or(|route_method| route_method.eq_ignore_ascii_case(method.as_str()))
+    });
+    match route {
+        Some(route) => (
+            format!("{} {}", route.method.as_deref().unwrap_or("*"), route.path),
+            Quota {
+                requests: route.requests,
+                window_secs: route.window_secs,
+            },
+        ),
+        None => (
+            String::from("*"),
+            Quota {
+                requests: config.requests,
+                window_secs: config.window_secs,
+            },
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::common::config::RoutePolicy;
+
+    /// Three requests per minute, one token back every 20 seconds.
+    const QUOTA: Quota = Quota {
+        requests: 3,
+        window_secs: 60,
+    };
+
+    #[test]
+    fn buckets_run_dry_after_their_burst() {
+        let now = Utc::now();
+        let mut bucket = Bucket::full(QUOTA, now);
+        let remaining: Vec<u32> = (0..3)
+            .map(|_| {
+                let decision = bucket.acquire(QUOTA, now);
+                assert!(decision.allowed);
+                assert_eq!(decision.retry_after_secs, 0);
+                decision.remaining
+            })
+            .collect();
+        assert_eq!(remaining, [2, 1, 0]);
+
+        let decision = bucket.acquire(QUOTA, now);
+        assert!(!decision.allowed);
+        assert_eq!(decision.remaining, 0);
+        assert_eq!(decision.retry_after_secs, 20);
+        assert_eq!(decision.reset_secs, 60);
+    }
+
+    #[test]
+    fn buckets_refill_over_time_up_to_the_quota() {
+        let now = Utc::now();
+        let mut bucket = Bucket::full(QUOTA, now);
+        for _ in 0..3 {
+            bucket.acquire(QUOTA, now);
+        }
+
+        let later = now + Duration::seconds(10);
+        let decision = bucket.acquire(QUOTA, later);
+        assert!(!decision.allowed);
+        assert_eq!(decision.retry_after_secs, 10);
+
+        let later = now + Duration::seconds(20);
+        assert!(bucket.acquire(QUOTA, later).allowed);
+
+        let much_later = later + Duration::hours(1);
+        let decision = bucket.acquire(QUOTA, much_later);
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 2);
+    }
+
+    #[test]
+    fn clocks_running_behind_do_not_drain_buckets() {
+        let now = Utc::now();
+        let mut bucket = Bucket::full(QUOTA, now);
+        bucket.acquire(QUOTA, now);
+        let decision = bucket.acquire(QUOTA, now - Duration::seconds(30));
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 1);
+    }
+
+    #[test]
+    fn peeking_takes_no_token() {
+        let now = Utc::now();
+        let mut bucket = Bucket::full(QUOTA, now);
+        bucket.acquire(QUOTA, now);
+        bucket.acquire(QUOTA, now);
+        for _ in 0..5 {
+            let decision = bucket.peek(QUOTA, now);
+            assert!(decision.allowed);
+            assert_eq!(decision.remaining, 1);
+        }
+        bucket.acquire(QUOTA, now);
+        assert!(!bucket.peek(QUOTA, now).allowed);
+    }
+
+    #[test]
+    fn routes_use_the_first_matching_policy() {
+        let config = RateLimitConfig {
+            routes: vec![
+                RoutePolicy {
+                    method: Some(String::from("post")),
+                    path: String::from("/api/login"),
+                    requests: 10,
+                    window_secs: 60,
+                },
+                RoutePolicy {
+                    method: None,
+                    path: String::from("/api/login"),
+                    requests: 20,
+                    window_secs: 60,
+                },
+            ],
+            ..RateLimitConfig::default()
+        };
+
+        let (name, quota) = route_quota(&config, &Method::POST, "/api/login");
+        assert_eq!(name, "post /api/login");
+        assert_eq!(quota.requests, 10);
+
+        let (name, quota) = route_quota(&config, &Method::GET, "/api/login");
+        assert_eq!(name, "* /api/login");
+        assert_eq!(quota.requests, 20);
+
+        let (name, quota) = route_quota(&config, &Method::POST, "/api/questions");
+        assert_eq!(name, "*");
+        assert_eq!(
+            quota,
+            Quota {
+                requests: config.requests,
+                window_secs: config.window_secs,
+            }
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use axum::extract::FromRef;
+
+use crate::{
+    common::{
+        config::Config, content_filter::ContentPolicy, mailer::Mailer, rate_limit::RateLimitStore,
+    },
+    repositories::store::Store,
+};
+
+#[derive(Debug, Clone)]
+pub struct AppState {
+    pub store: Store,
+    pub config: Arc<Config>,
+    pub content_policy: ContentPolicy,
+    pub mailer: Arc<dyn Mailer>,
+    pub rate_limits: Arc<dyn RateLimitStore>,
+}
+
+impl FromRef<AppState> for Store {
+    fn from_ref(state: &AppState) -> Self {
+        state.store.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Config> {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.clone()
+    }
+}
+
+impl FromRef<AppState> for ContentPolicy {
+    fn from_ref(state: &AppState) -> Self {
+        state.content_policy.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn Mailer> {
+    fn from_ref(state: &AppState) -> Self {
+        state.mailer.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn RateLimitStore> {
+    fn from_ref(state: &AppState) -> Self {
+        state.rate_limits.clone()
+    }
+}
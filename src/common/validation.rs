@@ -0,0 +1,257 @@
+use std::sync::Arc;
+
+use axum::{
+    async_trait,
+    extract::{rejection::JsonRejection, FromRef, FromRequest, Request},
+    Json,
+};
+use serde::de::DeserializeOwned;
+
+use crate::common::{
+    config::{Config, ValidationConfig},
+    error::{Error, FieldError},
+};
+
+/// Characters, the size of the `accounts.email` column.
+pub const EMAIL_MAX_LENGTH: usize = 255;
+
+/// Request payload rules. Payloads without rules keep the default, so they
+/// still get problem responses for malformed JSON through `ValidJson`.
+pub trait Validate {
+    fn validate(&self, _rules: &ValidationConfig, _checks: &mut Checks) {}
+}
+
+/// Collects every broken rule of a payload, so clients can fix all fields
+/// in one round trip.
+#[derive(Debug, Default)]
+pub struct Checks {
+    errors: Vec<FieldError>,
+}
+
+impl Checks {
+    pub fn check(&mut self, field: &str, valid: bool, message: &str) -> &mut Self {
+        if !valid {
+            self.errors.push(FieldError::new(field, message));
+        }
+        self
+    }
+
+    pub fn not_blank(&mut self, field: &str, value: &str) -> &mut Self {
+        self.check(field, !value.trim().is_empty(), "must not be blank")
+    }
+
+    /// Counts characters rather than bytes, like `VARCHAR(n)` does.
+    pub fn max_length(&mut self, field: &str, value: &str, max: usize) -> &mut Self {
+        let message = format!("must be at most {} characters", max);
+        self.check(field, value.chars().count() <= max, &message)
+    }
+
+    pub fn max_items<T>(&mut self, field: &str, items: &[T], max: usize) -> &mut Self {
+        let message = format!("must have at most {} entries", max);
+        self.check(field, items.len() <= max, &message)
+    }
+
+    pub fn email(&mut self, field: &str, value: &str) -> &mut Self {
+        let valid = match value.split_once('@') {
+            Some((local, domain)) => {
+                !local.is_empty()
+                    && !domain.contains('@')
+                    && domain.contains('.')
+                    && !domain.starts_with('.')
+                    && !domain.ends_with('.')
+                    && !value.chars().any(char::is_whitespace)
+            }
+            None => false,
+        };
+        self.check(field, valid, "must be a valid email address")
+            .max_length(field, value, EMAIL_MAX_LENGTH)
+    }
+
+    pub fn password(&mut self, field: &str, value: &str, rules: &ValidationConfig) -> &mut Self {
+        let message = format!("must be at least {} characters", rules.password_min_length);
+        self.check(
+            field,
+            value.chars().count() >= rules.password_min_length,
+            &message,
+        );
+        if rules.password_require_letter {
+            let has_letter = value.chars().any(char::is_alphabetic);
+            self.check(field, has_letter, "must contain a letter");
+        }
+        if rules.password_require_digit {
+            let has_digit = value.chars().any(|c| c.is_ascii_digit());
+            self.check(field, has_digit, "must contain a digit");
+        }
+        self
+    }
+
+    pub fn finish(self) -> Result<(), Error> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::ValidationFailed(self.errors))
+        }
+    }
+}
+
+/// `Json` extractor that also runs the payload's `Validate` rules, rejecting
+/// with 422 before the handler touches the store.
+#[derive(Debug)]
+pub struct ValidJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+    Arc<Config>: FromRef<S>,
+{
+    type Rejection = Error;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(payload) = match Json::<T>::from_request(req, state).await {
+            Ok(payload) => payload,
+            Err(JsonRejection::JsonDataError(e)) => {
+                return Err(Error::ValidationFailed(vec![FieldError::new(
+                    "body",
+                    e.body_text(),
+                )]))
+            }
+            Err(e) => return Err(Error::InvalidBody(e.body_text())),
+        };
+
+        let config = Arc::<Config>::from_ref(state);
+        let mut checks = Checks::default();
+        payload.validate(&config.validation, &mut checks);
+        checks.finish()?;
+
+        Ok(ValidJson(payload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `(field, message)` of every broken rule, in order.
+    fn broken(checks: &Checks) -> Vec<(&str, &str)> {
+        checks
+            .errors
+            .iter()
+            .map(|e| (e.field.as_str(), e.message.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn blank_values_are_refused() {
+        let mut checks = Checks::default();
+        checks
+            .not_blank("title", " \t\n")
+            .not_blank("content", "")
+            .not_blank("comment", " ok ");
+        assert_eq!(
+            broken(&checks),
+            [
+                ("title", "must not be blank"),
+                ("content", "must not be blank")
+            ]
+        );
+    }
+
+    #[test]
+    fn lengths_count_characters() {
+        let mut checks = Checks::default();
+        checks
+            .max_length("title", "ääää", 4)
+            .max_length("content", "ääääa", 4);
+        assert_eq!(
+            broken(&checks),
+            [("content", "must be at most 4 characters")]
+        );
+
+        let mut checks = Checks::default();
+        checks
+            .max_items("tags", &[1, 2], 2)
+            .max_items("tags", &[1, 2, 3], 2);
+        assert_eq!(broken(&checks), [("tags", "must have at most 2 entries")]);
+    }
+
+    #[test]
+    fn emails_need_a_local_part_and_a_dotted_domain() {
+        let valid = ["ann@example.com", "a.b+c@mail.example.org"];
+        let invalid = [
+            "",
+            "ann",
+            "@example.com",
+            "ann@example",
+            "ann@.example.com",
+            "ann@example.com.",
+            "ann@b@example.com",
+            "ann smith@example.com",
+        ];
+        for value in valid {
+            let mut checks = Checks::default();
+            checks.email("email", value);
+            assert!(checks.errors.is_empty(), "{:?}", value);
+        }
+        for value in invalid {
+            let mut checks = Checks::default();
+            checks.email("email", value);
+            assert_eq!(
+                broken(&checks),
+                [("email", "must be a valid email address")],
+                "{:?}",
+                value
+            );
+        }
+
+        let long = format!("{}@example.com", "a".repeat(EMAIL_MAX_LENGTH));
+        let mut checks = Checks::default();
+        checks.email("email", &long);
+        assert_eq!(
+            broken(&checks),
+            [("email", "must be at most 255 characters")]
+        );
+    }
+
+    #[test]
+    fn passwords_follow_the_configured_rules() {
+        let rules = ValidationConfig::default();
+        let mut checks = Checks::default();
+        checks.password("password", "winter2024", &rules);
+        assert!(checks.errors.is_empty());
+
+        let mut checks = Checks::default();
+        checks.password("password", "!!", &rules);
+        assert_eq!(
+            broken(&checks),
+            [
+                ("password", "must be at least 8 characters"),
+                ("password", "must contain a letter"),
+                ("password", "must contain a digit"),
+            ]
+        );
+
+        let rules = ValidationConfig {
+            password_min_length: 2,
+            password_require_letter: false,
+            password_require_digit: false,
+            ..ValidationConfig::default()
+        };
+        let mut checks = Checks::default();
+        checks.password("password", "!!", &rules);
+        assert!(checks.errors.is_empty());
+    }
+
+    #[test]
+    fn finish_reports_every_broken_rule() {
+        assert!(Checks::default().finish().is_ok());
+
+        let mut checks = Checks::default();
+        checks.not_blank("title", "").email("email", "ann");
+        match checks.finish() {
+            Err(Error::ValidationFailed(errors)) => assert_eq!(errors.len(), 2),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
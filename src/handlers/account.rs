@@ -1,67 +1,334 @@
+use std::sync::Arc;
+
 use argon2::Config;
 use axum::{
     body::Body,
     extract::{Request, State},
+    http::Method,
     middleware::Next,
     response::Response,
-    Json,
+    Extension, Json,
 };
 use chrono::Utc;
-use paseto::v2::local_paseto;
 use rand::Rng;
 use reqwest::header;
 use tracing::{event, Level};
+use uuid::Uuid;
 
 use crate::{
-    common::error::Error,
-    models::account::{self, Account, AccountId, Session},
+    common::{
+        client_ip::{client_ip, ClientIp},
+        config::{AuthConfig, Config as AppConfig},
+        error::Error,
+        extract::{Path, Query},
+        guard::{Admin, Moderator, RequireRole},
+        mailer::Mailer,
+        rate_limit::{check_failed_auth, record_failed_auth, RateLimitStore},
+        validation::ValidJson,
+    },
+    handlers::{
+        api_key::verify_api_key,
+        lockout::{check_login_allowed, clear_login_failures, record_login_failure},
+        two_factor::issue_challenge,
+        verification::send_verification_email,
+    },
+    models::{
+        account::{
+            Account, AccountDeletion, AccountId, AccountInfo, Credentials, EmailChange,
+            PasswordChange, Profile, ProfileUpdate, PublicProfile, Role, RoleUpdate, Session,
+        },
+        api_key::ApiKeyScope,
+        login_attempt::LoginKeys,
+        session::{AuthSession, RefreshRequest, SessionId, TokenPair},
+        two_factor::LoginResponse,
+        Pagination,
+    },
     repositories::store::Store,
 };
 
 pub async fn register(
     State(store): State<Store>,
-    Json(account): Json<Account>,
+    State(config): State<Arc<AppConfig>>,
+    State(mailer): State<Arc<dyn Mailer>>,
+    ValidJson(account): ValidJson<Account>,
 ) -> Result<String, Error> {
     event!(target:"axum-web-dev", Level::INFO, "register new user");
     let hashed_pwd = hash_passowrd(account.password.as_bytes());
 
+    let display_name = match account.display_name.trim() {
+        "" => account.email.split('@').next().unwrap_or_default(),
+        name => name,
+    }
+    .to_string();
+
     let account = Account {
-        id: account.id,
-        email: account.email,
         password: hashed_pwd,
+        display_name,
+        // `auth.admin_emails` only count once verified, see `verify_email`
+        role: Role::User,
+        ..account
     };
 
+    let email = account.email.clone();
     let _res: Result<bool, Error> = match store.add_account(account).await {
         Err(e) => return Err(e),
         Ok(res) => Ok(res),
     };
 
+    // the account exists either way, a lost mail can be resent after login
+    let account = store.get_account(email).await?;
+    if let Err(e) = send_verification_email(&store, mailer.as_ref(), &config, &account).await {
+        event!(target:"axum-web-dev", Level::WARN, "cannot send verification mail: {}", e);
+    }
+
     Ok(String::from("Success"))
 }
 
 pub async fn login(
     State(store): State<Store>,
-    Json(login): Json<Account>,
-) -> Result<String, Error> {
-    let token: Result<String, Error> = match store.get_account(login.email).await {
-        Ok(account) => match verify_password(login.password.as_bytes(), &account.password) {
-            Ok(verified) => {
-                if verified {
-                    Ok(issue_token(account.id.expect("id not found")))
-                } else {
-                    return Err(Error::WrongPassword);
-                }
-            }
-            Err(e) => return Err(Error::ArgonLibraryError(e)),
-        },
+    State(config): State<Arc<AppConfig>>,
+    ClientIp(ip): ClientIp,
+    ValidJson(login): ValidJson<Credentials>,
+) -> Result<Json<LoginResponse>, Error> {
+    let keys = LoginKeys::new(&login.email, ip);
+    check_login_allowed(&store, &config.auth, &keys).await?;
+    // unknown emails cost the same hash and answer like wrong passwords, so
+    // accounts cannot be probed
+    let checked = match store.get_account(login.email).await {
+        Ok(account) => check_password(&login.password, &account).map(|_| account),
+        Err(Error::AccountNotFound) => {
+            let _ = verify_password(login.password.as_bytes(), DUMMY_PASSWORD_HASH);
+            Err(Error::WrongPassword)
+        }
         Err(e) => return Err(e),
     };
+    let account = match checked {
+        Ok(account) => account,
+        Err(Error::WrongPassword) => {
+            record_login_failure(&store, &config.auth, &keys).await?;
+            return Err(Error::WrongPassword);
+        }
+        Err(e) => return Err(e),
+    };
+    // failures are only cleared once the second factor is in as well
+    if account.two_factor_enabled {
+        let account_id = account.id.expect("id not found");
+        return Ok(Json(LoginResponse::Challenge(issue_challenge(
+            account_id,
+            &config.auth,
+        ))));
+    }
+    clear_login_failures(&store, &keys).await?;
+    let res = start_session(&store, &account, &config.auth).await?;
+
+    Ok(Json(LoginResponse::Tokens(res)))
+}
+
+/// Exchanges a refresh token for a new token pair. The refresh token is
+/// rotated on every use; presenting an already used one revokes the session.
+pub async fn refresh_token(
+    State(store): State<Store>,
+    State(config): State<Arc<AppConfig>>,
+    ValidJson(request): ValidJson<RefreshRequest>,
+) -> Result<Json<TokenPair>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "refresh token");
+    let (session_id, secret) = parse_refresh_token(&request.refresh_token)?;
+    let session = match store.get_session(&session_id).await? {
+        Some(session) if session.is_active() => session,
+        _ => return Err(Error::CannotDecryptToken),
+    };
+    match verify_password(secret.as_bytes(), &session.refresh_token_hash) {
+        Ok(true) => (),
+        Ok(false) => {
+            event!(target:"axum-web-dev", Level::WARN, "refresh token reuse, revoking session");
+            store.revoke_session(&session_id).await?;
+            return Err(Error::CannotDecryptToken);
+        }
+        Err(e) => return Err(Error::ArgonLibraryError(e)),
+    }
+
+    let account = store.get_account_byid(&session.account_id).await?;
+    let refresh_secret = generate_secret();
+    store
+        .rotate_refresh_token(
+            &session_id,
+            hash_passowrd(refresh_secret.as_bytes()),
+            refresh_token_expiry(&config.auth),
+        )
+        .await?;
+
+    Ok(Json(issue_token_pair(
+        &account,
+        &session_id,
+        &refresh_secret,
+        &config.auth,
+    )))
+}
+
+pub async fn logout(
+    State(store): State<Store>,
+    Extension(session): Extension<Session>,
+) -> Result<String, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "logout");
+    store.revoke_session(&session.jti).await?;
+
+    Ok(String::from("Logged out"))
+}
+
+pub async fn logout_all(
+    State(store): State<Store>,
+    Extension(session): Extension<Session>,
+) -> Result<String, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "logout all sessions");
+    store.revoke_account_sessions(&session.account_id).await?;
+
+    Ok(String::from("Logged out"))
+}
+
+pub async fn get_me(
+    State(store): State<Store>,
+    Extension(session): Extension<Session>,
+) -> Result<Json<Profile>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "get own profile");
+    let account = store.get_account_byid(&session.account_id).await?;
+
+    Ok(Json(account.into()))
+}
+
+pub async fn update_me(
+    State(store): State<Store>,
+    Extension(session): Extension<Session>,
+    ValidJson(update): ValidJson<ProfileUpdate>,
+) -> Result<Json<Profile>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "update own profile");
+    let update = ProfileUpdate {
+        display_name: update.display_name.map(|name| name.trim().to_string()),
+        ..update
+    };
+    let account = store.update_profile(&session.account_id, update).await?;
+
+    Ok(Json(account.into()))
+}
+
+pub async fn get_account_profile(
+    State(store): State<Store>,
+    Path(id): Path<i32>,
+) -> Result<Json<PublicProfile>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "get account profile");
+    let account = store.get_account_byid(&AccountId(id)).await?;
+
+    Ok(Json(account.into()))
+}
+
+/// The account counts as unverified until the new address is confirmed.
+pub async fn change_email(
+    State(store): State<Store>,
+    State(config): State<Arc<AppConfig>>,
+    State(mailer): State<Arc<dyn Mailer>>,
+    Extension(session): Extension<Session>,
+    ValidJson(change): ValidJson<EmailChange>,
+) -> Result<Json<Profile>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "change email");
+    let account = store.get_account_byid(&session.account_id).await?;
+    check_password(&change.current_password, &account)?;
+    let account = store
+        .update_email(&session.account_id, change.email)
+        .await?;
+    if let Err(e) = send_verification_email(&store, mailer.as_ref(), &config, &account).await {
+        event!(target:"axum-web-dev", Level::WARN, "cannot send verification mail: {}", e);
+    }
+
+    Ok(Json(account.into()))
+}
+
+/// Signs out every session, the caller continues with the returned pair.
+pub async fn change_password(
+    State(store): State<Store>,
+    State(config): State<Arc<AppConfig>>,
+    Extension(session): Extension<Session>,
+    ValidJson(change): ValidJson<PasswordChange>,
+) -> Result<Json<TokenPair>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "change password");
+    let account = store.get_account_byid(&session.account_id).await?;
+    check_password(&change.current_password, &account)?;
+    store
+        .update_password(
+            &session.account_id,
+            hash_passowrd(change.new_password.as_bytes()),
+        )
+        .await?;
+    store.revoke_account_sessions(&session.account_id).await?;
+    let res = start_session(&store, &account, &config.auth).await?;
+
+    Ok(Json(res))
+}
+
+/// Anonymizes the account, see `AccountRepository::delete_account`.
+pub async fn delete_me(
+    State(store): State<Store>,
+    Extension(session): Extension<Session>,
+    ValidJson(deletion): ValidJson<AccountDeletion>,
+) -> Result<String, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "delete own account");
+    let account = store.get_account_byid(&session.account_id).await?;
+    check_password(&deletion.password, &account)?;
+    store.delete_account(&session.account_id).await?;
+
+    Ok(String::from("Account Deleted"))
+}
+
+pub async fn get_accounts(
+    State(store): State<Store>,
+    _moderator: RequireRole<Moderator>,
+    Query(pagination): Query<Pagination>,
+) -> Result<Json<Vec<AccountInfo>>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "get accounts");
+    let page = pagination
+        .offset_request()
+        .map_err(Error::InvalidParameter)?;
+    let res = store.get_accounts(page.offset, page.limit).await?;
 
-    token
+    Ok(Json(res))
 }
 
-pub async fn auth(mut req: Request<Body>, next: Next) -> Result<Response, Error> {
-    if req.uri().path().ends_with("registration") || req.uri().path().ends_with("login") {
+pub async fn update_account_role(
+    State(store): State<Store>,
+    admin: RequireRole<Admin>,
+    Path(id): Path<i32>,
+    ValidJson(update): ValidJson<RoleUpdate>,
+) -> Result<Json<AccountInfo>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "update account role");
+    // keeps at least one admin around, another admin has to demote you
+    if admin.session.account_id.0 == id {
+        return Err(Error::Forbidden);
+    }
+    let res = store
+        .update_account_role(&AccountId(id), update.role)
+        .await?;
+
+    Ok(Json(res))
+}
+
+/// Served without a session. Compared as a whole, a suffix would also match
+/// paths such as `/api/tags/login`.
+const PUBLIC_PATHS: [&str; 7] = [
+    "/api/registration",
+    "/api/login",
+    "/api/login/2fa",
+    "/api/token/refresh",
+    "/api/email/verify",
+    "/api/password/forgot",
+    "/api/password/reset",
+];
+
+pub async fn auth(
+    State(store): State<Store>,
+    State(config): State<Arc<AppConfig>>,
+    State(limits): State<Arc<dyn RateLimitStore>>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Result<Response, Error> {
+    if PUBLIC_PATHS.contains(&req.uri().path()) {
         return Ok(next.run(req).await);
     }
 
@@ -69,50 +336,191 @@ pub async fn auth(mut req: Request<Body>, next: Next) -> Result<Response, Error>
         .headers()
         .get(header::AUTHORIZATION)
         .and_then(|auth_header| auth_header.to_str().ok());
-    let token = match token {
-        Some(t) => t,
-        None => "",
+    let token = token.unwrap_or_default();
+    // `rate_limit` runs after this layer, rejected credentials are throttled here
+    let ip = client_ip(req.headers(), req.extensions(), &config);
+    if !token.is_empty() {
+        check_failed_auth(limits.as_ref(), &config, ip).await?;
+    }
+    let verified = match token.strip_prefix("ApiKey ") {
+        Some(key) => verify_api_key(key.trim(), &store).await,
+        None => {
+            let token = token.strip_prefix("Bearer ").unwrap_or(token);
+            verify_token(token.to_string(), &config.auth, &store).await
+        }
     };
-    let session = match verify_token(token.to_string()) {
-        Ok(s) => s,
-        Err(e) => return Err(e),
+    let session = match verified {
+        Ok(session) => session,
+        Err(e) => {
+            if matches!(e, Error::CannotDecryptToken) && !token.is_empty() {
+                record_failed_auth(limits.as_ref(), &config, ip).await;
+            }
+            return Err(e);
+        }
     };
+    if session.api_key == Some(ApiKeyScope::Read)
+        && req.method() != Method::GET
+        && req.method() != Method::HEAD
+    {
+        return Err(Error::InsufficientScope);
+    }
+    if config.auth.require_verified_email
+        && !session.email_verified
+        && !allowed_while_restricted(&req)
+    {
+        return Err(Error::EmailNotVerified);
+    }
+    if config.auth.two_factor_roles.contains(&session.role)
+        && !session.two_factor
+        && !allowed_while_restricted(&req)
+    {
+        return Err(Error::TwoFactorRequired);
+    }
 
     req.extensions_mut().insert(session);
     Ok(next.run(req).await)
 }
 
+/// Unverified accounts, and accounts that still have to set up 2FA, may read
+/// and look after their own account.
+fn allowed_while_restricted(req: &Request<Body>) -> bool {
+    let path = req.uri().path();
+    req.method() == Method::GET
+        || req.method() == Method::HEAD
+        || path.starts_with("/api/me")
+        || path.starts_with("/api/logout")
+}
+
+/// Hash of a discarded random password with the same parameters as
+/// `hash_passowrd`, so checking it costs as much as a real account.
+const DUMMY_PASSWORD_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$7z33qAo8hLMSqfLmGicGsVwsMmEsZlUeYk1AS7yhQIY$usJNEPrOfjWC8Ca1BfTBxuFcBkiQeXbSVY13dpICgbc";
+
 pub fn hash_passowrd(password: &[u8]) -> String {
     let salt = rand::thread_rng().gen::<[u8; 32]>();
     let config = Config::default();
     argon2::hash_encoded(password, &salt, &config).unwrap()
 }
 
-pub fn verify_token(token: String) -> Result<Session, Error> {
+/// Validates the token and checks that the session it was issued for has not
+/// been revoked or expired and belongs to the account the token names. Role
+/// and verification come from the account, so they apply right away and do
+/// not rest on the claims alone.
+pub async fn verify_token(
+    token: String,
+    config: &AuthConfig,
+    store: &Store,
+) -> Result<Session, Error> {
     let token = paseto::tokens::validate_local_token(
         &token,
         None,
-        &"RANDOM WORDS WINTER MACINTOSH PC".as_bytes(),
+        config.token_key.as_bytes(),
         &paseto::tokens::TimeBackend::Chrono,
     )
     .map_err(|_| Error::CannotDecryptToken)?;
 
-    serde_json::from_value::<Session>(token).map_err(|_| Error::CannotDecryptToken)
+    let mut session =
+        serde_json::from_value::<Session>(token).map_err(|_| Error::CannotDecryptToken)?;
+    match store.get_session(&session.jti).await? {
+        Some(auth_session)
+            if auth_session.is_active() && auth_session.account_id == session.account_id => {}
+        _ => return Err(Error::CannotDecryptToken),
+    }
+    let account = match store.get_account_byid(&session.account_id).await {
+        Ok(account) => account,
+        Err(Error::AccountNotFound) => return Err(Error::CannotDecryptToken),
+        Err(e) => return Err(e),
+    };
+    session.role = account.role;
+    session.email_verified = account.email_verified;
+
+    Ok(session)
 }
 
-fn verify_password(password: &[u8], hash: &str) -> Result<bool, argon2::Error> {
+pub fn verify_password(password: &[u8], hash: &str) -> Result<bool, argon2::Error> {
     argon2::verify_encoded(hash, password)
 }
 
-fn issue_token(account_id: AccountId) -> String {
+pub fn check_password(password: &str, account: &Account) -> Result<(), Error> {
+    match verify_password(password.as_bytes(), &account.password) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(Error::WrongPassword),
+        Err(e) => Err(Error::ArgonLibraryError(e)),
+    }
+}
+
+/// Stores a new session and issues its first token pair.
+pub async fn start_session(
+    store: &Store,
+    account: &Account,
+    config: &AuthConfig,
+) -> Result<TokenPair, Error> {
+    let session_id = SessionId(Uuid::new_v4());
+    let refresh_secret = generate_secret();
+    store
+        .add_session(AuthSession {
+            id: session_id.clone(),
+            account_id: account.id.clone().expect("id not found"),
+            refresh_token_hash: hash_passowrd(refresh_secret.as_bytes()),
+            expires_on: refresh_token_expiry(config),
+            revoked_on: None,
+        })
+        .await?;
+
+    Ok(issue_token_pair(
+        account,
+        &session_id,
+        &refresh_secret,
+        config,
+    ))
+}
+
+fn issue_token(account: &Account, session_id: &SessionId, config: &AuthConfig) -> String {
     let current_datetime = Utc::now();
-    let dt = current_datetime + chrono::Duration::days(1);
+    let dt = current_datetime + chrono::Duration::seconds(config.token_ttl);
 
     paseto::tokens::PasetoBuilder::new()
-        .set_encryption_key(&Vec::from("RANDOM WORDS WINTER MACINTOSH PC".as_bytes()))
+        .set_encryption_key(config.token_key.as_bytes())
         .set_expiration(&dt)
         .set_not_before(&Utc::now())
-        .set_claim("account_id", serde_json::json!(account_id))
+        .set_jti(&session_id.0.to_string())
+        .set_claim("account_id", serde_json::json!(account.id))
+        .set_claim("role", serde_json::json!(account.role))
+        .set_claim("email_verified", serde_json::json!(account.email_verified))
+        .set_claim("two_factor", serde_json::json!(account.two_factor_enabled))
         .build()
         .expect("Failed to construct paseto token w/ builder!")
 }
+
+fn issue_token_pair(
+    account: &Account,
+    session_id: &SessionId,
+    refresh_secret: &str,
+    config: &AuthConfig,
+) -> TokenPair {
+    TokenPair {
+        access_token: issue_token(account, session_id, config),
+        refresh_token: format!("{}.{}", session_id.0, refresh_secret),
+        expires_in: config.token_ttl,
+    }
+}
+
+/// 32 random bytes, hex encoded.
+pub fn generate_secret() -> String {
+    rand::thread_rng()
+        .gen::<[u8; 32]>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Refresh tokens have the form `<session id>.<secret>`.
+fn parse_refresh_token(token: &str) -> Result<(SessionId, &str), Error> {
+    let (session_id, secret) = token.split_once('.').ok_or(Error::CannotDecryptToken)?;
+    let session_id = Uuid::parse_str(session_id).map_err(|_| Error::CannotDecryptToken)?;
+
+    Ok((SessionId(session_id), secret))
+}
+
+fn refresh_token_expiry(config: &AuthConfig) -> chrono::DateTime<Utc> {
+    Utc::now() + chrono::Duration::seconds(config.refresh_token_ttl)
+}
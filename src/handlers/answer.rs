@@ -1,22 +1,229 @@
-use axum::{extract::State, Json};
+use axum::{
+    extract::{OriginalUri, State},
+    Extension, Json,
+};
 use tracing::{event, instrument, Level};
 
 use crate::{
-    common::error::Error,
-    models::answer::{Answer, NewAnswer},
+    common::{
+        content_filter::ContentPolicy,
+        error::Error,
+        extract::{Path, Query},
+        validation::ValidJson,
+    },
+    handlers::{id_page_request, page_response, PageResponse},
+    models::{
+        account::{Role, Session},
+        answer::{Answer, AnswerId, AnswerUpdate, NewAnswer},
+        revision::{AnswerRevision, DiffQuery, RevisionDiff, Rollback},
+        vote::{NewVote, VoteTarget},
+        Pagination,
+    },
     repositories::store::Store,
 };
 
 #[instrument]
 pub async fn add_answer(
     State(store): State<Store>,
-    Json(new_answer): Json<NewAnswer>,
+    State(policy): State<ContentPolicy>,
+    Extension(session): Extension<Session>,
+    ValidJson(new_answer): ValidJson<NewAnswer>,
 ) -> Result<Json<Answer>, Error> {
     event!(target:"axum-web-dev", Level::INFO, "add new answer");
-    let res = match store.add_answer(new_answer).await {
+    let new_answer = NewAnswer {
+        content: policy.apply("content", new_answer.content).await?,
+        ..new_answer
+    };
+    let res = match store.add_answer(new_answer, &session.account_id).await {
         Err(e) => return Err(e),
         Ok(res) => res,
     };
 
     Ok(Json(res))
 }
+
+#[instrument]
+pub async fn get_answers(
+    State(store): State<Store>,
+    Path(question_id): Path<i64>,
+    OriginalUri(uri): OriginalUri,
+    Query(pagination): Query<Pagination>,
+) -> Result<PageResponse<Answer>, Error> {
+    event!(target:"axum-web-demo", Level::INFO, "get answers of question");
+    let page_request = id_page_request(&pagination)?;
+    store.get_question_byid(question_id).await?;
+    let mut page = store.get_answers(question_id, &page_request).await?;
+    if pagination.include_total {
+        page.total = Some(store.count_answers(question_id).await?);
+    }
+
+    Ok(page_response(&uri, &page_request, page))
+}
+
+#[instrument]
+pub async fn get_answer_byid(
+    State(store): State<Store>,
+    Path(id): Path<i64>,
+) -> Result<Json<Answer>, Error> {
+    event!(target:"axum-web-demo", Level::INFO, "get answer by id");
+    let res = store.get_answer_byid(id).await?;
+
+    Ok(Json(res))
+}
+
+#[instrument]
+pub async fn update_answer(
+    State(store): State<Store>,
+    State(policy): State<ContentPolicy>,
+    Path(id): Path<i64>,
+    Extension(session): Extension<Session>,
+    ValidJson(answer): ValidJson<AnswerUpdate>,
+) -> Result<Json<Answer>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "update answer");
+    ensure_answer_owner(&store, id, &session).await?;
+    let answer = AnswerUpdate {
+        content: policy.apply("content", answer.content).await?,
+        ..answer
+    };
+    let res = store.update_answer(answer, id, &session.account_id).await?;
+
+    Ok(Json(res))
+}
+
+#[instrument]
+pub async fn delete_answer(
+    State(store): State<Store>,
+    Path(id): Path<i64>,
+    Extension(session): Extension<Session>,
+) -> Result<String, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "delete answer");
+    ensure_answer_owner(&store, id, &session).await?;
+    store.delete_answer(id, &session.account_id).await?;
+
+    Ok(String::from("Answer Deleted"))
+}
+
+#[instrument]
+pub async fn vote_answer(
+    State(store): State<Store>,
+    Path(id): Path<i64>,
+    Extension(session): Extension<Session>,
+    ValidJson(vote): ValidJson<NewVote>,
+) -> Result<Json<Answer>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "vote on answer");
+    store.get_answer_byid(id).await?;
+    if store.is_answer_owner(id, &session.account_id).await? {
+        return Err(Error::CannotVoteOwnPost);
+    }
+    let target = VoteTarget::Answer(AnswerId(id as i32));
+    store
+        .cast_vote(&target, &session.account_id, vote.direction)
+        .await?;
+    let res = store.get_answer_byid(id).await?;
+
+    Ok(Json(res))
+}
+
+#[instrument]
+pub async fn retract_answer_vote(
+    State(store): State<Store>,
+    Path(id): Path<i64>,
+    Extension(session): Extension<Session>,
+) -> Result<Json<Answer>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "retract answer vote");
+    store.get_answer_byid(id).await?;
+    let target = VoteTarget::Answer(AnswerId(id as i32));
+    store.retract_vote(&target, &session.account_id).await?;
+    let res = store.get_answer_byid(id).await?;
+
+    Ok(Json(res))
+}
+
+#[instrument]
+pub async fn get_answer_revisions(
+    State(store): State<Store>,
+    Path(id): Path<i64>,
+    OriginalUri(uri): OriginalUri,
+    Query(pagination): Query<Pagination>,
+    Extension(session): Extension<Session>,
+) -> Result<PageResponse<AnswerRevision>, Error> {
+    event!(target:"axum-web-demo", Level::INFO, "get answer revisions");
+    let page_request = id_page_request(&pagination)?;
+    ensure_answer_owner(&store, id, &session).await?;
+    let page = store.get_answer_revisions(id, &page_request).await?;
+
+    Ok(page_response(&uri, &page_request, page))
+}
+
+#[instrument]
+pub async fn get_answer_revision(
+    State(store): State<Store>,
+    Path((id, revision)): Path<(i64, i32)>,
+    Extension(session): Extension<Session>,
+) -> Result<Json<AnswerRevision>, Error> {
+    event!(target:"axum-web-demo", Level::INFO, "get answer revision");
+    ensure_answer_owner(&store, id, &session).await?;
+    let res = store.get_answer_revision(id, revision).await?;
+
+    Ok(Json(res))
+}
+
+#[instrument]
+pub async fn diff_answer_revisions(
+    State(store): State<Store>,
+    Path(id): Path<i64>,
+    Query(query): Query<DiffQuery>,
+    Extension(session): Extension<Session>,
+) -> Result<Json<RevisionDiff>, Error> {
+    event!(target:"axum-web-demo", Level::INFO, "diff answer revisions");
+    ensure_answer_owner(&store, id, &session).await?;
+    let from = store.get_answer_revision(id, query.from).await?;
+    let to = store.get_answer_revision(id, query.to).await?;
+
+    Ok(Json(RevisionDiff::answers(&from, &to)))
+}
+
+/// Stores the text of an older revision as a new revision.
+#[instrument]
+pub async fn rollback_answer(
+    State(store): State<Store>,
+    Path((id, revision)): Path<(i64, i32)>,
+    Extension(session): Extension<Session>,
+    ValidJson(rollback): ValidJson<Rollback>,
+) -> Result<Json<Answer>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "rollback answer");
+    ensure_answer_owner(&store, id, &session).await?;
+    let target = store.get_answer_revision(id, revision).await?;
+    let answer = AnswerUpdate {
+        content: target.content,
+        comment: Some(
+            rollback
+                .comment
+                .unwrap_or_else(|| format!("Rolled back to revision {}", revision)),
+        ),
+    };
+    let res = store.update_answer(answer, id, &session.account_id).await?;
+
+    Ok(Json(res))
+}
+
+/// Fails with `AnswerNotFound` for a missing answer and `Forbidden` when the
+/// answer belongs to someone else. Moderators may act on any answer.
+async fn ensure_answer_owner(
+    store: &Store,
+    answer_id: i64,
+    session: &Session,
+) -> Result<(), Error> {
+    store.get_answer_byid(answer_id).await?;
+    if session.has_role(Role::Moderator) {
+        return Ok(());
+    }
+    if !store
+        .is_answer_owner(answer_id, &session.account_id)
+        .await?
+    {
+        return Err(Error::Forbidden);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use axum::{extract::State, Extension, Json};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use tracing::{event, Level};
+use uuid::Uuid;
+
+use crate::{
+    common::{error::Error, extract::Path, validation::ValidJson},
+    handlers::account::generate_secret,
+    models::{
+        account::Session,
+        api_key::{ApiKey, ApiKeyId, ApiKeyInfo, CreatedApiKey, NewApiKey},
+        session::SessionId,
+    },
+    repositories::store::Store,
+};
+
+pub async fn get_api_keys(
+    State(store): State<Store>,
+    Extension(session): Extension<Session>,
+) -> Result<Json<Vec<ApiKeyInfo>>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "get api keys");
+    ensure_token_session(&session)?;
+    let keys = store.get_api_keys(&session.account_id).await?;
+
+    Ok(Json(keys.into_iter().map(ApiKeyInfo::from).collect()))
+}
+
+/// The returned `key` is the only time the secret is visible.
+pub async fn create_api_key(
+    State(store): State<Store>,
+    Extension(session): Extension<Session>,
+    ValidJson(new_key): ValidJson<NewApiKey>,
+) -> Result<Json<CreatedApiKey>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "create api key");
+    ensure_token_session(&session)?;
+    let key_id = ApiKeyId(Uuid::new_v4());
+    let secret = generate_secret();
+    let key = store
+        .add_api_key(ApiKey {
+            id: key_id.clone(),
+            account_id: session.account_id,
+            name: new_key.name.trim().to_string(),
+            scope: new_key.scope,
+            secret_hash: digest_secret(&secret),
+            created_at: Utc::now(),
+            last_used_at: None,
+            revoked_at: None,
+        })
+        .await?;
+
+    Ok(Json(CreatedApiKey {
+        key: format!("{}.{}", key_id.0, secret),
+        info: key.into(),
+    }))
+}
+
+pub async fn revoke_api_key(
+    State(store): State<Store>,
+    Extension(session): Extension<Session>,
+    Path(id): Path<Uuid>,
+) -> Result<String, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "revoke api key");
+    ensure_token_session(&session)?;
+    if !store
+        .revoke_api_key(&session.account_id, &ApiKeyId(id))
+        .await?
+    {
+        return Err(Error::ApiKeyNotFound);
+    }
+
+    Ok(String::from("API key revoked"))
+}
+
+/// Checks an `<id>.<secret>` key and builds the session the request runs
+/// under, with the account's current role.
+pub async fn verify_api_key(key: &str, store: &Store) -> Result<Session, Error> {
+    let (key_id, secret) = key.split_once('.').ok_or(Error::CannotDecryptToken)?;
+    let key_id = ApiKeyId(Uuid::parse_str(key_id).map_err(|_| Error::CannotDecryptToken)?);
+    let api_key = store
+        .get_api_key(&key_id)
+        .await?
+        .ok_or(Error::CannotDecryptToken)?;
+    if !bool::from(
+        digest_secret(secret)
+            .as_bytes()
+            .ct_eq(api_key.secret_hash.as_bytes()),
+    ) {
+        return Err(Error::CannotDecryptToken);
+    }
+    let account = match store.get_account_byid(&api_key.account_id).await {
+        Ok(account) => account,
+        Err(Error::AccountNotFound) => return Err(Error::CannotDecryptToken),
+        Err(e) => return Err(e),
+    };
+    store.touch_api_key(&key_id).await?;
+
+    // valid for this request only, the key is checked again on the next one
+    let now = Utc::now();
+    Ok(Session {
+        exp: now,
+        account_id: api_key.account_id,
+        nbf: now,
+        jti: SessionId(key_id.0),
+        role: account.role,
+        email_verified: account.email_verified,
+        two_factor: account.two_factor_enabled,
+        api_key: Some(api_key.scope),
+    })
+}
+
+/// Secrets are 32 random bytes, too many to guess, so unlike passwords they
+/// need no slow hash and checking a key stays as cheap as checking a token.
+fn digest_secret(secret: &str) -> String {
+    Sha256::digest(secret.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Keys cannot mint or list other keys, a leaked one stays contained.
+fn ensure_token_session(session: &Session) -> Result<(), Error> {
+    match session.api_key {
+        Some(_) => Err(Error::Forbidden),
+        None => Ok(()),
+    }
+}
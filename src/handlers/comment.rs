@@ -0,0 +1,185 @@
+use axum::{
+    extract::{OriginalUri, State},
+    Extension, Json,
+};
+use tracing::{event, instrument, Level};
+
+use crate::{
+    common::{
+        content_filter::ContentPolicy,
+        error::{Error, FieldError},
+        extract::{Path, Query},
+        validation::ValidJson,
+    },
+    handlers::{id_page_request, page_response, PageResponse},
+    models::{
+        account::{Role, Session},
+        answer::AnswerId,
+        comment::{Comment, CommentTarget, CommentUpdate, NewComment},
+        question::QuestionId,
+        Pagination,
+    },
+    repositories::store::Store,
+};
+
+#[instrument]
+pub async fn add_question_comment(
+    State(store): State<Store>,
+    State(policy): State<ContentPolicy>,
+    Path(id): Path<i64>,
+    Extension(session): Extension<Session>,
+    ValidJson(new_comment): ValidJson<NewComment>,
+) -> Result<Json<Comment>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "add question comment");
+    store.get_question_byid(id).await?;
+    let target = CommentTarget::Question(QuestionId(id as i32));
+    add_comment(&store, &policy, &target, new_comment, &session).await
+}
+
+#[instrument]
+pub async fn add_answer_comment(
+    State(store): State<Store>,
+    State(policy): State<ContentPolicy>,
+    Path(id): Path<i64>,
+    Extension(session): Extension<Session>,
+    ValidJson(new_comment): ValidJson<NewComment>,
+) -> Result<Json<Comment>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "add answer comment");
+    store.get_answer_byid(id).await?;
+    let target = CommentTarget::Answer(AnswerId(id as i32));
+    add_comment(&store, &policy, &target, new_comment, &session).await
+}
+
+#[instrument]
+pub async fn get_question_comments(
+    State(store): State<Store>,
+    Path(id): Path<i64>,
+    OriginalUri(uri): OriginalUri,
+    Query(pagination): Query<Pagination>,
+) -> Result<PageResponse<Comment>, Error> {
+    event!(target:"axum-web-demo", Level::INFO, "get comments of question");
+    let page_request = id_page_request(&pagination)?;
+    let question = store.get_question_byid(id).await?;
+    let mut page = store
+        .get_comments(&CommentTarget::Question(question.id), &page_request)
+        .await?;
+    if pagination.include_total {
+        page.total = Some(question.comment_count as i64);
+    }
+
+    Ok(page_response(&uri, &page_request, page))
+}
+
+#[instrument]
+pub async fn get_answer_comments(
+    State(store): State<Store>,
+    Path(id): Path<i64>,
+    OriginalUri(uri): OriginalUri,
+    Query(pagination): Query<Pagination>,
+) -> Result<PageResponse<Comment>, Error> {
+    event!(target:"axum-web-demo", Level::INFO, "get comments of answer");
+    let page_request = id_page_request(&pagination)?;
+    let answer = store.get_answer_byid(id).await?;
+    let mut page = store
+        .get_comments(&CommentTarget::Answer(answer.id), &page_request)
+        .await?;
+    if pagination.include_total {
+        page.total = Some(answer.comment_count as i64);
+    }
+
+    Ok(page_response(&uri, &page_request, page))
+}
+
+#[instrument]
+pub async fn get_comment(
+    State(store): State<Store>,
+    Path(id): Path<i64>,
+) -> Result<Json<Comment>, Error> {
+    event!(target:"axum-web-demo", Level::INFO, "get comment by id");
+    let res = store.get_comment(id).await?;
+
+    Ok(Json(res))
+}
+
+#[instrument]
+pub async fn update_comment(
+    State(store): State<Store>,
+    State(policy): State<ContentPolicy>,
+    Path(id): Path<i64>,
+    Extension(session): Extension<Session>,
+    ValidJson(comment): ValidJson<CommentUpdate>,
+) -> Result<Json<Comment>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "update comment");
+    ensure_comment_owner(&store, id, &session).await?;
+    let comment = CommentUpdate {
+        content: policy.apply("content", comment.content).await?,
+    };
+    let res = store.update_comment(comment, id).await?;
+
+    Ok(Json(res))
+}
+
+/// Replies to the comment are deleted with it.
+#[instrument]
+pub async fn delete_comment(
+    State(store): State<Store>,
+    Path(id): Path<i64>,
+    Extension(session): Extension<Session>,
+) -> Result<String, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "delete comment");
+    ensure_comment_owner(&store, id, &session).await?;
+    store.delete_comment(id).await?;
+
+    Ok(String::from("Comment Deleted"))
+}
+
+async fn add_comment(
+    store: &Store,
+    policy: &ContentPolicy,
+    target: &CommentTarget,
+    new_comment: NewComment,
+    session: &Session,
+) -> Result<Json<Comment>, Error> {
+    if let Some(parent_id) = &new_comment.parent_id {
+        let parent = match store.get_comment(parent_id.0 as i64).await {
+            Err(Error::CommentNotFound) => None,
+            res => Some(res?),
+        };
+        if parent.is_none_or(|parent| &parent.target() != target) {
+            return Err(Error::ValidationFailed(vec![FieldError::new(
+                "parent_id",
+                "comment does not exist on this post",
+            )]));
+        }
+    }
+    let new_comment = NewComment {
+        content: policy.apply("content", new_comment.content).await?,
+        ..new_comment
+    };
+    let res = store
+        .add_comment(target, new_comment, &session.account_id)
+        .await?;
+
+    Ok(Json(res))
+}
+
+/// Fails with `CommentNotFound` for a missing comment and `Forbidden` when
+/// the comment belongs to someone else. Moderators may act on any comment.
+async fn ensure_comment_owner(
+    store: &Store,
+    comment_id: i64,
+    session: &Session,
+) -> Result<(), Error> {
+    store.get_comment(comment_id).await?;
+    if session.has_role(Role::Moderator) {
+        return Ok(());
+    }
+    if !store
+        .is_comment_owner(comment_id, &session.account_id)
+        .await?
+    {
+        return Err(Error::Forbidden);
+    }
+
+    Ok(())
+}
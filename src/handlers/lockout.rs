@@ -0,0 +1,225 @@
+use axum::extract::State;
+use chrono::{DateTime, Utc};
+use tracing::{event, Level};
+
+use crate::{
+    common::{
+        config::AuthConfig,
+        error::Error,
+        extract::Path,
+        guard::{Admin, RequireRole},
+    },
+    models::{
+        account::AccountId,
+        login_attempt::{email_key, LoginAttempts, LoginKeys},
+    },
+    repositories::store::Store,
+};
+
+/// Lifts the lockout of an account before it runs out.
+pub async fn unlock_account(
+    State(store): State<Store>,
+    _admin: RequireRole<Admin>,
+    Path(id): Path<i32>,
+) -> Result<String, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "unlock account");
+    let account = store.get_account_byid(&AccountId(id)).await?;
+    store
+        .clear_login_attempts(&email_key(&account.email))
+        .await?;
+
+    Ok(String::from("Account unlocked"))
+}
+
+/// Refuses a login attempt while the email or the client address is locked,
+/// or while the email still waits out the delay of its last failure.
+pub async fn check_login_allowed(
+    store: &Store,
+    config: &AuthConfig,
+    keys: &LoginKeys,
+) -> Result<(), Error> {
+    let now = Utc::now();
+    if let Some(ip) = &keys.ip {
+        if let Some(attempts) = store.get_login_attempts(ip).await? {
+            check_locked(store, ip, &attempts, now).await?;
+        }
+    }
+    if let Some(attempts) = store.get_login_attempts(&keys.email).await? {
+        check_locked(store, &keys.email, &attempts, now).await?;
+        let retry_at = attempts.last_failure_at + login_delay(config, attempts.failures);
+        if retry_at > now {
+            return Err(Error::TooManyAttempts(seconds_until(retry_at, now)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Counts a failed attempt against both keys, locking the ones that reached
+/// their limit.
+pub async fn record_login_failure(
+    store: &Store,
+    config: &AuthConfig,
+    keys: &LoginKeys,
+) -> Result<(), Error> {
+    let window_start = Utc::now() - chrono::Duration::seconds(config.login_lockout_secs);
+    let mut limits = vec![(&keys.email, config.login_max_failures)];
+    if let Some(ip) = &keys.ip {
+        limits.push((ip, config.login_ip_max_failures));
+    }
+    for (key, max_failures) in limits {
+        let attempts = store.record_login_failure(key, window_start).await?;
+        if attempts.failures >= max_failures {
+            event!(target:"axum-web-dev", Level::WARN, key, "login locked after repeated failures");
+            let until = Utc::now() + chrono::Duration::seconds(config.login_lockout_secs);
+            store.lock_login(key, until).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Forgets the failures of the email once a login went through. The address
+/// keeps its count, a working password says nothing about the others tried.
+pub async fn clear_login_failures(store: &Store, keys: &LoginKeys) -> Result<(), Error> {
+    store.clear_login_attempts(&keys.email).await
+}
+
+/// An expired lock starts the key over, so one more failure does not lock
+/// it again right away.
+async fn check_locked(
+    store: &Store,
+    key: &str,
+    attempts: &LoginAttempts,
+    now: DateTime<Utc>,
+) -> Result<(), Error> {
+    match attempts.locked_until {
+        Some(until) if until > now => Err(Error::TooManyAttempts(seconds_until(until, now))),
+        Some(_) => store.clear_login_attempts(key).await,
+        None => Ok(()),
+    }
+}
+
+/// `login_delay_base_ms`, doubled for every failure after the first.
+fn login_delay(config: &AuthConfig, failures: i32) -> chrono::Duration {
+    let doublings = (failures - 1).clamp(0, 30) as u32;
+    let delay = config
+        .login_delay_base_ms
+        .saturating_mul(1 << doublings)
+        .min(config.login_delay_max_ms);
+    chrono::Duration::milliseconds(delay)
+}
+
+/// Whole seconds for `Retry-After`, rounded up so clients do not come back early.
+fn seconds_until(at: DateTime<Utc>, now: DateTime<Utc>) -> u64 {
+    let millis = (at - now).num_milliseconds().max(0) as u64;
+    millis.div_ceil(1000).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::IpAddr, sync::Arc};
+
+    use super::*;
+    use crate::repositories::memory::MemoryStore;
+
+    const ADDRESS: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 1));
+
+    /// No delay between attempts, so only the lockouts are in play.
+    fn thresholds(email: i32, ip: i32) -> AuthConfig {
+        AuthConfig {
+            login_max_failures: email,
+            login_ip_max_failures: ip,
+            login_delay_base_ms: 0,
+            ..AuthConfig::default()
+        }
+    }
+
+    async fn fail(store: &Store, config: &AuthConfig, keys: &LoginKeys, times: usize) {
+        for _ in 0..times {
+            record_login_failure(store, config, keys).await.unwrap();
+        }
+    }
+
+    #[test]
+    fn delays_double_up_to_the_cap() {
+        let config = AuthConfig {
+            login_delay_base_ms: 500,
+            login_delay_max_ms: 3_000,
+            ..AuthConfig::default()
+        };
+        let delays: Vec<i64> = (0..=5)
+            .map(|failures| login_delay(&config, failures).num_milliseconds())
+            .collect();
+        assert_eq!(delays, [500, 500, 1_000, 2_000, 3_000, 3_000]);
+        assert_eq!(login_delay(&config, i32::MAX).num_milliseconds(), 3_000);
+    }
+
+    #[test]
+    fn retry_after_rounds_up_to_whole_seconds() {
+        let now = Utc::now();
+        let after = |millis| seconds_until(now + chrono::Duration::milliseconds(millis), now);
+        assert_eq!(after(1), 1);
+        assert_eq!(after(1_000), 1);
+        assert_eq!(after(1_001), 2);
+        assert_eq!(after(-5_000), 1);
+    }
+
+    #[tokio::test]
+    async fn emails_lock_at_their_limit() {
+        let store: Store = Arc::new(MemoryStore::new());
+        let config = thresholds(3, 50);
+        let keys = LoginKeys::new("ann@example.com", Some(ADDRESS));
+
+        fail(&store, &config, &keys, 2).await;
+        assert!(check_login_allowed(&store, &config, &keys).await.is_ok());
+        fail(&store, &config, &keys, 1).await;
+        assert!(matches!(
+            check_login_allowed(&store, &config, &keys).await,
+            Err(Error::TooManyAttempts(_))
+        ));
+
+        let other = LoginKeys::new("bob@example.com", Some(ADDRESS));
+        assert!(check_login_allowed(&store, &config, &other).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn addresses_lock_across_emails() {
+        let store: Store = Arc::new(MemoryStore::new());
+        let config = thresholds(5, 4);
+        for email in ["a@example.com", "b@example.com"] {
+            let keys = LoginKeys::new(email, Some(ADDRESS));
+            fail(&store, &config, &keys, 2).await;
+            clear_login_failures(&store, &keys).await.unwrap();
+        }
+
+        let keys = LoginKeys::new("c@example.com", Some(ADDRESS));
+        assert!(matches!(
+            check_login_allowed(&store, &config, &keys).await,
+            Err(Error::TooManyAttempts(_))
+        ));
+        let elsewhere = LoginKeys::new("c@example.com", None);
+        assert!(check_login_allowed(&store, &config, &elsewhere)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn expired_locks_start_over() {
+        let store: Store = Arc::new(MemoryStore::new());
+        let config = thresholds(2, 50);
+        let keys = LoginKeys::new("ann@example.com", None);
+        fail(&store, &config, &keys, 2).await;
+        store
+            .lock_login(&keys.email, Utc::now() - chrono::Duration::seconds(1))
+            .await
+            .unwrap();
+
+        assert!(check_login_allowed(&store, &config, &keys).await.is_ok());
+        assert!(store
+            .get_login_attempts(&keys.email)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -1,8 +1,25 @@
-use axum::{response::IntoResponse, Json};
+use axum::{
+    http::{header, HeaderName, Uri},
+    response::IntoResponse,
+    Json,
+};
+
+use crate::{
+    common::error::Error,
+    models::{CursorKey, Page, PageRequest, Pagination},
+};
 
 pub mod account;
 pub mod answer;
+pub mod api_key;
+pub mod comment;
+pub mod lockout;
 pub mod question;
+pub mod search;
+pub mod tag;
+pub mod trash;
+pub mod two_factor;
+pub mod verification;
 
 pub async fn health_check_handler() -> impl IntoResponse {
     const MESSAGE: &str = "Server is running";
@@ -14,3 +31,59 @@ pub async fn health_check_handler() -> impl IntoResponse {
 
     Json(json_response)
 }
+
+/// A listing page together with its `Link` header.
+pub type PageResponse<T> = ([(HeaderName, String); 1], Json<Page<T>>);
+
+pub fn page_response<T>(uri: &Uri, request: &PageRequest, page: Page<T>) -> PageResponse<T> {
+    let links = page_links(uri, request, &page);
+    ([(header::LINK, links)], Json(page))
+}
+
+/// Page request for listings ordered by id alone, rejecting cursors issued
+/// by sorted listings.
+pub fn id_page_request(pagination: &Pagination) -> Result<PageRequest, Error> {
+    let page_request = pagination.page_request().map_err(Error::InvalidParameter)?;
+    if let Some(after) = &page_request.after {
+        if after.key != CursorKey::Id || after.sort.is_some() {
+            return Err(Error::InvalidParameter(String::from(
+                "cursor does not belong to this listing",
+            )));
+        }
+    }
+
+    Ok(page_request)
+}
+
+/// RFC 8288 `Link` header value for a listing page. Links reuse the request
+/// query, so filters and `limit` carry over to the other pages.
+fn page_links<T>(uri: &Uri, request: &PageRequest, page: &Page<T>) -> String {
+    let params: Vec<&str> = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|param| {
+            let name = param.split('=').next().unwrap_or_default();
+            !name.is_empty() && name != "offset" && name != "cursor"
+        })
+        .collect();
+    let link = |position: Option<String>, rel: &str| {
+        let mut query = params.clone();
+        query.extend(position.as_deref());
+        if query.is_empty() {
+            format!("<{}>; rel=\"{}\"", uri.path(), rel)
+        } else {
+            format!("<{}?{}>; rel=\"{}\"", uri.path(), query.join("&"), rel)
+        }
+    };
+
+    let mut links = vec![link(None, "first")];
+    if request.after.is_none() && request.offset > 0 {
+        let offset = (request.offset - request.limit).max(0);
+        links.push(link(Some(format!("offset={}", offset)), "prev"));
+    }
+    if let Some(cursor) = &page.next_cursor {
+        links.push(link(Some(format!("cursor={}", cursor.encode())), "next"));
+    }
+    links.join(", ")
+}
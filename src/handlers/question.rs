@@ -1,15 +1,26 @@
 use axum::{
-    extract::{Path, Query, Request, State},
-    response::IntoResponse,
+    extract::{OriginalUri, State},
+    http::Uri,
     Extension, Json,
 };
 use tracing::{event, instrument, Level};
 
 use crate::{
-    common::error::Error,
+    common::{
+        content_filter::ContentPolicy,
+        error::{Error, FieldError},
+        extract::{Path, Query},
+        validation::ValidJson,
+    },
+    handlers::{id_page_request, page_response, PageResponse},
     models::{
-        account::Session,
-        question::{NewQuestion, Question},
+        account::{Role, Session},
+        question::{
+            AcceptedAnswer, NewQuestion, Question, QuestionFilter, QuestionId, QuestionUpdate,
+        },
+        revision::{DiffQuery, QuestionRevision, RevisionDiff, Rollback},
+        tag::normalize_tags,
+        vote::{NewVote, VoteTarget},
         Pagination,
     },
     repositories::store::Store,
@@ -18,10 +29,17 @@ use crate::{
 #[instrument]
 pub async fn add_question(
     State(store): State<Store>,
-    Json(new_question): Json<NewQuestion>,
+    State(policy): State<ContentPolicy>,
+    Extension(session): Extension<Session>,
+    ValidJson(new_question): ValidJson<NewQuestion>,
 ) -> Result<Json<Question>, Error> {
     event!(target:"axum-web-dev", Level::INFO, "add new question");
-    let res = match store.add_question(new_question).await {
+    let new_question = NewQuestion {
+        title: policy.apply("title", new_question.title).await?,
+        content: policy.apply("content", new_question.content).await?,
+        tags: canonical_tags(&store, new_question.tags).await?,
+    };
+    let res = match store.add_question(new_question, &session.account_id).await {
         Err(e) => return Err(e),
         Ok(res) => res,
     };
@@ -32,19 +50,44 @@ pub async fn add_question(
 #[instrument]
 pub async fn get_questions(
     State(store): State<Store>,
-    pagination: Option<Query<Pagination>>,
+    OriginalUri(uri): OriginalUri,
+    Query(pagination): Query<Pagination>,
+    Query(filter): Query<QuestionFilter>,
     Extension(session): Extension<Session>,
-) -> Result<Json<Vec<Question>>, Error> {
+) -> Result<PageResponse<Question>, Error> {
     event!(target:"axum-web-demo", Level::INFO, "get pagination questions");
-    let Query(pagination) = pagination.unwrap_or_default();
-    let offset: i64 = pagination.offset.unwrap_or(0);
-    let limit: i64 = pagination.limit.unwrap_or(100);
-    let res: Vec<Question> = match store.get_questions(offset, limit).await {
-        Ok(res) => res,
-        Err(e) => return Err(e),
-    };
 
-    Ok(Json(res))
+    list_questions(&store, &uri, pagination, filter).await
+}
+
+/// Question listing shared with the per tag listing.
+pub async fn list_questions(
+    store: &Store,
+    uri: &Uri,
+    pagination: Pagination,
+    mut filter: QuestionFilter,
+) -> Result<PageResponse<Question>, Error> {
+    if let Some(tags) = filter.tag_list() {
+        let slugs = store.resolve_tags(normalize_tags(&tags)).await?;
+        filter.tags = Some(normalize_tags(&slugs).join(","));
+    }
+    filter
+        .validate()
+        .map_err(|e| Error::ValidationFailed(vec![e]))?;
+    let page_request = pagination.page_request().map_err(Error::InvalidParameter)?;
+    if let Some(after) = &page_request.after {
+        if !filter.sort.accepts(after) {
+            return Err(Error::InvalidParameter(String::from(
+                "cursor does not match the sort order",
+            )));
+        }
+    }
+    let mut page = store.get_questions(&filter, &page_request).await?;
+    if pagination.include_total {
+        page.total = Some(store.count_questions(&filter).await?);
+    }
+
+    Ok(page_response(uri, &page_request, page))
 }
 
 #[instrument]
@@ -54,10 +97,7 @@ pub async fn get_question_byid(
     Extension(session): Extension<Session>,
 ) -> Result<Json<Question>, Error> {
     event!(target:"axum-web-demo", Level::INFO, "get question by id");
-    let account_id = session.account_id;
-    if !store.is_question_owner(id, &account_id).await? {
-        return Err(Error::Unahthorized);
-    }
+    ensure_question_owner(&store, id, &session).await?;
     let res = match store.get_question_byid(id).await {
         Ok(res) => res,
         Err(e) => return Err(e),
@@ -69,11 +109,23 @@ pub async fn get_question_byid(
 #[instrument]
 pub async fn update_question(
     State(store): State<Store>,
+    State(policy): State<ContentPolicy>,
     Path(id): Path<i64>,
-    Json(question): Json<Question>,
+    Extension(session): Extension<Session>,
+    ValidJson(question): ValidJson<QuestionUpdate>,
 ) -> Result<Json<Question>, Error> {
     event!(target:"axum-web-dev", Level::INFO, "update question");
-    let res = match store.update_question(question, id).await {
+    ensure_question_owner(&store, id, &session).await?;
+    let question = QuestionUpdate {
+        title: policy.apply("title", question.title).await?,
+        content: policy.apply("content", question.content).await?,
+        tags: canonical_tags(&store, question.tags).await?,
+        ..question
+    };
+    let res = match store
+        .update_question(question, id, &session.account_id)
+        .await
+    {
         Err(e) => return Err(e),
         Ok(res) => res,
     };
@@ -85,11 +137,215 @@ pub async fn update_question(
 pub async fn delete_question(
     State(store): State<Store>,
     Path(id): Path<i64>,
+    Extension(session): Extension<Session>,
 ) -> Result<String, Error> {
     event!(target:"axum-web-dev", Level::INFO, "delete question");
-    if let Err(e) = store.delete_question(id).await {
-        return Err(e);
-    }
+    ensure_question_owner(&store, id, &session).await?;
+    store.delete_question(id, &session.account_id).await?;
 
     Ok(String::from("Question Deleted"))
 }
+
+/// Marks one of the question's answers as accepted, replacing any previously
+/// accepted answer. Only the question owner may do this.
+#[instrument]
+pub async fn accept_answer(
+    State(store): State<Store>,
+    Path(id): Path<i64>,
+    Extension(session): Extension<Session>,
+    ValidJson(accepted): ValidJson<AcceptedAnswer>,
+) -> Result<Json<Question>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "accept answer");
+    store.get_question_byid(id).await?;
+    if !store.is_question_owner(id, &session.account_id).await? {
+        return Err(Error::Forbidden);
+    }
+    let answer = store.get_answer_byid(accepted.answer_id.0 as i64).await?;
+    if answer.question_id.0 as i64 != id {
+        return Err(Error::ValidationFailed(vec![FieldError::new(
+            "answer_id",
+            "answer belongs to another question",
+        )]));
+    }
+    let res = store
+        .set_accepted_answer(id, Some(answer.id.0 as i64))
+        .await?;
+
+    Ok(Json(res))
+}
+
+#[instrument]
+pub async fn unaccept_answer(
+    State(store): State<Store>,
+    Path(id): Path<i64>,
+    Extension(session): Extension<Session>,
+) -> Result<Json<Question>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "unaccept answer");
+    store.get_question_byid(id).await?;
+    if !store.is_question_owner(id, &session.account_id).await? {
+        return Err(Error::Forbidden);
+    }
+    let res = store.set_accepted_answer(id, None).await?;
+
+    Ok(Json(res))
+}
+
+#[instrument]
+pub async fn vote_question(
+    State(store): State<Store>,
+    Path(id): Path<i64>,
+    Extension(session): Extension<Session>,
+    ValidJson(vote): ValidJson<NewVote>,
+) -> Result<Json<Question>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "vote on question");
+    ensure_not_question_owner(&store, id, &session).await?;
+    let target = VoteTarget::Question(QuestionId(id as i32));
+    store
+        .cast_vote(&target, &session.account_id, vote.direction)
+        .await?;
+    let res = store.get_question_byid(id).await?;
+
+    Ok(Json(res))
+}
+
+#[instrument]
+pub async fn retract_question_vote(
+    State(store): State<Store>,
+    Path(id): Path<i64>,
+    Extension(session): Extension<Session>,
+) -> Result<Json<Question>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "retract question vote");
+    store.get_question_byid(id).await?;
+    let target = VoteTarget::Question(QuestionId(id as i32));
+    store.retract_vote(&target, &session.account_id).await?;
+    let res = store.get_question_byid(id).await?;
+
+    Ok(Json(res))
+}
+
+#[instrument]
+pub async fn get_question_revisions(
+    State(store): State<Store>,
+    Path(id): Path<i64>,
+    OriginalUri(uri): OriginalUri,
+    Query(pagination): Query<Pagination>,
+    Extension(session): Extension<Session>,
+) -> Result<PageResponse<QuestionRevision>, Error> {
+    event!(target:"axum-web-demo", Level::INFO, "get question revisions");
+    let page_request = id_page_request(&pagination)?;
+    ensure_question_owner(&store, id, &session).await?;
+    let page = store.get_question_revisions(id, &page_request).await?;
+
+    Ok(page_response(&uri, &page_request, page))
+}
+
+#[instrument]
+pub async fn get_question_revision(
+    State(store): State<Store>,
+    Path((id, revision)): Path<(i64, i32)>,
+    Extension(session): Extension<Session>,
+) -> Result<Json<QuestionRevision>, Error> {
+    event!(target:"axum-web-demo", Level::INFO, "get question revision");
+    ensure_question_owner(&store, id, &session).await?;
+    let res = store.get_question_revision(id, revision).await?;
+
+    Ok(Json(res))
+}
+
+#[instrument]
+pub async fn diff_question_revisions(
+    State(store): State<Store>,
+    Path(id): Path<i64>,
+    Query(query): Query<DiffQuery>,
+    Extension(session): Extension<Session>,
+) -> Result<Json<RevisionDiff>, Error> {
+    event!(target:"axum-web-demo", Level::INFO, "diff question revisions");
+    ensure_question_owner(&store, id, &session).await?;
+    let from = store.get_question_revision(id, query.from).await?;
+    let to = store.get_question_revision(id, query.to).await?;
+
+    Ok(Json(RevisionDiff::questions(&from, &to)))
+}
+
+/// Stores the title, content and tags of an older revision as a new
+/// revision. Only the owner or a moderator may do this.
+#[instrument]
+pub async fn rollback_question(
+    State(store): State<Store>,
+    Path((id, revision)): Path<(i64, i32)>,
+    Extension(session): Extension<Session>,
+    ValidJson(rollback): ValidJson<Rollback>,
+) -> Result<Json<Question>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "rollback question");
+    ensure_question_owner(&store, id, &session).await?;
+    let target = store.get_question_revision(id, revision).await?;
+    let question = QuestionUpdate {
+        title: target.title,
+        content: target.content,
+        tags: canonical_tags(&store, target.tags).await?,
+        comment: Some(
+            rollback
+                .comment
+                .unwrap_or_else(|| format!("Rolled back to revision {}", revision)),
+        ),
+    };
+    let res = store
+        .update_question(question, id, &session.account_id)
+        .await?;
+
+    Ok(Json(res))
+}
+
+/// Normalizes tags to slugs and replaces synonyms, adding unknown tags to
+/// the catalogue.
+async fn canonical_tags(
+    store: &Store,
+    tags: Option<Vec<String>>,
+) -> Result<Option<Vec<String>>, Error> {
+    let Some(tags) = tags else {
+        return Ok(None);
+    };
+    let slugs = store.resolve_tags(normalize_tags(&tags)).await?;
+    // two synonyms of the same tag resolve to the same slug
+    let slugs = normalize_tags(&slugs);
+    store.ensure_tags(&slugs).await?;
+
+    Ok(Some(slugs))
+}
+
+async fn ensure_not_question_owner(
+    store: &Store,
+    question_id: i64,
+    session: &Session,
+) -> Result<(), Error> {
+    store.get_question_byid(question_id).await?;
+    if store
+        .is_question_owner(question_id, &session.account_id)
+        .await?
+    {
+        return Err(Error::CannotVoteOwnPost);
+    }
+
+    Ok(())
+}
+
+/// Fails with `QuestionNotFound` for a missing question and `Forbidden` when
+/// the question belongs to someone else. Moderators may act on any question.
+async fn ensure_question_owner(
+    store: &Store,
+    question_id: i64,
+    session: &Session,
+) -> Result<(), Error> {
+    store.get_question_byid(question_id).await?;
+    if session.has_role(Role::Moderator) {
+        return Ok(());
+    }
+    if !store
+        .is_question_owner(question_id, &session.account_id)
+        .await?
+    {
+        return Err(Error::Forbidden);
+    }
+
+    Ok(())
+}
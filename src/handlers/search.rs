@@ -0,0 +1,34 @@
+use axum::{extract::State, Json};
+use tracing::{event, instrument, Level};
+
+use crate::{
+    common::{error::Error, extract::Query},
+    models::{
+        search::{SearchQuery, SearchResult},
+        tag::normalize_tags,
+        Pagination,
+    },
+    repositories::store::Store,
+};
+
+#[instrument]
+pub async fn search(
+    State(store): State<Store>,
+    Query(pagination): Query<Pagination>,
+    Query(mut query): Query<SearchQuery>,
+) -> Result<Json<Vec<SearchResult>>, Error> {
+    event!(target:"axum-web-demo", Level::INFO, "search questions and answers");
+    if query.q.trim().is_empty() {
+        return Err(Error::MissingParameters);
+    }
+    if let Some(tags) = query.tag_list() {
+        let slugs = store.resolve_tags(normalize_tags(&tags)).await?;
+        query.tags = Some(slugs.join(","));
+    }
+    let page = pagination
+        .offset_request()
+        .map_err(Error::InvalidParameter)?;
+    let res = store.search(&query, page.offset, page.limit).await?;
+
+    Ok(Json(res))
+}
@@ -0,0 +1,124 @@
+use axum::{
+    extract::{OriginalUri, State},
+    Json,
+};
+use tracing::{event, instrument, Level};
+
+use crate::{
+    common::{
+        error::Error,
+        extract::{Path, Query},
+        guard::{Moderator, RequireRole},
+        validation::ValidJson,
+    },
+    handlers::{question::list_questions, PageResponse},
+    models::{
+        question::{Question, QuestionFilter},
+        tag::{slugify, Tag, TagQuery, TagUpdate},
+        Pagination,
+    },
+    repositories::store::Store,
+};
+
+/// Tag catalogue, most used first. `q` narrows it down by prefix, which is
+/// what autocomplete widgets need.
+#[instrument]
+pub async fn get_tags(
+    State(store): State<Store>,
+    Query(pagination): Query<Pagination>,
+    Query(mut query): Query<TagQuery>,
+) -> Result<Json<Vec<Tag>>, Error> {
+    event!(target:"axum-web-demo", Level::INFO, "get tags");
+    let page = pagination
+        .offset_request()
+        .map_err(Error::InvalidParameter)?;
+    query.q = query.q.as_deref().map(slugify);
+    let res = store.get_tags(&query, page.offset, page.limit).await?;
+
+    Ok(Json(res))
+}
+
+#[instrument]
+pub async fn get_tag(
+    State(store): State<Store>,
+    Path(slug): Path<String>,
+) -> Result<Json<Tag>, Error> {
+    event!(target:"axum-web-demo", Level::INFO, "get tag");
+    let res = store.get_tag(&slugify(&slug)).await?;
+
+    Ok(Json(res))
+}
+
+/// Same as the question listing with the tag filter set, synonyms in the
+/// path resolve to their tag.
+#[instrument]
+pub async fn get_tag_questions(
+    State(store): State<Store>,
+    Path(slug): Path<String>,
+    OriginalUri(uri): OriginalUri,
+    Query(pagination): Query<Pagination>,
+    Query(filter): Query<QuestionFilter>,
+) -> Result<PageResponse<Question>, Error> {
+    event!(target:"axum-web-demo", Level::INFO, "get questions of tag");
+    if filter.tags.is_some() {
+        return Err(Error::InvalidParameter(String::from(
+            "tags cannot be combined with a tag listing",
+        )));
+    }
+    let tag = store.get_tag(&slugify(&slug)).await?;
+    let filter = QuestionFilter {
+        tags: Some(tag.slug),
+        ..filter
+    };
+
+    list_questions(&store, &uri, pagination, filter).await
+}
+
+#[instrument]
+pub async fn update_tag(
+    State(store): State<Store>,
+    _moderator: RequireRole<Moderator>,
+    Path(slug): Path<String>,
+    ValidJson(update): ValidJson<TagUpdate>,
+) -> Result<Json<Tag>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "update tag");
+    let res = store
+        .update_tag_description(&slugify(&slug), update.description)
+        .await?;
+
+    Ok(Json(res))
+}
+
+/// Merges `synonym` into the tag, see `TagRepository::add_tag_synonym`.
+#[instrument]
+pub async fn add_tag_synonym(
+    State(store): State<Store>,
+    _moderator: RequireRole<Moderator>,
+    Path((slug, synonym)): Path<(String, String)>,
+) -> Result<Json<Tag>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "add tag synonym");
+    let synonym = slugify(&synonym);
+    if synonym.is_empty() {
+        return Err(Error::InvalidParameter(String::from(
+            "synonym must contain a letter or digit",
+        )));
+    }
+    let res = store.add_tag_synonym(&slugify(&slug), &synonym).await?;
+
+    Ok(Json(res))
+}
+
+/// Only stops future rewrites, questions retagged earlier keep the tag.
+#[instrument]
+pub async fn remove_tag_synonym(
+    State(store): State<Store>,
+    _moderator: RequireRole<Moderator>,
+    Path((slug, synonym)): Path<(String, String)>,
+) -> Result<Json<Tag>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "remove tag synonym");
+    let res = store
+        .remove_tag_synonym(&slugify(&slug), &slugify(&synonym))
+        .await?;
+
+    Ok(Json(res))
+}
@@ -0,0 +1,84 @@
+use axum::{
+    extract::{OriginalUri, State},
+    Json,
+};
+use tracing::{event, instrument, Level};
+
+use crate::{
+    common::{
+        error::Error,
+        extract::{Path, Query},
+        guard::{Moderator, RequireRole},
+    },
+    handlers::{page_response, PageResponse},
+    models::{answer::Answer, question::Question, CursorKey, PageRequest, Pagination},
+    repositories::store::Store,
+};
+
+#[instrument]
+pub async fn get_deleted_questions(
+    State(store): State<Store>,
+    _moderator: RequireRole<Moderator>,
+    OriginalUri(uri): OriginalUri,
+    Query(pagination): Query<Pagination>,
+) -> Result<PageResponse<Question>, Error> {
+    event!(target:"axum-web-demo", Level::INFO, "get deleted questions");
+    let page_request = trash_page_request(&pagination)?;
+    let page = store.get_deleted_questions(&page_request).await?;
+
+    Ok(page_response(&uri, &page_request, page))
+}
+
+#[instrument]
+pub async fn get_deleted_answers(
+    State(store): State<Store>,
+    _moderator: RequireRole<Moderator>,
+    OriginalUri(uri): OriginalUri,
+    Query(pagination): Query<Pagination>,
+) -> Result<PageResponse<Answer>, Error> {
+    event!(target:"axum-web-demo", Level::INFO, "get deleted answers");
+    let page_request = trash_page_request(&pagination)?;
+    let page = store.get_deleted_answers(&page_request).await?;
+
+    Ok(page_response(&uri, &page_request, page))
+}
+
+#[instrument]
+pub async fn restore_question(
+    State(store): State<Store>,
+    _moderator: RequireRole<Moderator>,
+    Path(id): Path<i64>,
+) -> Result<Json<Question>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "restore question");
+    let res = store.restore_question(id).await?;
+
+    Ok(Json(res))
+}
+
+/// Answers of a question that is still in the trash stay hidden until the
+/// question is restored as well.
+#[instrument]
+pub async fn restore_answer(
+    State(store): State<Store>,
+    _moderator: RequireRole<Moderator>,
+    Path(id): Path<i64>,
+) -> Result<Json<Answer>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "restore answer");
+    let res = store.restore_answer(id).await?;
+
+    Ok(Json(res))
+}
+
+/// Trash listings are ordered by deletion time, other cursors do not fit.
+fn trash_page_request(pagination: &Pagination) -> Result<PageRequest, Error> {
+    let page_request = pagination.page_request().map_err(Error::InvalidParameter)?;
+    if let Some(after) = &page_request.after {
+        if !matches!(after.key, CursorKey::Time(_)) || after.sort.is_some() {
+            return Err(Error::InvalidParameter(String::from(
+                "cursor does not belong to this listing",
+            )));
+        }
+    }
+
+    Ok(page_request)
+}
@@ -0,0 +1,368 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Extension, Json};
+use chrono::Utc;
+use rand::Rng;
+use totp_rs::{Algorithm, Secret, TOTP};
+use tracing::{event, Level};
+
+use crate::{
+    common::{
+        client_ip::ClientIp,
+        config::{AuthConfig, Config as AppConfig},
+        error::Error,
+        validation::ValidJson,
+    },
+    handlers::{
+        account::{check_password, hash_passowrd, start_session, verify_password},
+        lockout::{check_login_allowed, clear_login_failures, record_login_failure},
+    },
+    models::{
+        account::{Account, AccountId, Session},
+        login_attempt::LoginKeys,
+        session::TokenPair,
+        two_factor::{
+            ChallengeClaims, RecoveryCodes, TwoFactorChallenge, TwoFactorCode, TwoFactorDisable,
+            TwoFactorEnabled, TwoFactorEnroll, TwoFactorLogin, TwoFactorSetup, RECOVERY_CODE_COUNT,
+        },
+    },
+    repositories::store::Store,
+};
+
+/// RFC 6238 defaults, the ones every authenticator app supports.
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP: u64 = 30;
+const CHALLENGE_PURPOSE: &str = "two_factor";
+
+/// Starts enrollment with a fresh secret, 2FA is only on after `confirm`.
+pub async fn enroll_two_factor(
+    State(store): State<Store>,
+    State(config): State<Arc<AppConfig>>,
+    Extension(session): Extension<Session>,
+    ValidJson(request): ValidJson<TwoFactorEnroll>,
+) -> Result<Json<TwoFactorSetup>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "enroll two-factor authentication");
+    let account = store.get_account_byid(&session.account_id).await?;
+    check_password(&request.password, &account)?;
+    if account.two_factor_enabled {
+        return Err(Error::InvalidParameter(String::from(
+            "two-factor authentication is already enabled",
+        )));
+    }
+    let secret = Secret::Raw(rand::thread_rng().gen::<[u8; 20]>().to_vec())
+        .to_encoded()
+        .to_string();
+    store
+        .set_totp_secret(&session.account_id, secret.clone())
+        .await?;
+    let otpauth_uri = totp(&secret, &account, &config.auth)?.get_url();
+
+    Ok(Json(TwoFactorSetup {
+        secret,
+        otpauth_uri,
+    }))
+}
+
+/// Turns 2FA on once the authenticator shows a matching code. Every session
+/// is signed out, the caller continues with the returned pair.
+pub async fn confirm_two_factor(
+    State(store): State<Store>,
+    State(config): State<Arc<AppConfig>>,
+    Extension(session): Extension<Session>,
+    ValidJson(request): ValidJson<TwoFactorCode>,
+) -> Result<Json<TwoFactorEnabled>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "confirm two-factor authentication");
+    let account = store.get_account_byid(&session.account_id).await?;
+    let two_factor = store.get_two_factor(&session.account_id).await?;
+    let secret = match two_factor.secret {
+        Some(secret) if !two_factor.enabled => secret,
+        _ => {
+            return Err(Error::InvalidParameter(String::from(
+                "no two-factor enrollment in progress",
+            )))
+        }
+    };
+    let step = matching_step(&totp(&secret, &account, &config.auth)?, &request.code)
+        .ok_or(Error::InvalidTwoFactorCode)?;
+    let (recovery_codes, code_hashes) = generate_recovery_codes();
+    store
+        .enable_two_factor(&session.account_id, step, code_hashes)
+        .await?;
+    store.revoke_account_sessions(&session.account_id).await?;
+    let account = store.get_account_byid(&session.account_id).await?;
+    let tokens = start_session(&store, &account, &config.auth).await?;
+
+    Ok(Json(TwoFactorEnabled {
+        recovery_codes,
+        tokens,
+    }))
+}
+
+pub async fn disable_two_factor(
+    State(store): State<Store>,
+    State(config): State<Arc<AppConfig>>,
+    Extension(session): Extension<Session>,
+    ValidJson(request): ValidJson<TwoFactorDisable>,
+) -> Result<String, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "disable two-factor authentication");
+    let account = store.get_account_byid(&session.account_id).await?;
+    check_password(&request.password, &account)?;
+    check_second_factor(&store, &config.auth, &account, &request.code, true).await?;
+    store.disable_two_factor(&session.account_id).await?;
+
+    Ok(String::from("Two-factor authentication disabled"))
+}
+
+/// Replaces every recovery code, used or not.
+pub async fn regenerate_recovery_codes(
+    State(store): State<Store>,
+    State(config): State<Arc<AppConfig>>,
+    Extension(session): Extension<Session>,
+    ValidJson(request): ValidJson<TwoFactorCode>,
+) -> Result<Json<RecoveryCodes>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "regenerate recovery codes");
+    let account = store.get_account_byid(&session.account_id).await?;
+    check_second_factor(&store, &config.auth, &account, &request.code, false).await?;
+    let (recovery_codes, code_hashes) = generate_recovery_codes();
+    store
+        .replace_recovery_codes(&session.account_id, code_hashes)
+        .await?;
+
+    Ok(Json(RecoveryCodes { recovery_codes }))
+}
+
+/// Second login step, takes the challenge from `login` and a TOTP or
+/// recovery code. Wrong codes count towards the same lockout as passwords.
+pub async fn login_two_factor(
+    State(store): State<Store>,
+    State(config): State<Arc<AppConfig>>,
+    ClientIp(ip): ClientIp,
+    ValidJson(request): ValidJson<TwoFactorLogin>,
+) -> Result<Json<TokenPair>, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "two-factor login");
+    let account_id = verify_challenge(&request.challenge_token, &config.auth)?;
+    let account = match store.get_account_byid(&account_id).await {
+        Ok(account) => account,
+        Err(Error::AccountNotFound) => return Err(Error::CannotDecryptToken),
+        Err(e) => return Err(e),
+    };
+    let keys = LoginKeys::new(&account.email, ip);
+    check_login_allowed(&store, &config.auth, &keys).await?;
+    match check_second_factor(&store, &config.auth, &account, &request.code, true).await {
+        Ok(()) => clear_login_failures(&store, &keys).await?,
+        Err(Error::InvalidTwoFactorCode) => {
+            record_login_failure(&store, &config.auth, &keys).await?;
+            return Err(Error::InvalidTwoFactorCode);
+        }
+        Err(e) => return Err(e),
+    }
+    let res = start_session(&store, &account, &config.auth).await?;
+
+    Ok(Json(res))
+}
+
+/// Short lived token proving the password step, it carries no session so it
+/// is useless as an access token.
+pub fn issue_challenge(account_id: AccountId, config: &AuthConfig) -> TwoFactorChallenge {
+    let dt = Utc::now() + chrono::Duration::seconds(config.two_factor_challenge_ttl);
+    let challenge_token = paseto::tokens::PasetoBuilder::new()
+        .set_encryption_key(config.token_key.as_bytes())
+        .set_expiration(&dt)
+        .set_not_before(&Utc::now())
+        .set_claim("account_id", serde_json::json!(account_id))
+        .set_claim("purpose", serde_json::json!(CHALLENGE_PURPOSE))
+        .build()
+        .expect("Failed to construct paseto token w/ builder!");
+
+    TwoFactorChallenge {
+        two_factor_required: true,
+        challenge_token,
+        expires_in: config.two_factor_challenge_ttl,
+    }
+}
+
+fn verify_challenge(token: &str, config: &AuthConfig) -> Result<AccountId, Error> {
+    let token = paseto::tokens::validate_local_token(
+        token,
+        None,
+        config.token_key.as_bytes(),
+        &paseto::tokens::TimeBackend::Chrono,
+    )
+    .map_err(|_| Error::CannotDecryptToken)?;
+    match serde_json::from_value::<ChallengeClaims>(token) {
+        Ok(claims) if claims.purpose == CHALLENGE_PURPOSE => Ok(claims.account_id),
+        _ => Err(Error::CannotDecryptToken),
+    }
+}
+
+/// Accepts a TOTP code for an account with 2FA on, or one of its unused
+/// recovery codes when `allow_recovery`. Either works only once.
+async fn check_second_factor(
+    store: &Store,
+    config: &AuthConfig,
+    account: &Account,
+    code: &str,
+    allow_recovery: bool,
+) -> Result<(), Error> {
+    let account_id = account.id.clone().expect("id not found");
+    let two_factor = store.get_two_factor(&account_id).await?;
+    let secret = match two_factor.secret {
+        Some(secret) if two_factor.enabled => secret,
+        _ => {
+            return Err(Error::InvalidParameter(String::from(
+                "two-factor authentication is not enabled",
+            )))
+        }
+    };
+
+    let code = code.trim();
+    if code.len() == TOTP_DIGITS && code.chars().all(|c| c.is_ascii_digit()) {
+        return match matching_step(&totp(&secret, account, config)?, code) {
+            Some(step) if store.use_totp_step(&account_id, step).await? => Ok(()),
+            _ => Err(Error::InvalidTwoFactorCode),
+        };
+    }
+    if allow_recovery {
+        let code = normalize_recovery_code(code);
+        for recovery_code in store.get_recovery_codes(&account_id).await? {
+            match verify_password(code.as_bytes(), &recovery_code.code_hash) {
+                Ok(true) if store.use_recovery_code(recovery_code.id).await? => {
+                    event!(target:"axum-web-dev", Level::INFO, "recovery code used");
+                    return Ok(());
+                }
+                Ok(_) => (),
+                Err(e) => return Err(Error::ArgonLibraryError(e)),
+            }
+        }
+    }
+
+    Err(Error::InvalidTwoFactorCode)
+}
+
+fn totp(secret: &str, account: &Account, config: &AuthConfig) -> Result<TOTP, Error> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|_| Error::InvalidTwoFactorCode)?;
+    // unchecked as the label is the email, which may legally contain ':'
+    Ok(TOTP::new_unchecked(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        0,
+        TOTP_STEP,
+        secret,
+        Some(config.totp_issuer.clone()),
+        account.email.clone(),
+    ))
+}
+
+/// Time step the code belongs to, allowing one step of clock drift either way.
+fn matching_step(totp: &TOTP, code: &str) -> Option<i64> {
+    let current = Utc::now().timestamp() as u64 / TOTP_STEP;
+    (current - 1..=current + 1)
+        .find(|step| totp.check(code.trim(), step * TOTP_STEP))
+        .map(|step| step as i64)
+}
+
+/// Plain codes for the user and their hashes for the store.
+fn generate_recovery_codes() -> (Vec<String>, Vec<String>) {
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let hex: String = rand::thread_rng()
+                .gen::<[u8; 5]>()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            format!("{}-{}", &hex[..5], &hex[5..])
+        })
+        .collect();
+    let hashes = codes
+        .iter()
+        .map(|code| hash_passowrd(normalize_recovery_code(code).as_bytes()))
+        .collect();
+
+    (codes, hashes)
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::repositories::memory::MemoryStore;
+
+    /// Account with 2FA on, its last accepted code two steps back.
+    async fn enrolled(store: &Store, secret: &str) -> Account {
+        let account: Account =
+            serde_json::from_value(json!({ "email": "ann@example.com", "password": "unused" }))
+                .unwrap();
+        store.add_account(account).await.unwrap();
+        let account = store
+            .get_account(String::from("ann@example.com"))
+            .await
+            .unwrap();
+        let account_id = account.id.clone().unwrap();
+        let current = Utc::now().timestamp() / TOTP_STEP as i64;
+        store
+            .set_totp_secret(&account_id, secret.to_string())
+            .await
+            .unwrap();
+        store
+            .enable_two_factor(&account_id, current - 2, Vec::new())
+            .await
+            .unwrap();
+        store.get_account_byid(&account_id).await.unwrap()
+    }
+
+    fn code_at(totp: &TOTP, step: i64) -> String {
+        totp.generate(step as u64 * TOTP_STEP)
+    }
+
+    #[tokio::test]
+    async fn totp_codes_work_once() {
+        let store: Store = Arc::new(MemoryStore::new());
+        let config = AuthConfig::default();
+        let secret = Secret::Raw(vec![7; 20]).to_encoded().to_string();
+        let account = enrolled(&store, &secret).await;
+        let totp = totp(&secret, &account, &config).unwrap();
+        let current = Utc::now().timestamp() / TOTP_STEP as i64;
+
+        let code = code_at(&totp, current);
+        assert!(check_second_factor(&store, &config, &account, &code, false)
+            .await
+            .is_ok());
+        assert!(matches!(
+            check_second_factor(&store, &config, &account, &code, false).await,
+            Err(Error::InvalidTwoFactorCode)
+        ));
+
+        // codes from the drift window before an accepted step are spent too
+        let earlier = code_at(&totp, current - 1);
+        assert!(matches!(
+            check_second_factor(&store, &config, &account, &earlier, false).await,
+            Err(Error::InvalidTwoFactorCode)
+        ));
+    }
+
+    #[tokio::test]
+    async fn codes_outside_the_drift_window_are_refused() {
+        let store: Store = Arc::new(MemoryStore::new());
+        let config = AuthConfig::default();
+        let secret = Secret::Raw(vec![9; 20]).to_encoded().to_string();
+        let account = enrolled(&store, &secret).await;
+        let totp = totp(&secret, &account, &config).unwrap();
+        let current = Utc::now().timestamp() / TOTP_STEP as i64;
+
+        assert_eq!(
+            matching_step(&totp, &code_at(&totp, current + 1)),
+            Some(current + 1)
+        );
+        assert_eq!(matching_step(&totp, &code_at(&totp, current - 3)), None);
+    }
+}
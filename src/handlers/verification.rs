@@ -0,0 +1,254 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Extension};
+use chrono::Utc;
+use tracing::{event, Level};
+use uuid::Uuid;
+
+use crate::{
+    common::{
+        config::{AuthConfig, Config as AppConfig},
+        error::Error,
+        mailer::{Email, Mailer},
+        validation::ValidJson,
+    },
+    handlers::account::{generate_secret, hash_passowrd, verify_password},
+    models::{
+        account::{Account, AccountId, Role, Session},
+        account_token::{
+            AccountToken, AccountTokenId, EmailVerification, PasswordForgot, PasswordReset,
+            TokenPurpose,
+        },
+        login_attempt::email_key,
+    },
+    repositories::store::Store,
+};
+
+/// Confirms the address a verification mail was sent to. Tokens issued
+/// afterwards carry the verified claim, so clients should refresh.
+pub async fn verify_email(
+    State(store): State<Store>,
+    State(config): State<Arc<AppConfig>>,
+    ValidJson(request): ValidJson<EmailVerification>,
+) -> Result<String, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "verify email");
+    let token = redeem_token(&store, &request.token, TokenPurpose::VerifyEmail).await?;
+    let was_verified = store
+        .get_account_byid(&token.account_id)
+        .await?
+        .email_verified;
+    // the email changed again since the mail went out
+    if !store
+        .mark_email_verified(&token.account_id, &token.email)
+        .await?
+    {
+        return Err(Error::InvalidOneTimeToken);
+    }
+    if !was_verified {
+        grant_admin_email(&store, &config.auth, &token.account_id, &token.email).await?;
+    }
+
+    Ok(String::from("Email verified"))
+}
+
+pub async fn resend_verification(
+    State(store): State<Store>,
+    State(config): State<Arc<AppConfig>>,
+    State(mailer): State<Arc<dyn Mailer>>,
+    Extension(session): Extension<Session>,
+) -> Result<String, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "resend verification mail");
+    let account = store.get_account_byid(&session.account_id).await?;
+    if account.email_verified {
+        return Ok(String::from("Email already verified"));
+    }
+    send_verification_email(&store, mailer.as_ref(), &config, &account).await?;
+
+    Ok(String::from("Verification mail sent"))
+}
+
+/// Answers the same whether or not the account exists.
+pub async fn forgot_password(
+    State(store): State<Store>,
+    State(config): State<Arc<AppConfig>>,
+    State(mailer): State<Arc<dyn Mailer>>,
+    ValidJson(request): ValidJson<PasswordForgot>,
+) -> Result<String, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "forgot password");
+    match store.get_account(request.email).await {
+        // sent off the request path, waiting for the mail server would give
+        // away which addresses have an account
+        Ok(account) => {
+            tokio::spawn(async move {
+                if let Err(e) = send_reset_email(&store, mailer.as_ref(), &config, &account).await {
+                    event!(target:"axum-web-dev", Level::WARN, "cannot send reset mail: {}", e);
+                }
+            });
+        }
+        Err(Error::AccountNotFound) => (),
+        Err(e) => return Err(e),
+    }
+
+    Ok(String::from(
+        "If the account exists, a reset link has been sent",
+    ))
+}
+
+/// Sets a new password and signs out every session. Following the link
+/// also proves the address, so it counts as verified and lifts a lockout.
+pub async fn reset_password(
+    State(store): State<Store>,
+    State(config): State<Arc<AppConfig>>,
+    ValidJson(request): ValidJson<PasswordReset>,
+) -> Result<String, Error> {
+    event!(target:"axum-web-dev", Level::INFO, "reset password");
+    let token = redeem_token(&store, &request.token, TokenPurpose::ResetPassword).await?;
+    let was_verified = store
+        .get_account_byid(&token.account_id)
+        .await?
+        .email_verified;
+    store
+        .update_password(
+            &token.account_id,
+            hash_passowrd(request.new_password.as_bytes()),
+        )
+        .await?;
+    store
+        .revoke_account_tokens(&token.account_id, TokenPurpose::ResetPassword)
+        .await?;
+    store.revoke_account_sessions(&token.account_id).await?;
+    if store
+        .mark_email_verified(&token.account_id, &token.email)
+        .await?
+        && !was_verified
+    {
+        grant_admin_email(&store, &config.auth, &token.account_id, &token.email).await?;
+    }
+    store.clear_login_attempts(&email_key(&token.email)).await?;
+
+    Ok(String::from("Password reset"))
+}
+
+pub async fn send_verification_email(
+    store: &Store,
+    mailer: &dyn Mailer,
+    config: &AppConfig,
+    account: &Account,
+) -> Result<(), Error> {
+    let token = issue_token(
+        store,
+        account,
+        TokenPurpose::VerifyEmail,
+        config.auth.verification_token_ttl,
+    )
+    .await?;
+    mailer
+        .send(&Email {
+            to: account.email.clone(),
+            subject: String::from("Confirm your email address"),
+            body: format!(
+                "Hi {},\n\nplease confirm your email address by opening\n\n{}/verify-email?token={}\n\nThe link is valid for {} hours.\n",
+                account.display_name,
+                config.mail.public_url,
+                token,
+                config.auth.verification_token_ttl / 3600
+            ),
+        })
+        .await
+}
+
+async fn send_reset_email(
+    store: &Store,
+    mailer: &dyn Mailer,
+    config: &AppConfig,
+    account: &Account,
+) -> Result<(), Error> {
+    let token = issue_token(
+        store,
+        account,
+        TokenPurpose::ResetPassword,
+        config.auth.reset_token_ttl,
+    )
+    .await?;
+    mailer
+        .send(&Email {
+            to: account.email.clone(),
+            subject: String::from("Reset your password"),
+            body: format!(
+                "Hi {},\n\nsomeone asked to reset your password. To choose a new one open\n\n{}/reset-password?token={}\n\nThe link is valid for {} minutes. If it was not you, ignore this mail.\n",
+                account.display_name,
+                config.mail.public_url,
+                token,
+                config.auth.reset_token_ttl / 60
+            ),
+        })
+        .await
+}
+
+/// Promotes the owner of an `auth.admin_emails` address the moment the
+/// address is proven. Only on that change from unverified, so a later
+/// demotion by another admin sticks.
+async fn grant_admin_email(
+    store: &Store,
+    config: &AuthConfig,
+    account_id: &AccountId,
+    email: &str,
+) -> Result<(), Error> {
+    if config.is_admin_email(email) {
+        event!(target:"axum-web-dev", Level::INFO, "admin email verified, granting admin role");
+        store.update_account_role(account_id, Role::Admin).await?;
+    }
+    Ok(())
+}
+
+/// Stores a new token, replacing earlier ones for the same purpose, and
+/// returns it in its mailed `<id>.<secret>` form.
+async fn issue_token(
+    store: &Store,
+    account: &Account,
+    purpose: TokenPurpose,
+    ttl: i64,
+) -> Result<String, Error> {
+    let account_id = account.id.clone().expect("id not found");
+    store.revoke_account_tokens(&account_id, purpose).await?;
+    let token_id = AccountTokenId(Uuid::new_v4());
+    let secret = generate_secret();
+    store
+        .add_account_token(AccountToken {
+            id: token_id.clone(),
+            account_id,
+            purpose,
+            secret_hash: hash_passowrd(secret.as_bytes()),
+            email: account.email.clone(),
+            expires_on: Utc::now() + chrono::Duration::seconds(ttl),
+            used_on: None,
+        })
+        .await?;
+
+    Ok(format!("{}.{}", token_id.0, secret))
+}
+
+/// Checks the token and marks it used, so it works exactly once.
+async fn redeem_token(
+    store: &Store,
+    token: &str,
+    purpose: TokenPurpose,
+) -> Result<AccountToken, Error> {
+    let (token_id, secret) = token.split_once('.').ok_or(Error::InvalidOneTimeToken)?;
+    let token_id =
+        AccountTokenId(Uuid::parse_str(token_id).map_err(|_| Error::InvalidOneTimeToken)?);
+    let token = match store.get_account_token(&token_id).await? {
+        Some(token) if token.purpose == purpose && token.is_usable() => token,
+        _ => return Err(Error::InvalidOneTimeToken),
+    };
+    match verify_password(secret.as_bytes(), &token.secret_hash) {
+        Ok(true) => (),
+        Ok(false) => return Err(Error::InvalidOneTimeToken),
+        Err(e) => return Err(Error::ArgonLibraryError(e)),
+    }
+    if !store.use_account_token(&token_id).await? {
+        return Err(Error::InvalidOneTimeToken);
+    }
+
+    Ok(token)
+}
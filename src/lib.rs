@@ -0,0 +1,8 @@
+//! The API server as a library, so integration tests can build the router
+//! over the in-memory store.
+
+pub mod common;
+pub mod handlers;
+pub mod models;
+pub mod repositories;
+pub mod routers;
@@ -1,19 +1,268 @@
+use std::str::FromStr;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use super::{api_key::ApiKeyScope, session::SessionId};
+use crate::common::{
+    config::ValidationConfig,
+    validation::{Checks, Validate, EMAIL_MAX_LENGTH},
+};
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Account {
     pub id: Option<AccountId>,
     pub email: String,
     pub password: String,
+    /// Shown next to posts, defaults to the local part of the email.
+    #[serde(default)]
+    pub display_name: String,
+    /// Never taken from request payloads, new accounts always start as `User`.
+    #[serde(skip_deserializing, default)]
+    pub role: Role,
+    #[serde(skip_deserializing, default)]
+    pub bio: String,
+    #[serde(skip_deserializing, default)]
+    pub avatar_url: Option<String>,
+    /// Cleared again whenever the email changes.
+    #[serde(skip_deserializing, default)]
+    pub email_verified: bool,
+    /// Login needs a TOTP or recovery code on top of the password.
+    #[serde(skip_deserializing, default)]
+    pub two_factor_enabled: bool,
+    #[serde(skip_deserializing, default)]
+    pub created_at: DateTime<Utc>,
+}
+
+impl Validate for Account {
+    fn validate(&self, rules: &ValidationConfig, checks: &mut Checks) {
+        checks
+            .email("email", &self.email)
+            .password("password", &self.password, rules)
+            .max_length("display_name", &self.display_name, 255);
+    }
+}
+
+/// Login payload, checked against the stored hash only so accounts created
+/// under an older password policy can still sign in.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Credentials {
+    pub email: String,
+    pub password: String,
+}
+
+/// The email still has to fit, as registered and as the lockout key, or a
+/// padded one would fail to count as a failed attempt.
+impl Validate for Credentials {
+    fn validate(&self, _rules: &ValidationConfig, checks: &mut Checks) {
+        checks.max_length("email", &self.email, EMAIL_MAX_LENGTH);
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct AccountId(pub i32);
 
+pub const BIO_MAX_LENGTH: usize = 2000;
+pub const AVATAR_URL_MAX_LENGTH: usize = 2048;
+/// Shown in place of the author of posts whose account was deleted.
+pub const DELETED_DISPLAY_NAME: &str = "Deleted user";
+
+/// The signed in account as it sees itself.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Profile {
+    pub id: AccountId,
+    pub email: String,
+    pub email_verified: bool,
+    pub two_factor_enabled: bool,
+    pub display_name: String,
+    pub bio: String,
+    pub avatar_url: Option<String>,
+    pub role: Role,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<Account> for Profile {
+    fn from(account: Account) -> Self {
+        Profile {
+            id: account.id.expect("stored account without id"),
+            email: account.email,
+            email_verified: account.email_verified,
+            two_factor_enabled: account.two_factor_enabled,
+            display_name: account.display_name,
+            bio: account.bio,
+            avatar_url: account.avatar_url,
+            role: account.role,
+            created_at: account.created_at,
+        }
+    }
+}
+
+/// Profile as anyone may see it, without the email.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PublicProfile {
+    pub id: AccountId,
+    pub display_name: String,
+    pub bio: String,
+    pub avatar_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<Account> for PublicProfile {
+    fn from(account: Account) -> Self {
+        PublicProfile {
+            id: account.id.expect("stored account without id"),
+            display_name: account.display_name,
+            bio: account.bio,
+            avatar_url: account.avatar_url,
+            created_at: account.created_at,
+        }
+    }
+}
+
+/// Partial profile edit, missing fields are left alone. An empty
+/// `avatar_url` removes the avatar.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ProfileUpdate {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+impl Validate for ProfileUpdate {
+    fn validate(&self, _rules: &ValidationConfig, checks: &mut Checks) {
+        if let Some(display_name) = &self.display_name {
+            checks.not_blank("display_name", display_name).max_length(
+                "display_name",
+                display_name,
+                255,
+            );
+        }
+        if let Some(bio) = &self.bio {
+            checks.max_length("bio", bio, BIO_MAX_LENGTH);
+        }
+        if let Some(avatar_url) = self.avatar_url.as_deref().filter(|url| !url.is_empty()) {
+            let is_http = avatar_url.starts_with("https://") || avatar_url.starts_with("http://");
+            checks
+                .check("avatar_url", is_http, "must be an http or https URL")
+                .max_length("avatar_url", avatar_url, AVATAR_URL_MAX_LENGTH);
+        }
+    }
+}
+
+/// The new address has to be verified again.
+#[derive(Deserialize, Debug, Clone)]
+pub struct EmailChange {
+    pub email: String,
+    pub current_password: String,
+}
+
+impl Validate for EmailChange {
+    fn validate(&self, _rules: &ValidationConfig, checks: &mut Checks) {
+        checks.email("email", &self.email);
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PasswordChange {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+impl Validate for PasswordChange {
+    fn validate(&self, rules: &ValidationConfig, checks: &mut Checks) {
+        checks.password("new_password", &self.new_password, rules);
+    }
+}
+
+/// Deleting an account asks for the password once more.
+#[derive(Deserialize, Debug, Clone)]
+pub struct AccountDeletion {
+    pub password: String,
+}
+
+impl Validate for AccountDeletion {}
+
+/// Public face of an account, embedded in questions and answers.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AuthorSummary {
+    pub id: AccountId,
+    pub display_name: String,
+}
+
+/// Account as exposed to admins, without credentials.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AccountInfo {
+    pub id: AccountId,
+    pub email: String,
+    pub role: Role,
+}
+
+/// Roles are ordered, a higher role has every permission of the lower ones.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    User,
+    Moderator,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user" => Ok(Role::User),
+            "moderator" => Ok(Role::Moderator),
+            "admin" => Ok(Role::Admin),
+            _ => Err(format!("unknown role {:?}", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RoleUpdate {
+    pub role: Role,
+}
+
+impl Validate for RoleUpdate {}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Session {
     pub exp: DateTime<Utc>,
     pub account_id: AccountId,
     pub nbf: DateTime<Utc>,
+    pub jti: SessionId,
+    /// Tokens issued before roles existed carry no role claim.
+    #[serde(default)]
+    pub role: Role,
+    /// Same for the verification claim, those accounts were all verified.
+    #[serde(default = "verified_by_default")]
+    pub email_verified: bool,
+    /// Signed in with a second factor.
+    #[serde(default)]
+    pub two_factor: bool,
+    /// Set when the request came with an API key instead of a token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<ApiKeyScope>,
+}
+
+fn verified_by_default() -> bool {
+    true
+}
+
+impl Session {
+    pub fn has_role(&self, role: Role) -> bool {
+        self.role >= role
+    }
 }
@@ -0,0 +1,91 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::account::AccountId;
+use crate::common::{
+    config::ValidationConfig,
+    validation::{Checks, Validate},
+};
+
+/// Single use token mailed to the account owner as `<id>.<secret>`, only a
+/// hash of the secret is stored.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct AccountTokenId(pub Uuid);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenPurpose {
+    VerifyEmail,
+    ResetPassword,
+}
+
+impl TokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenPurpose::VerifyEmail => "verify_email",
+            TokenPurpose::ResetPassword => "reset_password",
+        }
+    }
+}
+
+impl FromStr for TokenPurpose {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "verify_email" => Ok(TokenPurpose::VerifyEmail),
+            "reset_password" => Ok(TokenPurpose::ResetPassword),
+            _ => Err(format!("unknown token purpose {:?}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AccountToken {
+    pub id: AccountTokenId,
+    pub account_id: AccountId,
+    pub purpose: TokenPurpose,
+    pub secret_hash: String,
+    /// Address the token was mailed to
+    pub email: String,
+    pub expires_on: DateTime<Utc>,
+    pub used_on: Option<DateTime<Utc>>,
+}
+
+impl AccountToken {
+    pub fn is_usable(&self) -> bool {
+        self.used_on.is_none() && self.expires_on > Utc::now()
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct EmailVerification {
+    pub token: String,
+}
+
+impl Validate for EmailVerification {}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PasswordForgot {
+    pub email: String,
+}
+
+impl Validate for PasswordForgot {
+    fn validate(&self, _rules: &ValidationConfig, checks: &mut Checks) {
+        checks.email("email", &self.email);
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PasswordReset {
+    pub token: String,
+    pub new_password: String,
+}
+
+impl Validate for PasswordReset {
+    fn validate(&self, rules: &ValidationConfig, checks: &mut Checks) {
+        checks.password("new_password", &self.new_password, rules);
+    }
+}
@@ -1,6 +1,15 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use super::question::QuestionId;
+use super::{
+    account::{AccountId, AuthorSummary},
+    question::QuestionId,
+    revision::validate_comment,
+};
+use crate::common::{
+    config::ValidationConfig,
+    validation::{Checks, Validate},
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 pub struct AnswerId(pub i32);
@@ -10,6 +19,21 @@ pub struct Answer {
     pub id: AnswerId,
     pub content: String,
     pub question_id: QuestionId,
+    /// Sum of up and down votes
+    pub score: i32,
+    /// Replies included
+    pub comment_count: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// `None` for answers predating account ownership
+    pub author: Option<AuthorSummary>,
+    /// Number of the latest revision, bumped by every edit
+    pub revision: i32,
+    /// Only set on answers in the trash
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<AccountId>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -17,3 +41,32 @@ pub struct NewAnswer {
     pub content: String,
     pub question_id: QuestionId,
 }
+
+impl Validate for NewAnswer {
+    fn validate(&self, rules: &ValidationConfig, checks: &mut Checks) {
+        checks.not_blank("content", &self.content).max_length(
+            "content",
+            &self.content,
+            rules.content_max_length,
+        );
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AnswerUpdate {
+    pub content: String,
+    /// Optional edit summary shown in the revision history
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+impl Validate for AnswerUpdate {
+    fn validate(&self, rules: &ValidationConfig, checks: &mut Checks) {
+        checks.not_blank("content", &self.content).max_length(
+            "content",
+            &self.content,
+            rules.content_max_length,
+        );
+        validate_comment(&self.comment, checks);
+    }
+}
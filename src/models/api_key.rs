@@ -0,0 +1,105 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::account::AccountId;
+use crate::common::{
+    config::ValidationConfig,
+    validation::{Checks, Validate},
+};
+
+pub const API_KEY_NAME_MAX_LENGTH: usize = 100;
+
+/// Long lived credential for scripts, sent as `Authorization: ApiKey
+/// <id>.<secret>`. Only a hash of the secret is stored.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct ApiKeyId(pub Uuid);
+
+/// `Read` keys are limited to `GET` requests.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyScope {
+    Read,
+    Write,
+}
+
+impl ApiKeyScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyScope::Read => "read",
+            ApiKeyScope::Write => "write",
+        }
+    }
+}
+
+impl FromStr for ApiKeyScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(ApiKeyScope::Read),
+            "write" => Ok(ApiKeyScope::Write),
+            _ => Err(format!("unknown api key scope {:?}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    pub id: ApiKeyId,
+    pub account_id: AccountId,
+    pub name: String,
+    pub scope: ApiKeyScope,
+    pub secret_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Key as listed to its owner, without the secret.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiKeyInfo {
+    pub id: ApiKeyId,
+    pub name: String,
+    pub scope: ApiKeyScope,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiKey> for ApiKeyInfo {
+    fn from(key: ApiKey) -> Self {
+        ApiKeyInfo {
+            id: key.id,
+            name: key.name,
+            scope: key.scope,
+            created_at: key.created_at,
+            last_used_at: key.last_used_at,
+        }
+    }
+}
+
+/// The full key is only part of this response, it cannot be shown again.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreatedApiKey {
+    pub key: String,
+    #[serde(flatten)]
+    pub info: ApiKeyInfo,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewApiKey {
+    pub name: String,
+    pub scope: ApiKeyScope,
+}
+
+impl Validate for NewApiKey {
+    fn validate(&self, _rules: &ValidationConfig, checks: &mut Checks) {
+        checks.not_blank("name", &self.name).max_length(
+            "name",
+            &self.name,
+            API_KEY_NAME_MAX_LENGTH,
+        );
+    }
+}
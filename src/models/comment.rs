@@ -0,0 +1,78 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{account::AuthorSummary, answer::AnswerId, question::QuestionId};
+use crate::common::{
+    config::ValidationConfig,
+    validation::{Checks, Validate},
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct CommentId(pub i32);
+
+/// Short remark on a question or an answer. Exactly one of `question_id` and
+/// `answer_id` is set.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Comment {
+    pub id: CommentId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub question_id: Option<QuestionId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub answer_id: Option<AnswerId>,
+    /// Comment this one replies to, `None` at the top of a thread
+    pub parent_id: Option<CommentId>,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// `None` once the author's account is gone
+    pub author: Option<AuthorSummary>,
+}
+
+impl Comment {
+    pub fn target(&self) -> CommentTarget {
+        match (&self.question_id, &self.answer_id) {
+            (Some(question_id), _) => CommentTarget::Question(question_id.clone()),
+            (None, Some(answer_id)) => CommentTarget::Answer(answer_id.clone()),
+            (None, None) => unreachable!("comment without target"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NewComment {
+    pub content: String,
+    /// Comment to reply to, it must be on the same post
+    #[serde(default)]
+    pub parent_id: Option<CommentId>,
+}
+
+impl Validate for NewComment {
+    fn validate(&self, rules: &ValidationConfig, checks: &mut Checks) {
+        checks.not_blank("content", &self.content).max_length(
+            "content",
+            &self.content,
+            rules.comment_max_length,
+        );
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommentUpdate {
+    pub content: String,
+}
+
+impl Validate for CommentUpdate {
+    fn validate(&self, rules: &ValidationConfig, checks: &mut Checks) {
+        checks.not_blank("content", &self.content).max_length(
+            "content",
+            &self.content,
+            rules.comment_max_length,
+        );
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CommentTarget {
+    Question(QuestionId),
+    Answer(AnswerId),
+}
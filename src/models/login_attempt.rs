@@ -0,0 +1,32 @@
+use std::net::IpAddr;
+
+use chrono::{DateTime, Utc};
+
+/// Recent failed logins for one key.
+#[derive(Debug, Clone)]
+pub struct LoginAttempts {
+    pub failures: i32,
+    pub last_failure_at: DateTime<Utc>,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+/// Keys a login attempt counts against. Emails are tracked whether or not an
+/// account exists, so lockouts do not reveal which ones do.
+#[derive(Debug, Clone)]
+pub struct LoginKeys {
+    pub email: String,
+    pub ip: Option<String>,
+}
+
+impl LoginKeys {
+    pub fn new(email: &str, ip: Option<IpAddr>) -> Self {
+        LoginKeys {
+            email: email_key(email),
+            ip: ip.map(|ip| format!("ip:{}", ip)),
+        }
+    }
+}
+
+pub fn email_key(email: &str) -> String {
+    format!("email:{}", email.trim().to_lowercase())
+}
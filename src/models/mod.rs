@@ -1,11 +1,260 @@
-use serde::Deserialize;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize, Serializer};
+
+use self::question::QuestionSort;
 
 pub mod account;
+pub mod account_token;
 pub mod answer;
+pub mod api_key;
+pub mod comment;
+pub mod login_attempt;
 pub mod question;
+pub mod revision;
+pub mod search;
+pub mod session;
+pub mod tag;
+pub mod two_factor;
+pub mod vote;
+
+pub const DEFAULT_PAGE_LIMIT: i64 = 100;
+pub const MAX_PAGE_LIMIT: i64 = 100;
 
+/// Listing query parameters. Offset paging stays the default, passing a
+/// `cursor` from a previous page switches to keyset paging.
 #[derive(Debug, Deserialize, Default)]
 pub struct Pagination {
     pub offset: Option<i64>,
     pub limit: Option<i64>,
+    pub cursor: Option<String>,
+    /// Counting every match is not free, so `total` is opt in
+    #[serde(default)]
+    pub include_total: bool,
+}
+
+impl Pagination {
+    /// Validates the raw parameters, capping `limit` at `MAX_PAGE_LIMIT`.
+    pub fn page_request(&self) -> Result<PageRequest, String> {
+        let offset = self.offset.unwrap_or(0);
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        if offset < 0 || limit < 0 {
+            return Err(String::from("offset and limit must not be negative"));
+        }
+        let after = match &self.cursor {
+            Some(_) if self.offset.is_some() => {
+                return Err(String::from("offset and cursor cannot be combined"));
+            }
+            Some(cursor) => Some(Cursor::decode(cursor)?),
+            None => None,
+        };
+        Ok(PageRequest {
+            offset,
+            limit: limit.min(MAX_PAGE_LIMIT),
+            after,
+        })
+    }
+
+    /// Same as `page_request` for listings without keyset support.
+    pub fn offset_request(&self) -> Result<PageRequest, String> {
+        if self.cursor.is_some() {
+            return Err(String::from("cursor is not supported here"));
+        }
+        self.page_request()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PageRequest {
+    pub offset: i64,
+    pub limit: i64,
+    /// Resume after this row, `offset` is zero whenever this is set
+    pub after: Option<Cursor>,
+}
+
+/// Position of a row in a listing: the value of the sort column plus the row
+/// id as tie breaker. Clients only ever see the encoded form.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor {
+    pub key: CursorKey,
+    pub id: i32,
+    /// Order of the question listing that issued it, several orders share
+    /// the same kind of key
+    pub sort: Option<QuestionSort>,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CursorKey {
+    /// Ordered by id alone
+    Id,
+    Time(DateTime<Utc>),
+    Text(String),
+    Score(i32),
+}
+
+impl Cursor {
+    pub fn new(key: CursorKey, id: i32) -> Self {
+        Cursor {
+            key,
+            id,
+            sort: None,
+        }
+    }
+
+    pub fn with_sort(self, sort: QuestionSort) -> Self {
+        Cursor {
+            sort: Some(sort),
+            ..self
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        // serializing a plain tuple into a Vec cannot fail
+        URL_SAFE_NO_PAD
+            .encode(serde_json::to_vec(&(&self.key, self.id, self.sort)).unwrap_or_default())
+    }
+
+    pub fn decode(value: &str) -> Result<Self, String> {
+        URL_SAFE_NO_PAD
+            .decode(value)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .map(|(key, id, sort)| Cursor { key, id, sort })
+            .ok_or_else(|| String::from("cursor is malformed"))
+    }
+}
+
+impl Serialize for Cursor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.encode())
+    }
+}
+
+/// One page of a listing.
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Pass back as `cursor` to fetch the following page, `null` on the last one
+    pub next_cursor: Option<Cursor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<i64>,
+}
+
+impl<T> Page<T> {
+    /// Builds a page from up to `limit + 1` rows, the extra row only signals
+    /// that another page follows.
+    pub fn from_rows(mut rows: Vec<(T, Cursor)>, limit: i64) -> Self {
+        let next_cursor = if rows.len() as i64 > limit {
+            rows.truncate(limit as usize);
+            rows.last().map(|(_, cursor)| cursor.clone())
+        } else {
+            None
+        };
+        Page {
+            items: rows.into_iter().map(|(item, _)| item).collect(),
+            next_cursor,
+            total: None,
+        }
+    }
+}
+
+/// Splits a comma separated query parameter, dropping empty entries.
+pub fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn rows(ids: &[i32]) -> Vec<(i32, Cursor)> {
+        ids.iter()
+            .map(|&id| (id, Cursor::new(CursorKey::Id, id)))
+            .collect()
+    }
+
+    #[test]
+    fn cursors_survive_encoding() {
+        let created = Utc.with_ymd_and_hms(2024, 6, 1, 12, 30, 0).unwrap();
+        let cursors = [
+            Cursor::new(CursorKey::Id, 7),
+            Cursor::new(CursorKey::Time(created), 8).with_sort(QuestionSort::Newest),
+            Cursor::new(CursorKey::Text(String::from("axum")), 9).with_sort(QuestionSort::Title),
+            Cursor::new(CursorKey::Score(-3), 10).with_sort(QuestionSort::Score),
+        ];
+        for cursor in cursors {
+            assert_eq!(Cursor::decode(&cursor.encode()), Ok(cursor));
+        }
+    }
+
+    #[test]
+    fn malformed_cursors_are_refused() {
+        let not_a_tuple = URL_SAFE_NO_PAD.encode(br#"{"id":1}"#);
+        for value in ["", "not base64!", "bm90IGpzb24", not_a_tuple.as_str()] {
+            assert_eq!(
+                Cursor::decode(value),
+                Err(String::from("cursor is malformed")),
+                "{:?}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn sorts_only_accept_their_own_cursors() {
+        let created = Utc.with_ymd_and_hms(2024, 6, 1, 12, 30, 0).unwrap();
+        let newest = Cursor::new(CursorKey::Time(created), 1).with_sort(QuestionSort::Newest);
+        assert!(QuestionSort::Newest.accepts(&newest));
+        assert!(!QuestionSort::Oldest.accepts(&newest));
+        assert!(!QuestionSort::Title.accepts(&newest));
+
+        let untagged = Cursor::new(CursorKey::Time(created), 1);
+        assert!(!QuestionSort::Newest.accepts(&untagged));
+
+        let mismatched = Cursor::new(CursorKey::Score(4), 1).with_sort(QuestionSort::Newest);
+        assert!(!QuestionSort::Newest.accepts(&mismatched));
+    }
+
+    #[test]
+    fn pages_point_at_their_last_item_only_when_more_follow() {
+        let page = Page::from_rows(rows(&[1, 2, 3]), 2);
+        assert_eq!(page.items, vec![1, 2]);
+        assert_eq!(page.next_cursor, Some(Cursor::new(CursorKey::Id, 2)));
+
+        let page = Page::from_rows(rows(&[1, 2]), 2);
+        assert_eq!(page.items, vec![1, 2]);
+        assert_eq!(page.next_cursor, None);
+
+        let page = Page::from_rows(rows(&[]), 2);
+        assert!(page.items.is_empty());
+        assert_eq!(page.next_cursor, None);
+    }
+
+    #[test]
+    fn cursors_and_offsets_do_not_mix() {
+        let cursor = Cursor::new(CursorKey::Id, 3).encode();
+        let pagination = Pagination {
+            offset: Some(0),
+            cursor: Some(cursor.clone()),
+            ..Default::default()
+        };
+        assert!(pagination.page_request().is_err());
+
+        let pagination = Pagination {
+            cursor: Some(cursor),
+            limit: Some(500),
+            ..Default::default()
+        };
+        let request = pagination.page_request().unwrap();
+        assert_eq!(request.limit, MAX_PAGE_LIMIT);
+        assert_eq!(request.after, Some(Cursor::new(CursorKey::Id, 3)));
+        assert!(pagination.offset_request().is_err());
+    }
 }
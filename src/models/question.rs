@@ -1,11 +1,52 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::common::{
+    config::ValidationConfig,
+    error::FieldError,
+    validation::{Checks, Validate},
+};
+
+use super::{
+    account::{AccountId, AuthorSummary},
+    answer::AnswerId,
+    revision::validate_comment,
+    split_list,
+    tag::slugify,
+    Cursor, CursorKey,
+};
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Question {
     pub id: QuestionId,
     pub title: String,
     pub content: String,
     pub tags: Option<Vec<String>>,
+    /// Only changed through the accepted answer endpoints.
+    #[serde(default)]
+    pub accepted_answer_id: Option<AnswerId>,
+    /// Sum of up and down votes.
+    #[serde(default)]
+    pub score: i32,
+    /// Comments on the question itself, replies included.
+    #[serde(default)]
+    pub comment_count: i32,
+    /// Maintained by the server.
+    #[serde(default)]
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub updated_at: DateTime<Utc>,
+    /// `None` for questions predating account ownership.
+    #[serde(default)]
+    pub author: Option<AuthorSummary>,
+    /// Number of the latest revision, bumped by every edit.
+    #[serde(default)]
+    pub revision: i32,
+    /// Only set on questions in the trash.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<AccountId>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
@@ -17,3 +58,155 @@ pub struct NewQuestion {
     pub content: String,
     pub tags: Option<Vec<String>>,
 }
+
+impl Validate for NewQuestion {
+    fn validate(&self, rules: &ValidationConfig, checks: &mut Checks) {
+        validate_post(&self.title, &self.content, &self.tags, rules, checks);
+    }
+}
+
+/// Edit payload, the previous text is kept as a revision.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QuestionUpdate {
+    pub title: String,
+    pub content: String,
+    pub tags: Option<Vec<String>>,
+    /// Optional edit summary shown in the revision history
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+impl Validate for QuestionUpdate {
+    fn validate(&self, rules: &ValidationConfig, checks: &mut Checks) {
+        validate_post(&self.title, &self.content, &self.tags, rules, checks);
+        validate_comment(&self.comment, checks);
+    }
+}
+
+fn validate_post(
+    title: &str,
+    content: &str,
+    tags: &Option<Vec<String>>,
+    rules: &ValidationConfig,
+    checks: &mut Checks,
+) {
+    checks
+        .not_blank("title", title)
+        .max_length("title", title, rules.title_max_length)
+        .not_blank("content", content)
+        .max_length("content", content, rules.content_max_length);
+    let tags = tags.as_deref().unwrap_or_default();
+    checks.max_items("tags", tags, rules.max_tags);
+    for (i, tag) in tags.iter().enumerate() {
+        let field = format!("tags[{}]", i);
+        checks
+            .not_blank(&field, tag)
+            .max_length(&field, tag, rules.tag_max_length);
+        if !tag.trim().is_empty() {
+            let has_alphanumeric = slugify(tag).chars().any(char::is_alphanumeric);
+            checks.check(&field, has_alphanumeric, "must contain a letter or digit");
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AcceptedAnswer {
+    pub answer_id: AnswerId,
+}
+
+impl Validate for AcceptedAnswer {}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum QuestionStatus {
+    /// At least one answer
+    Answered,
+    /// No answers yet
+    Unanswered,
+    /// An answer has been accepted
+    Resolved,
+}
+
+/// Query parameters narrowing down the question listing. Read from the same
+/// query string as `Pagination`, so unknown parameters cannot be refused.
+#[derive(Debug, Deserialize, Default)]
+pub struct QuestionFilter {
+    pub status: Option<QuestionStatus>,
+    /// Comma separated tag list, see `tag_match`
+    pub tags: Option<String>,
+    #[serde(default)]
+    pub tag_match: TagMatch,
+    /// Account id of the question author
+    pub author: Option<i32>,
+    /// RFC 3339 timestamps, both bounds inclusive
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub sort: QuestionSort,
+}
+
+impl QuestionFilter {
+    pub fn tag_list(&self) -> Option<Vec<String>> {
+        self.tags.as_deref().map(split_list)
+    }
+
+    pub fn author_id(&self) -> Option<AccountId> {
+        self.author.map(AccountId)
+    }
+
+    pub fn validate(&self) -> Result<(), FieldError> {
+        if let (Some(after), Some(before)) = (self.created_after, self.created_before) {
+            if after > before {
+                return Err(FieldError::new(
+                    "created_after",
+                    "must not be later than created_before",
+                ));
+            }
+        }
+        if self.tag_list().is_some_and(|tags| tags.is_empty()) {
+            return Err(FieldError::new("tags", "must not be empty"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TagMatch {
+    /// Questions carrying at least one of the tags
+    #[default]
+    Any,
+    /// Questions carrying every tag
+    All,
+}
+
+/// Every order ends with the question id as tie breaker, so pages are stable.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum QuestionSort {
+    #[default]
+    Newest,
+    Oldest,
+    /// Alphabetical, case insensitive
+    Title,
+    /// Most recent question or answer first
+    Activity,
+    /// Highest score first
+    Score,
+}
+
+impl QuestionSort {
+    /// Whether a cursor can resume this order: issued for it, and with the
+    /// kind of key the order compares.
+    pub fn accepts(&self, cursor: &Cursor) -> bool {
+        cursor.sort == Some(*self)
+            && matches!(
+                (self, &cursor.key),
+                (
+                    QuestionSort::Newest | QuestionSort::Oldest | QuestionSort::Activity,
+                    CursorKey::Time(_)
+                ) | (QuestionSort::Title, CursorKey::Text(_))
+                    | (QuestionSort::Score, CursorKey::Score(_))
+            )
+    }
+}
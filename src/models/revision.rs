@@ -0,0 +1,132 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+
+use super::{account::AuthorSummary, answer::AnswerId, question::QuestionId};
+use crate::common::{
+    config::ValidationConfig,
+    validation::{Checks, Validate},
+};
+
+/// Edit comments are free text but short, like commit subjects.
+pub const COMMENT_MAX_LENGTH: usize = 255;
+
+/// State of a question after one edit. Revision 1 is the question as posted.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QuestionRevision {
+    pub question_id: QuestionId,
+    pub revision: i32,
+    pub title: String,
+    pub content: String,
+    pub tags: Option<Vec<String>>,
+    pub comment: Option<String>,
+    /// `None` once the editing account is gone
+    pub editor: Option<AuthorSummary>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AnswerRevision {
+    pub answer_id: AnswerId,
+    pub revision: i32,
+    pub content: String,
+    pub comment: Option<String>,
+    pub editor: Option<AuthorSummary>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Revision numbers to compare, `from` is usually the older one.
+#[derive(Debug, Deserialize)]
+pub struct DiffQuery {
+    pub from: i32,
+    pub to: i32,
+}
+
+/// Unified diffs between two revisions, fields that did not change are
+/// left out.
+#[derive(Debug, Serialize)]
+pub struct RevisionDiff {
+    pub from: i32,
+    pub to: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<String>,
+}
+
+impl RevisionDiff {
+    pub fn questions(from: &QuestionRevision, to: &QuestionRevision) -> Self {
+        let tag_lines = |revision: &QuestionRevision| {
+            revision
+                .tags
+                .as_deref()
+                .map(|tags| tags.join("\n"))
+                .unwrap_or_default()
+        };
+        let labels = (from.revision, to.revision);
+        RevisionDiff {
+            from: from.revision,
+            to: to.revision,
+            title: Some(unified_diff(&from.title, &to.title, labels)),
+            content: Some(unified_diff(&from.content, &to.content, labels)),
+            tags: Some(unified_diff(&tag_lines(from), &tag_lines(to), labels)),
+        }
+        .without_unchanged()
+    }
+
+    pub fn answers(from: &AnswerRevision, to: &AnswerRevision) -> Self {
+        RevisionDiff {
+            from: from.revision,
+            to: to.revision,
+            title: None,
+            content: Some(unified_diff(
+                &from.content,
+                &to.content,
+                (from.revision, to.revision),
+            )),
+            tags: None,
+        }
+        .without_unchanged()
+    }
+
+    fn without_unchanged(self) -> Self {
+        let changed = |diff: Option<String>| diff.filter(|diff| !diff.is_empty());
+        RevisionDiff {
+            title: changed(self.title),
+            content: changed(self.content),
+            tags: changed(self.tags),
+            ..self
+        }
+    }
+}
+
+/// Line based unified diff with three lines of context, empty when both
+/// texts are equal.
+fn unified_diff(old: &str, new: &str, (from, to): (i32, i32)) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("revision {}", from), &format!("revision {}", to))
+        .to_string()
+}
+
+/// Restores an older revision as a new one, history is never rewritten.
+#[derive(Debug, Deserialize, Default)]
+pub struct Rollback {
+    /// Defaults to "Rolled back to revision N"
+    pub comment: Option<String>,
+}
+
+impl Validate for Rollback {
+    fn validate(&self, _rules: &ValidationConfig, checks: &mut Checks) {
+        validate_comment(&self.comment, checks);
+    }
+}
+
+pub fn validate_comment(comment: &Option<String>, checks: &mut Checks) {
+    if let Some(comment) = comment {
+        checks.max_length("comment", comment, COMMENT_MAX_LENGTH);
+    }
+}
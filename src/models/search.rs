@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+use super::{answer::AnswerId, question::QuestionId, split_list};
+
+/// Query parameters of `GET /api/search`.
+#[derive(Debug, Deserialize, Default)]
+pub struct SearchQuery {
+    pub q: String,
+    /// Comma separated, a result's question must carry all of them
+    pub tags: Option<String>,
+}
+
+impl SearchQuery {
+    pub fn tag_list(&self) -> Option<Vec<String>> {
+        self.tags.as_deref().map(split_list)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchResultKind {
+    Question,
+    Answer,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchResult {
+    pub kind: SearchResultKind,
+    pub question_id: QuestionId,
+    pub answer_id: Option<AnswerId>,
+    /// Title of the question, also for answer hits
+    pub title: String,
+    /// HTML escaped excerpt of the matching content with hits wrapped in
+    /// `<mark>` tags
+    pub snippet: String,
+    pub rank: f32,
+}
+
+/// Delimit hits in snippets until `highlight` turns them into `<mark>` tags,
+/// control characters have no business in questions or answers.
+pub const HIT_START: char = '\u{2}';
+pub const HIT_END: char = '\u{3}';
+
+/// Escapes the content for HTML first, so that the `<mark>` tags are the
+/// only markup a snippet carries. Stray delimiters from the content are
+/// dropped rather than left to open a tag that never closes.
+pub fn highlight(text: &str) -> String {
+    let mut snippet = String::with_capacity(text.len());
+    let mut in_hit = false;
+    for c in text.chars() {
+        match c {
+            HIT_START if !in_hit => {
+                snippet.push_str("<mark>");
+                in_hit = true;
+            }
+            HIT_END if in_hit => {
+                snippet.push_str("</mark>");
+                in_hit = false;
+            }
+            HIT_START | HIT_END => {}
+            _ => push_escaped(&mut snippet, c),
+        }
+    }
+    if in_hit {
+        snippet.push_str("</mark>");
+    }
+    snippet
+}
+
+fn push_escaped(snippet: &mut String, c: char) {
+    match c {
+        '&' => snippet.push_str("&amp;"),
+        '<' => snippet.push_str("&lt;"),
+        '>' => snippet.push_str("&gt;"),
+        '"' => snippet.push_str("&quot;"),
+        '\'' => snippet.push_str("&#x27;"),
+        _ => snippet.push(c),
+    }
+}
@@ -0,0 +1,41 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::account::AccountId;
+use crate::common::validation::Validate;
+
+/// Server-side login session. Access tokens carry its id as `jti` claim, so
+/// revoking the session revokes every token issued for it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct SessionId(pub Uuid);
+
+#[derive(Debug, Clone)]
+pub struct AuthSession {
+    pub id: SessionId,
+    pub account_id: AccountId,
+    pub refresh_token_hash: String,
+    pub expires_on: DateTime<Utc>,
+    pub revoked_on: Option<DateTime<Utc>>,
+}
+
+impl AuthSession {
+    pub fn is_active(&self) -> bool {
+        self.revoked_on.is_none() && self.expires_on > Utc::now()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Access token lifetime in seconds
+    pub expires_in: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+impl Validate for RefreshRequest {}
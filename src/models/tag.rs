@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+
+use crate::common::{
+    config::ValidationConfig,
+    validation::{Checks, Validate},
+};
+
+pub const DESCRIPTION_MAX_LENGTH: usize = 2000;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Tag {
+    pub slug: String,
+    pub description: String,
+    /// Questions outside the trash carrying the tag
+    pub question_count: i64,
+    /// Spellings rewritten to `slug` when a question is saved
+    pub synonyms: Vec<String>,
+}
+
+/// Query parameters of `GET /api/tags`.
+#[derive(Debug, Deserialize, Default)]
+pub struct TagQuery {
+    /// Prefix of the slug or of one of its synonyms, for autocomplete
+    pub q: Option<String>,
+    #[serde(default)]
+    pub sort: TagSort,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TagSort {
+    /// Most used first
+    #[default]
+    Popular,
+    /// Alphabetical by slug
+    Name,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TagUpdate {
+    pub description: String,
+}
+
+impl Validate for TagUpdate {
+    fn validate(&self, _rules: &ValidationConfig, checks: &mut Checks) {
+        checks.max_length("description", &self.description, DESCRIPTION_MAX_LENGTH);
+    }
+}
+
+/// Canonical spelling of a tag: lower case, runs of whitespace and
+/// underscores turned into a dash, punctuation other than `+#.-` dropped,
+/// so "C Sharp", "c_sharp" and "c--sharp" all become "c-sharp".
+pub fn slugify(tag: &str) -> String {
+    let mut slug = String::with_capacity(tag.len());
+    for c in tag.trim().to_lowercase().chars() {
+        let c = if c.is_whitespace() || c == '_' {
+            '-'
+        } else {
+            c
+        };
+        if !(c.is_alphanumeric() || matches!(c, '+' | '#' | '.' | '-')) {
+            continue;
+        }
+        if c == '-' && (slug.is_empty() || slug.ends_with('-')) {
+            continue;
+        }
+        slug.push(c);
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Slugifies every tag, dropping empty and repeated ones while keeping the
+/// order of first appearance.
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut slugs: Vec<String> = Vec::with_capacity(tags.len());
+    for slug in tags.iter().map(|tag| slugify(tag)) {
+        if !slug.is_empty() && !slugs.contains(&slug) {
+            slugs.push(slug);
+        }
+    }
+    slugs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spellings_collapse_to_one_slug() {
+        for tag in [
+            "C Sharp",
+            "c_sharp",
+            "c--sharp",
+            "  C \t SHARP  ",
+            "-c-sharp-",
+        ] {
+            assert_eq!(slugify(tag), "c-sharp", "{:?}", tag);
+        }
+    }
+
+    #[test]
+    fn slugs_keep_language_punctuation() {
+        assert_eq!(slugify("C++"), "c++");
+        assert_eq!(slugify("F#"), "f#");
+        assert_eq!(slugify("ASP.NET"), "asp.net");
+        assert_eq!(slugify("rust (lang)!"), "rust-lang");
+        assert_eq!(slugify("Ünïcode"), "ünïcode");
+        assert_eq!(slugify("?!"), "");
+    }
+
+    #[test]
+    fn normalized_tags_are_unique_and_keep_their_order() {
+        let tags: Vec<String> = ["Rust", "axum", " rust ", "", "!!", "Web Dev", "web_dev"]
+            .iter()
+            .map(|tag| tag.to_string())
+            .collect();
+        assert_eq!(normalize_tags(&tags), ["rust", "axum", "web-dev"]);
+    }
+}
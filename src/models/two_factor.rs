@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+use super::{account::AccountId, session::TokenPair};
+use crate::common::validation::Validate;
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Stored TOTP state of an account. The secret is set on enrollment but only
+/// trusted once `enabled`.
+#[derive(Debug, Clone, Default)]
+pub struct TwoFactor {
+    /// Base32 encoded
+    pub secret: Option<String>,
+    pub enabled: bool,
+    /// Last accepted time step, guards against replayed codes
+    pub last_step: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RecoveryCode {
+    pub id: i32,
+    pub account_id: AccountId,
+    pub code_hash: String,
+}
+
+/// What an authenticator app needs, `otpauth_uri` is meant for a QR code.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TwoFactorSetup {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+/// Shown once, only hashes are stored.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+/// Enabling 2FA signs out every other session, the caller continues with
+/// `tokens`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TwoFactorEnabled {
+    pub recovery_codes: Vec<String>,
+    pub tokens: TokenPair,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TwoFactorEnroll {
+    pub password: String,
+}
+
+impl Validate for TwoFactorEnroll {}
+
+/// A TOTP code, or a recovery code where the endpoint accepts one.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TwoFactorCode {
+    pub code: String,
+}
+
+impl Validate for TwoFactorCode {}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TwoFactorDisable {
+    pub password: String,
+    pub code: String,
+}
+
+impl Validate for TwoFactorDisable {}
+
+/// Answer to a correct password when the account has 2FA enabled.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TwoFactorChallenge {
+    pub two_factor_required: bool,
+    pub challenge_token: String,
+    /// Challenge lifetime in seconds
+    pub expires_in: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TwoFactorLogin {
+    pub challenge_token: String,
+    pub code: String,
+}
+
+impl Validate for TwoFactorLogin {}
+
+/// Claims of a challenge token, which cannot pass for an access token as it
+/// carries no session.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChallengeClaims {
+    pub account_id: AccountId,
+    pub purpose: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Tokens(TokenPair),
+    Challenge(TwoFactorChallenge),
+}
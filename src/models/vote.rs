@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+use crate::common::validation::Validate;
+
+use super::{answer::AnswerId, question::QuestionId};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VoteDirection {
+    Up,
+    Down,
+}
+
+impl VoteDirection {
+    pub fn value(&self) -> i16 {
+        match self {
+            VoteDirection::Up => 1,
+            VoteDirection::Down => -1,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NewVote {
+    pub direction: VoteDirection,
+}
+
+impl Validate for NewVote {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum VoteTarget {
+    Question(QuestionId),
+    Answer(AnswerId),
+}
@@ -0,0 +1,1829 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Arc, RwLock},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::{
+    common::error::Error,
+    models::{
+        account::{
+            Account, AccountId, AccountInfo, AuthorSummary, ProfileUpdate, Role,
+            DELETED_DISPLAY_NAME,
+        },
+        account_token::{AccountToken, AccountTokenId, TokenPurpose},
+        answer::{Answer, AnswerId, AnswerUpdate, NewAnswer},
+        api_key::{ApiKey, ApiKeyId},
+        comment::{Comment, CommentId, CommentTarget, CommentUpdate, NewComment},
+        login_attempt::LoginAttempts,
+        question::{
+            NewQuestion, Question, QuestionFilter, QuestionId, QuestionSort, QuestionStatus,
+            QuestionUpdate, TagMatch,
+        },
+        revision::{AnswerRevision, QuestionRevision},
+        search::{highlight, SearchQuery, SearchResult, SearchResultKind, HIT_END, HIT_START},
+        session::{AuthSession, SessionId},
+        tag::{Tag, TagQuery, TagSort},
+        two_factor::{RecoveryCode, TwoFactor},
+        vote::{VoteDirection, VoteTarget},
+        Cursor, CursorKey, Page, PageRequest,
+    },
+    repositories::store::{
+        AccountRepository, AccountTokenRepository, AnswerRepository, ApiKeyRepository,
+        CommentRepository, LoginAttemptRepository, QuestionRepository, RevisionRepository,
+        SearchRepository, SessionRepository, TagRepository, TrashRepository, TwoFactorRepository,
+        VoteRepository,
+    },
+};
+
+/// Storage backend keeping everything in process memory, used for tests and
+/// offline demos. Data is lost when the server stops.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    data: Arc<RwLock<MemoryData>>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryData {
+    questions: BTreeMap<i32, StoredQuestion>,
+    answers: BTreeMap<i32, StoredAnswer>,
+    comments: BTreeMap<i32, StoredComment>,
+    /// Keyed by post id and revision number
+    question_revisions: BTreeMap<(i32, i32), StoredRevision<QuestionRevision>>,
+    answer_revisions: BTreeMap<(i32, i32), StoredRevision<AnswerRevision>>,
+    /// Tag descriptions by slug
+    tags: BTreeMap<String, String>,
+    /// Tag slug by synonym
+    tag_synonyms: BTreeMap<String, String>,
+    accounts: BTreeMap<i32, Account>,
+    /// Anonymized accounts, kept for the posts pointing at them
+    deleted_accounts: HashSet<i32>,
+    sessions: HashMap<SessionId, AuthSession>,
+    account_tokens: HashMap<AccountTokenId, AccountToken>,
+    /// TOTP state by account id, `Account::two_factor_enabled` mirrors `enabled`
+    two_factor: HashMap<i32, TwoFactor>,
+    recovery_codes: BTreeMap<i32, StoredRecoveryCode>,
+    api_keys: HashMap<ApiKeyId, ApiKey>,
+    login_attempts: HashMap<String, LoginAttempts>,
+    votes: HashMap<(VoteTarget, AccountId), VoteDirection>,
+    next_question_id: i32,
+    next_answer_id: i32,
+    next_comment_id: i32,
+    next_account_id: i32,
+    next_recovery_code_id: i32,
+}
+
+#[derive(Debug, Clone)]
+struct StoredQuestion {
+    question: Question,
+    account_id: Option<AccountId>,
+}
+
+#[derive(Debug, Clone)]
+struct StoredAnswer {
+    answer: Answer,
+    account_id: AccountId,
+}
+
+#[derive(Debug, Clone)]
+struct StoredComment {
+    comment: Comment,
+    account_id: AccountId,
+}
+
+#[derive(Debug, Clone)]
+struct StoredRecoveryCode {
+    code: RecoveryCode,
+    used: bool,
+}
+
+#[derive(Debug, Clone)]
+struct StoredRevision<T> {
+    revision: T,
+    account_id: AccountId,
+}
+
+impl MemoryData {
+    fn next_id(counter: &mut i32) -> i32 {
+        *counter += 1;
+        *counter
+    }
+
+    fn update_score(&mut self, target: &VoteTarget) {
+        let score = self
+            .votes
+            .iter()
+            .filter(|((voted, _), _)| voted == target)
+            .map(|(_, direction)| direction.value() as i32)
+            .sum();
+        match target {
+            VoteTarget::Question(id) => {
+                if let Some(stored) = self.questions.get_mut(&id.0) {
+                    stored.question.score = score;
+                }
+            }
+            VoteTarget::Answer(id) => {
+                if let Some(stored) = self.answers.get_mut(&id.0) {
+                    stored.answer.score = score;
+                }
+            }
+        }
+    }
+
+    fn update_comment_count(&mut self, target: &CommentTarget) {
+        let count = self
+            .comments
+            .values()
+            .filter(|stored| &stored.comment.target() == target)
+            .count() as i32;
+        match target {
+            CommentTarget::Question(id) => {
+                if let Some(stored) = self.questions.get_mut(&id.0) {
+                    stored.question.comment_count = count;
+                }
+            }
+            CommentTarget::Answer(id) => {
+                if let Some(stored) = self.answers.get_mut(&id.0) {
+                    stored.answer.comment_count = count;
+                }
+            }
+        }
+    }
+
+    /// Creation time of the question or of its latest answer.
+    fn last_activity(&self, stored: &StoredQuestion) -> DateTime<Utc> {
+        self.live_answers()
+            .filter(|answer| answer.answer.question_id == stored.question.id)
+            .map(|answer| answer.answer.created_at)
+            .fold(stored.question.created_at, DateTime::max)
+    }
+
+    /// Authors are resolved on read, so renames show up on older posts.
+    fn author(&self, account_id: &AccountId) -> Option<AuthorSummary> {
+        self.accounts
+            .get(&account_id.0)
+            .map(|account| AuthorSummary {
+                id: account_id.clone(),
+                display_name: account.display_name.clone(),
+            })
+    }
+
+    fn question(&self, stored: &StoredQuestion) -> Question {
+        Question {
+            author: stored
+                .account_id
+                .as_ref()
+                .and_then(|account_id| self.author(account_id)),
+            ..stored.question.clone()
+        }
+    }
+
+    fn answer(&self, stored: &StoredAnswer) -> Answer {
+        Answer {
+            author: self.author(&stored.account_id),
+            ..stored.answer.clone()
+        }
+    }
+
+    fn comment(&self, stored: &StoredComment) -> Comment {
+        Comment {
+            author: self.author(&stored.account_id),
+            ..stored.comment.clone()
+        }
+    }
+
+    fn question_revision(&self, stored: &StoredRevision<QuestionRevision>) -> QuestionRevision {
+        QuestionRevision {
+            editor: self.author(&stored.account_id),
+            ..stored.revision.clone()
+        }
+    }
+
+    fn answer_revision(&self, stored: &StoredRevision<AnswerRevision>) -> AnswerRevision {
+        AnswerRevision {
+            editor: self.author(&stored.account_id),
+            ..stored.revision.clone()
+        }
+    }
+
+    fn add_question_revision(
+        &mut self,
+        question: &Question,
+        editor: &AccountId,
+        comment: Option<String>,
+    ) {
+        let revision = QuestionRevision {
+            question_id: question.id.clone(),
+            revision: question.revision,
+            title: question.title.clone(),
+            content: question.content.clone(),
+            tags: question.tags.clone(),
+            comment,
+            editor: None,
+            created_at: question.updated_at,
+        };
+        self.question_revisions.insert(
+            (question.id.0, question.revision),
+            StoredRevision {
+                revision,
+                account_id: editor.clone(),
+            },
+        );
+    }
+
+    fn add_answer_revision(
+        &mut self,
+        answer: &Answer,
+        editor: &AccountId,
+        comment: Option<String>,
+    ) {
+        let revision = AnswerRevision {
+            answer_id: answer.id.clone(),
+            revision: answer.revision,
+            content: answer.content.clone(),
+            comment,
+            editor: None,
+            created_at: answer.updated_at,
+        };
+        self.answer_revisions.insert(
+            (answer.id.0, answer.revision),
+            StoredRevision {
+                revision,
+                account_id: editor.clone(),
+            },
+        );
+    }
+
+    fn live_account(&self, id: i32) -> Option<&Account> {
+        self.accounts
+            .get(&id)
+            .filter(|_| !self.deleted_accounts.contains(&id))
+    }
+
+    fn live_account_mut(&mut self, id: i32) -> Option<&mut Account> {
+        if self.deleted_accounts.contains(&id) {
+            return None;
+        }
+        self.accounts.get_mut(&id)
+    }
+
+    fn insert_recovery_codes(&mut self, account_id: &AccountId, code_hashes: Vec<String>) {
+        for code_hash in code_hashes {
+            let id = MemoryData::next_id(&mut self.next_recovery_code_id);
+            self.recovery_codes.insert(
+                id,
+                StoredRecoveryCode {
+                    code: RecoveryCode {
+                        id,
+                        account_id: account_id.clone(),
+                        code_hash,
+                    },
+                    used: false,
+                },
+            );
+        }
+    }
+
+    /// Question outside the trash.
+    fn live_question(&self, id: i32) -> Option<&StoredQuestion> {
+        self.questions
+            .get(&id)
+            .filter(|stored| stored.question.deleted_at.is_none())
+    }
+
+    fn live_question_mut(&mut self, id: i32) -> Option<&mut StoredQuestion> {
+        self.questions
+            .get_mut(&id)
+            .filter(|stored| stored.question.deleted_at.is_none())
+    }
+
+    /// Answers in the trash and answers of trashed questions are hidden.
+    fn is_live_answer(&self, stored: &StoredAnswer) -> bool {
+        stored.answer.deleted_at.is_none()
+            && self.live_question(stored.answer.question_id.0).is_some()
+    }
+
+    fn live_answers(&self) -> impl Iterator<Item = &StoredAnswer> {
+        self.answers
+            .values()
+            .filter(|stored| self.is_live_answer(stored))
+    }
+
+    /// Comments of trashed posts are hidden along with the post.
+    fn is_live_comment(&self, stored: &StoredComment) -> bool {
+        match stored.comment.target() {
+            CommentTarget::Question(id) => self.live_question(id.0).is_some(),
+            CommentTarget::Answer(id) => self
+                .answers
+                .get(&id.0)
+                .is_some_and(|answer| self.is_live_answer(answer)),
+        }
+    }
+
+    /// Removes an answer for good, along with its votes, revisions and
+    /// comments.
+    fn purge_answer(&mut self, answer_id: i32) {
+        let Some(removed) = self.answers.remove(&answer_id) else {
+            return;
+        };
+        let target = VoteTarget::Answer(removed.answer.id.clone());
+        self.votes.retain(|(voted, _), _| voted != &target);
+        self.answer_revisions.retain(|(id, _), _| *id != answer_id);
+        let target = CommentTarget::Answer(removed.answer.id.clone());
+        self.comments
+            .retain(|_, stored| stored.comment.target() != target);
+        // mirrors ON DELETE SET NULL of questions.accepted_answer_id
+        if let Some(stored) = self.questions.get_mut(&removed.answer.question_id.0) {
+            if stored.question.accepted_answer_id == Some(removed.answer.id) {
+                stored.question.accepted_answer_id = None;
+            }
+        }
+    }
+
+    /// Removes a question for good, mirroring the cascades of the schema.
+    fn purge_question(&mut self, question_id: i32) {
+        let answer_ids: Vec<i32> = self
+            .answers
+            .values()
+            .filter(|stored| stored.answer.question_id.0 == question_id)
+            .map(|stored| stored.answer.id.0)
+            .collect();
+        for answer_id in answer_ids {
+            self.purge_answer(answer_id);
+        }
+        if let Some(removed) = self.questions.remove(&question_id) {
+            let target = VoteTarget::Question(removed.question.id.clone());
+            self.votes.retain(|(voted, _), _| voted != &target);
+            let target = CommentTarget::Question(removed.question.id);
+            self.comments
+                .retain(|_, stored| stored.comment.target() != target);
+            self.question_revisions
+                .retain(|(id, _), _| *id != question_id);
+        }
+    }
+
+    /// Slug of the tag `slug` names, directly or as a synonym.
+    fn canonical_slug(&self, slug: &str) -> Option<String> {
+        let slug = self.tag_synonyms.get(slug).map_or(slug, String::as_str);
+        self.tags.contains_key(slug).then(|| slug.to_string())
+    }
+
+    fn tag(&self, slug: &str) -> Tag {
+        let question_count = self
+            .questions
+            .values()
+            .filter(|stored| stored.question.deleted_at.is_none())
+            .filter(|stored| {
+                stored
+                    .question
+                    .tags
+                    .as_ref()
+                    .is_some_and(|tags| tags.iter().any(|tag| tag == slug))
+            })
+            .count() as i64;
+        Tag {
+            slug: slug.to_string(),
+            description: self.tags.get(slug).cloned().unwrap_or_default(),
+            question_count,
+            synonyms: self
+                .tag_synonyms
+                .iter()
+                .filter(|(_, tag_slug)| *tag_slug == slug)
+                .map(|(synonym, _)| synonym.clone())
+                .collect(),
+        }
+    }
+
+    fn filter_questions(&self, filter: &QuestionFilter) -> Vec<&StoredQuestion> {
+        let tags = filter.tag_list();
+        let author = filter.author_id();
+        self.questions
+            .values()
+            .filter(|stored| stored.question.deleted_at.is_none())
+            .filter(|stored| match filter.status {
+                Some(QuestionStatus::Answered) => self.has_answers(&stored.question.id),
+                Some(QuestionStatus::Unanswered) => !self.has_answers(&stored.question.id),
+                Some(QuestionStatus::Resolved) => stored.question.accepted_answer_id.is_some(),
+                None => true,
+            })
+            .filter(|stored| match (&tags, &stored.question.tags) {
+                (None, _) => true,
+                (Some(_), None) => false,
+                (Some(wanted), Some(tags)) => match filter.tag_match {
+                    TagMatch::Any => wanted.iter().any(|tag| tags.contains(tag)),
+                    TagMatch::All => wanted.iter().all(|tag| tags.contains(tag)),
+                },
+            })
+            .filter(|stored| author.is_none() || stored.account_id == author)
+            .filter(|stored| {
+                filter
+                    .created_after
+                    .is_none_or(|t| stored.question.created_at >= t)
+            })
+            .filter(|stored| {
+                filter
+                    .created_before
+                    .is_none_or(|t| stored.question.created_at <= t)
+            })
+            .collect()
+    }
+
+    fn question_cursor(&self, sort: QuestionSort, stored: &StoredQuestion) -> Cursor {
+        let key = match sort {
+            QuestionSort::Newest | QuestionSort::Oldest => {
+                CursorKey::Time(stored.question.created_at)
+            }
+            QuestionSort::Title => CursorKey::Text(stored.question.title.to_lowercase()),
+            QuestionSort::Activity => CursorKey::Time(self.last_activity(stored)),
+            QuestionSort::Score => CursorKey::Score(stored.question.score),
+        };
+        Cursor::new(key, stored.question.id.0).with_sort(sort)
+    }
+
+    fn has_answers(&self, question_id: &QuestionId) -> bool {
+        self.live_answers()
+            .any(|stored| &stored.answer.question_id == question_id)
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, MemoryData> {
+        self.data.read().expect("memory store lock poisoned")
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, MemoryData> {
+        self.data.write().expect("memory store lock poisoned")
+    }
+}
+
+fn account_info(account: &Account) -> AccountInfo {
+    AccountInfo {
+        id: account.id.clone().expect("stored account without id"),
+        email: account.email.clone(),
+        role: account.role,
+    }
+}
+
+/// Orders trash rows most recently deleted first and cuts out the page,
+/// plus one row to tell whether another page follows.
+fn trash_page<'a, T>(
+    rows: &'a mut [(T, Cursor)],
+    page: &PageRequest,
+) -> impl Iterator<Item = &'a (T, Cursor)> {
+    let newest_first = |a: &Cursor, b: &Cursor| {
+        let by_key = a.key.partial_cmp(&b.key).unwrap_or(Ordering::Equal);
+        by_key.then(a.id.cmp(&b.id)).reverse()
+    };
+    rows.sort_by(|(_, a), (_, b)| newest_first(a, b));
+    let after = page.after.clone();
+    rows.iter()
+        .filter(move |(_, cursor)| {
+            after
+                .as_ref()
+                .is_none_or(|after| newest_first(cursor, after).is_gt())
+        })
+        .skip(page.offset as usize)
+        .take(page.limit as usize + 1)
+}
+
+/// Compares two rows by cursor in the order `sort` lists them.
+fn listing_order(sort: QuestionSort, a: &Cursor, b: &Cursor) -> Ordering {
+    let by_key = a.key.partial_cmp(&b.key).unwrap_or(Ordering::Equal);
+    let by_id = a.id.cmp(&b.id);
+    match sort {
+        QuestionSort::Newest | QuestionSort::Activity => by_key.then(by_id).reverse(),
+        QuestionSort::Oldest | QuestionSort::Title => by_key.then(by_id),
+        QuestionSort::Score => by_key.reverse().then(by_id),
+    }
+}
+
+#[async_trait]
+impl QuestionRepository for MemoryStore {
+    async fn add_question(
+        &self,
+        new_question: NewQuestion,
+        account_id: &AccountId,
+    ) -> Result<Question, Error> {
+        let mut data = self.write();
+        let id = MemoryData::next_id(&mut data.next_question_id);
+        let now = Utc::now();
+        let question = Question {
+            id: QuestionId(id),
+            title: new_question.title,
+            content: new_question.content,
+            tags: new_question.tags,
+            accepted_answer_id: None,
+            score: 0,
+            comment_count: 0,
+            created_at: now,
+            updated_at: now,
+            author: None,
+            revision: 1,
+            deleted_at: None,
+            deleted_by: None,
+        };
+        data.add_question_revision(&question, account_id, None);
+        let stored = StoredQuestion {
+            question,
+            account_id: Some(account_id.clone()),
+        };
+        let question = data.question(&stored);
+        data.questions.insert(id, stored);
+
+        Ok(question)
+    }
+
+    async fn get_questions(
+        &self,
+        filter: &QuestionFilter,
+        page: &PageRequest,
+    ) -> Result<Page<Question>, Error> {
+        let data = self.read();
+        let mut rows: Vec<(&StoredQuestion, Cursor)> = data
+            .filter_questions(filter)
+            .into_iter()
+            .map(|stored| (stored, data.question_cursor(filter.sort, stored)))
+            .collect();
+        rows.sort_by(|(_, a), (_, b)| listing_order(filter.sort, a, b));
+        if let Some(after) = &page.after {
+            rows.retain(|(_, cursor)| listing_order(filter.sort, cursor, after).is_gt());
+        }
+
+        let rows = rows
+            .into_iter()
+            .skip(page.offset as usize)
+            .take(page.limit as usize + 1)
+            .map(|(stored, cursor)| (data.question(stored), cursor))
+            .collect();
+        Ok(Page::from_rows(rows, page.limit))
+    }
+
+    async fn count_questions(&self, filter: &QuestionFilter) -> Result<i64, Error> {
+        let data = self.read();
+        Ok(data.filter_questions(filter).len() as i64)
+    }
+
+    async fn get_question_byid(&self, id: i64) -> Result<Question, Error> {
+        let data = self.read();
+        data.live_question(id as i32)
+            .map(|stored| data.question(stored))
+            .ok_or(Error::QuestionNotFound)
+    }
+
+    async fn update_question(
+        &self,
+        question: QuestionUpdate,
+        question_id: i64,
+        editor: &AccountId,
+    ) -> Result<Question, Error> {
+        let mut data = self.write();
+        let stored = data
+            .live_question_mut(question_id as i32)
+            .ok_or(Error::QuestionNotFound)?;
+        stored.question.title = question.title;
+        stored.question.content = question.content;
+        stored.question.tags = question.tags;
+        stored.question.updated_at = Utc::now();
+        stored.question.revision += 1;
+        let updated = stored.question.clone();
+        data.add_question_revision(&updated, editor, question.comment);
+
+        Ok(data.question(&data.questions[&(question_id as i32)]))
+    }
+
+    async fn delete_question(
+        &self,
+        question_id: i64,
+        deleted_by: &AccountId,
+    ) -> Result<bool, Error> {
+        let mut data = self.write();
+        let stored = data
+            .live_question_mut(question_id as i32)
+            .ok_or(Error::QuestionNotFound)?;
+        stored.question.deleted_at = Some(Utc::now());
+        stored.question.deleted_by = Some(deleted_by.clone());
+
+        Ok(true)
+    }
+
+    async fn set_accepted_answer(
+        &self,
+        question_id: i64,
+        answer_id: Option<i64>,
+    ) -> Result<Question, Error> {
+        let mut data = self.write();
+        let stored = data
+            .live_question_mut(question_id as i32)
+            .ok_or(Error::QuestionNotFound)?;
+        stored.question.accepted_answer_id = answer_id.map(|id| AnswerId(id as i32));
+
+        Ok(data.question(&data.questions[&(question_id as i32)]))
+    }
+
+    async fn is_question_owner(
+        &self,
+        question_id: i64,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        let data = self.read();
+        Ok(data
+            .questions
+            .get(&(question_id as i32))
+            .is_some_and(|stored| stored.account_id.as_ref() == Some(account_id)))
+    }
+}
+
+#[async_trait]
+impl AnswerRepository for MemoryStore {
+    async fn add_answer(
+        &self,
+        new_answer: NewAnswer,
+        account_id: &AccountId,
+    ) -> Result<Answer, Error> {
+        let mut data = self.write();
+        if data.live_question(new_answer.question_id.0).is_none() {
+            return Err(Error::QuestionNotFound);
+        }
+        let id = MemoryData::next_id(&mut data.next_answer_id);
+        let now = Utc::now();
+        let answer = Answer {
+            id: AnswerId(id),
+            content: new_answer.content,
+            question_id: new_answer.question_id,
+            score: 0,
+            comment_count: 0,
+            created_at: now,
+            updated_at: now,
+            author: None,
+            revision: 1,
+            deleted_at: None,
+            deleted_by: None,
+        };
+        data.add_answer_revision(&answer, account_id, None);
+        let stored = StoredAnswer {
+            answer,
+            account_id: account_id.clone(),
+        };
+        let answer = data.answer(&stored);
+        data.answers.insert(id, stored);
+
+        Ok(answer)
+    }
+
+    async fn get_answers(
+        &self,
+        question_id: i64,
+        page: &PageRequest,
+    ) -> Result<Page<Answer>, Error> {
+        let data = self.read();
+        let after = page.after.as_ref().map_or(0, |after| after.id);
+        let rows = data
+            .live_answers()
+            .filter(|stored| stored.answer.question_id.0 as i64 == question_id)
+            .filter(|stored| stored.answer.id.0 > after)
+            .skip(page.offset as usize)
+            .take(page.limit as usize + 1)
+            .map(|stored| {
+                let cursor = Cursor::new(CursorKey::Id, stored.answer.id.0);
+                (data.answer(stored), cursor)
+            })
+            .collect();
+        Ok(Page::from_rows(rows, page.limit))
+    }
+
+    async fn count_answers(&self, question_id: i64) -> Result<i64, Error> {
+        let data = self.read();
+        Ok(data
+            .live_answers()
+            .filter(|stored| stored.answer.question_id.0 as i64 == question_id)
+            .count() as i64)
+    }
+
+    async fn get_answer_byid(&self, id: i64) -> Result<Answer, Error> {
+        let data = self.read();
+        data.answers
+            .get(&(id as i32))
+            .filter(|stored| data.is_live_answer(stored))
+            .map(|stored| data.answer(stored))
+            .ok_or(Error::AnswerNotFound)
+    }
+
+    async fn update_answer(
+        &self,
+        answer: AnswerUpdate,
+        answer_id: i64,
+        editor: &AccountId,
+    ) -> Result<Answer, Error> {
+        let mut data = self.write();
+        let stored = data
+            .answers
+            .get_mut(&(answer_id as i32))
+            .filter(|stored| stored.answer.deleted_at.is_none())
+            .ok_or(Error::AnswerNotFound)?;
+        stored.answer.content = answer.content;
+        stored.answer.updated_at = Utc::now();
+        stored.answer.revision += 1;
+        let updated = stored.answer.clone();
+        data.add_answer_revision(&updated, editor, answer.comment);
+
+        Ok(data.answer(&data.answers[&(answer_id as i32)]))
+    }
+
+    async fn delete_answer(&self, answer_id: i64, deleted_by: &AccountId) -> Result<bool, Error> {
+        let mut data = self.write();
+        let stored = data
+            .answers
+            .get_mut(&(answer_id as i32))
+            .filter(|stored| stored.answer.deleted_at.is_none())
+            .ok_or(Error::AnswerNotFound)?;
+        stored.answer.deleted_at = Some(Utc::now());
+        stored.answer.deleted_by = Some(deleted_by.clone());
+        let (id, question_id) = (stored.answer.id.clone(), stored.answer.question_id.0);
+        if let Some(stored) = data.questions.get_mut(&question_id) {
+            if stored.question.accepted_answer_id == Some(id) {
+                stored.question.accepted_answer_id = None;
+            }
+        }
+
+        Ok(true)
+    }
+
+    async fn is_answer_owner(&self, answer_id: i64, account_id: &AccountId) -> Result<bool, Error> {
+        let data = self.read();
+        Ok(data
+            .answers
+            .get(&(answer_id as i32))
+            .is_some_and(|stored| &stored.account_id == account_id))
+    }
+}
+
+#[async_trait]
+impl CommentRepository for MemoryStore {
+    async fn add_comment(
+        &self,
+        target: &CommentTarget,
+        new_comment: NewComment,
+        account_id: &AccountId,
+    ) -> Result<Comment, Error> {
+        let mut data = self.write();
+        let (question_id, answer_id) = match target {
+            CommentTarget::Question(id) => {
+                data.live_question(id.0).ok_or(Error::QuestionNotFound)?;
+                (Some(id.clone()), None)
+            }
+            CommentTarget::Answer(id) => {
+                data.answers
+                    .get(&id.0)
+                    .filter(|stored| data.is_live_answer(stored))
+                    .ok_or(Error::AnswerNotFound)?;
+                (None, Some(id.clone()))
+            }
+        };
+        let id = MemoryData::next_id(&mut data.next_comment_id);
+        let now = Utc::now();
+        let stored = StoredComment {
+            comment: Comment {
+                id: CommentId(id),
+                question_id,
+                answer_id,
+                parent_id: new_comment.parent_id,
+                content: new_comment.content,
+                created_at: now,
+                updated_at: now,
+                author: None,
+            },
+            account_id: account_id.clone(),
+        };
+        let comment = data.comment(&stored);
+        data.comments.insert(id, stored);
+        data.update_comment_count(target);
+
+        Ok(comment)
+    }
+
+    async fn get_comments(
+        &self,
+        target: &CommentTarget,
+        page: &PageRequest,
+    ) -> Result<Page<Comment>, Error> {
+        let data = self.read();
+        let after = page.after.as_ref().map_or(0, |after| after.id);
+        let rows = data
+            .comments
+            .range(after.saturating_add(1)..)
+            .map(|(_, stored)| stored)
+            .filter(|stored| &stored.comment.target() == target)
+            .skip(page.offset as usize)
+            .take(page.limit as usize + 1)
+            .map(|stored| {
+                let cursor = Cursor::new(CursorKey::Id, stored.comment.id.0);
+                (data.comment(stored), cursor)
+            })
+            .collect();
+        Ok(Page::from_rows(rows, page.limit))
+    }
+
+    async fn get_comment(&self, comment_id: i64) -> Result<Comment, Error> {
+        let data = self.read();
+        data.comments
+            .get(&(comment_id as i32))
+            .filter(|stored| data.is_live_comment(stored))
+            .map(|stored| data.comment(stored))
+            .ok_or(Error::CommentNotFound)
+    }
+
+    async fn update_comment(
+        &self,
+        comment: CommentUpdate,
+        comment_id: i64,
+    ) -> Result<Comment, Error> {
+        let mut data = self.write();
+        let stored = data
+            .comments
+            .get_mut(&(comment_id as i32))
+            .ok_or(Error::CommentNotFound)?;
+        stored.comment.content = comment.content;
+        stored.comment.updated_at = Utc::now();
+
+        Ok(data.comment(&data.comments[&(comment_id as i32)]))
+    }
+
+    async fn delete_comment(&self, comment_id: i64) -> Result<bool, Error> {
+        let mut data = self.write();
+        let removed = data
+            .comments
+            .remove(&(comment_id as i32))
+            .ok_or(Error::CommentNotFound)?;
+        // mirrors ON DELETE CASCADE of comments.parent_id
+        let mut orphans = vec![removed.comment.id.clone()];
+        while let Some(parent_id) = orphans.pop() {
+            let replies: Vec<i32> = data
+                .comments
+                .values()
+                .filter(|stored| stored.comment.parent_id.as_ref() == Some(&parent_id))
+                .map(|stored| stored.comment.id.0)
+                .collect();
+            for reply in replies {
+                data.comments.remove(&reply);
+                orphans.push(CommentId(reply));
+            }
+        }
+        data.update_comment_count(&removed.comment.target());
+
+        Ok(true)
+    }
+
+    async fn is_comment_owner(
+        &self,
+        comment_id: i64,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        let data = self.read();
+        Ok(data
+            .comments
+            .get(&(comment_id as i32))
+            .is_some_and(|stored| &stored.account_id == account_id))
+    }
+}
+
+#[async_trait]
+impl RevisionRepository for MemoryStore {
+    async fn get_question_revisions(
+        &self,
+        question_id: i64,
+        page: &PageRequest,
+    ) -> Result<Page<QuestionRevision>, Error> {
+        let data = self.read();
+        let after = page.after.as_ref().map_or(0, |after| after.id);
+        let id = question_id as i32;
+        let rows = data
+            .question_revisions
+            .range((id, after.saturating_add(1))..=(id, i32::MAX))
+            .skip(page.offset as usize)
+            .take(page.limit as usize + 1)
+            .map(|((_, revision), stored)| {
+                let cursor = Cursor::new(CursorKey::Id, *revision);
+                (data.question_revision(stored), cursor)
+            })
+            .collect();
+        Ok(Page::from_rows(rows, page.limit))
+    }
+
+    async fn get_question_revision(
+        &self,
+        question_id: i64,
+        revision: i32,
+    ) -> Result<QuestionRevision, Error> {
+        let data = self.read();
+        data.question_revisions
+            .get(&(question_id as i32, revision))
+            .map(|stored| data.question_revision(stored))
+            .ok_or(Error::RevisionNotFound)
+    }
+
+    async fn get_answer_revisions(
+        &self,
+        answer_id: i64,
+        page: &PageRequest,
+    ) -> Result<Page<AnswerRevision>, Error> {
+        let data = self.read();
+        let after = page.after.as_ref().map_or(0, |after| after.id);
+        let id = answer_id as i32;
+        let rows = data
+            .answer_revisions
+            .range((id, after.saturating_add(1))..=(id, i32::MAX))
+            .skip(page.offset as usize)
+            .take(page.limit as usize + 1)
+            .map(|((_, revision), stored)| {
+                let cursor = Cursor::new(CursorKey::Id, *revision);
+                (data.answer_revision(stored), cursor)
+            })
+            .collect();
+        Ok(Page::from_rows(rows, page.limit))
+    }
+
+    async fn get_answer_revision(
+        &self,
+        answer_id: i64,
+        revision: i32,
+    ) -> Result<AnswerRevision, Error> {
+        let data = self.read();
+        data.answer_revisions
+            .get(&(answer_id as i32, revision))
+            .map(|stored| data.answer_revision(stored))
+            .ok_or(Error::RevisionNotFound)
+    }
+}
+
+#[async_trait]
+impl TrashRepository for MemoryStore {
+    async fn get_deleted_questions(&self, page: &PageRequest) -> Result<Page<Question>, Error> {
+        let data = self.read();
+        let mut rows: Vec<(&StoredQuestion, Cursor)> = data
+            .questions
+            .values()
+            .filter_map(|stored| {
+                let deleted_at = stored.question.deleted_at?;
+                Some((
+                    stored,
+                    Cursor::new(CursorKey::Time(deleted_at), stored.question.id.0),
+                ))
+            })
+            .collect();
+        let rows = trash_page(&mut rows, page)
+            .map(|(stored, cursor)| (data.question(stored), cursor.clone()))
+            .collect();
+        Ok(Page::from_rows(rows, page.limit))
+    }
+
+    async fn get_deleted_answers(&self, page: &PageRequest) -> Result<Page<Answer>, Error> {
+        let data = self.read();
+        let mut rows: Vec<(&StoredAnswer, Cursor)> = data
+            .answers
+            .values()
+            .filter_map(|stored| {
+                let deleted_at = stored.answer.deleted_at?;
+                Some((
+                    stored,
+                    Cursor::new(CursorKey::Time(deleted_at), stored.answer.id.0),
+                ))
+            })
+            .collect();
+        let rows = trash_page(&mut rows, page)
+            .map(|(stored, cursor)| (data.answer(stored), cursor.clone()))
+            .collect();
+        Ok(Page::from_rows(rows, page.limit))
+    }
+
+    async fn restore_question(&self, question_id: i64) -> Result<Question, Error> {
+        let mut data = self.write();
+        let stored = data
+            .questions
+            .get_mut(&(question_id as i32))
+            .filter(|stored| stored.question.deleted_at.is_some())
+            .ok_or(Error::QuestionNotFound)?;
+        stored.question.deleted_at = None;
+        stored.question.deleted_by = None;
+
+        Ok(data.question(&data.questions[&(question_id as i32)]))
+    }
+
+    async fn restore_answer(&self, answer_id: i64) -> Result<Answer, Error> {
+        let mut data = self.write();
+        let stored = data
+            .answers
+            .get_mut(&(answer_id as i32))
+            .filter(|stored| stored.answer.deleted_at.is_some())
+            .ok_or(Error::AnswerNotFound)?;
+        stored.answer.deleted_at = None;
+        stored.answer.deleted_by = None;
+
+        Ok(data.answer(&data.answers[&(answer_id as i32)]))
+    }
+
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<u64, Error> {
+        let mut data = self.write();
+        let expired =
+            |deleted_at: Option<DateTime<Utc>>| deleted_at.is_some_and(|t| t < deleted_before);
+        let answer_ids: Vec<i32> = data
+            .answers
+            .values()
+            .filter(|stored| expired(stored.answer.deleted_at))
+            .map(|stored| stored.answer.id.0)
+            .collect();
+        let question_ids: Vec<i32> = data
+            .questions
+            .values()
+            .filter(|stored| expired(stored.question.deleted_at))
+            .map(|stored| stored.question.id.0)
+            .collect();
+        let purged = answer_ids.len() + question_ids.len();
+        for answer_id in answer_ids {
+            data.purge_answer(answer_id);
+        }
+        for question_id in question_ids {
+            data.purge_question(question_id);
+        }
+
+        Ok(purged as u64)
+    }
+}
+
+#[async_trait]
+impl TagRepository for MemoryStore {
+    async fn resolve_tags(&self, slugs: Vec<String>) -> Result<Vec<String>, Error> {
+        let data = self.read();
+        Ok(slugs
+            .into_iter()
+            .map(|slug| data.tag_synonyms.get(&slug).cloned().unwrap_or(slug))
+            .collect())
+    }
+
+    async fn ensure_tags(&self, slugs: &[String]) -> Result<(), Error> {
+        let mut data = self.write();
+        for slug in slugs {
+            data.tags.entry(slug.clone()).or_default();
+        }
+        Ok(())
+    }
+
+    async fn get_tags(&self, query: &TagQuery, offset: i64, limit: i64) -> Result<Vec<Tag>, Error> {
+        let data = self.read();
+        let mut tags: Vec<Tag> = data
+            .tags
+            .keys()
+            .map(|slug| data.tag(slug))
+            .filter(|tag| match &query.q {
+                Some(prefix) => {
+                    tag.slug.starts_with(prefix.as_str())
+                        || tag.synonyms.iter().any(|s| s.starts_with(prefix.as_str()))
+                }
+                None => true,
+            })
+            .collect();
+        // keys are already in slug order and the sort is stable
+        if query.sort == TagSort::Popular {
+            tags.sort_by_key(|tag| std::cmp::Reverse(tag.question_count));
+        }
+
+        Ok(tags
+            .into_iter()
+            .skip(offset.max(0) as usize)
+            .take(limit.max(0) as usize)
+            .collect())
+    }
+
+    async fn get_tag(&self, slug: &str) -> Result<Tag, Error> {
+        let data = self.read();
+        let slug = data.canonical_slug(slug).ok_or(Error::TagNotFound)?;
+        Ok(data.tag(&slug))
+    }
+
+    async fn update_tag_description(&self, slug: &str, description: String) -> Result<Tag, Error> {
+        let mut data = self.write();
+        let slug = data.canonical_slug(slug).ok_or(Error::TagNotFound)?;
+        data.tags.insert(slug.clone(), description);
+        Ok(data.tag(&slug))
+    }
+
+    async fn add_tag_synonym(&self, slug: &str, synonym: &str) -> Result<Tag, Error> {
+        let mut data = self.write();
+        let slug = data.canonical_slug(slug).ok_or(Error::TagNotFound)?;
+        if slug == synonym {
+            return Err(Error::InvalidParameter(String::from(
+                "a tag cannot be a synonym of itself",
+            )));
+        }
+        for stored in data.questions.values_mut() {
+            if let Some(tags) = stored.question.tags.as_mut() {
+                if !tags.iter().any(|tag| tag == synonym) {
+                    continue;
+                }
+                if tags.contains(&slug) {
+                    tags.retain(|tag| tag != synonym);
+                } else {
+                    for tag in tags.iter_mut().filter(|tag| *tag == synonym) {
+                        *tag = slug.clone();
+                    }
+                }
+            }
+        }
+        for tag_slug in data.tag_synonyms.values_mut() {
+            if tag_slug == synonym {
+                *tag_slug = slug.clone();
+            }
+        }
+        data.tags.remove(synonym);
+        data.tag_synonyms.insert(synonym.to_string(), slug.clone());
+
+        Ok(data.tag(&slug))
+    }
+
+    async fn remove_tag_synonym(&self, slug: &str, synonym: &str) -> Result<Tag, Error> {
+        let mut data = self.write();
+        let slug = data.canonical_slug(slug).ok_or(Error::TagNotFound)?;
+        if data.tag_synonyms.get(synonym) != Some(&slug) {
+            return Err(Error::TagNotFound);
+        }
+        data.tag_synonyms.remove(synonym);
+
+        Ok(data.tag(&slug))
+    }
+}
+
+#[async_trait]
+impl AccountRepository for MemoryStore {
+    async fn add_account(&self, account: Account) -> Result<bool, Error> {
+        let mut data = self.write();
+        if data.accounts.values().any(|a| a.email == account.email) {
+            return Err(Error::AccountAlreadyExists);
+        }
+        let id = MemoryData::next_id(&mut data.next_account_id);
+        data.accounts.insert(
+            id,
+            Account {
+                id: Some(AccountId(id)),
+                created_at: Utc::now(),
+                ..account
+            },
+        );
+
+        Ok(true)
+    }
+
+    async fn get_account(&self, email: String) -> Result<Account, Error> {
+        let data = self.read();
+        data.accounts
+            .values()
+            .find(|a| a.email == email)
+            .filter(|a| {
+                a.id.as_ref()
+                    .is_some_and(|id| data.live_account(id.0).is_some())
+            })
+            .cloned()
+            .ok_or(Error::AccountNotFound)
+    }
+
+    async fn get_account_byid(&self, account_id: &AccountId) -> Result<Account, Error> {
+        let data = self.read();
+        data.live_account(account_id.0)
+            .cloned()
+            .ok_or(Error::AccountNotFound)
+    }
+
+    async fn get_accounts(&self, offset: i64, limit: i64) -> Result<Vec<AccountInfo>, Error> {
+        let data = self.read();
+        Ok(data
+            .accounts
+            .values()
+            .filter(|account| {
+                account
+                    .id
+                    .as_ref()
+                    .is_some_and(|id| !data.deleted_accounts.contains(&id.0))
+            })
+            .skip(offset.max(0) as usize)
+            .take(limit.max(0) as usize)
+            .map(account_info)
+            .collect())
+    }
+
+    async fn update_account_role(
+        &self,
+        account_id: &AccountId,
+        role: Role,
+    ) -> Result<AccountInfo, Error> {
+        let mut data = self.write();
+        let account = data
+            .live_account_mut(account_id.0)
+            .ok_or(Error::AccountNotFound)?;
+        account.role = role;
+
+        Ok(account_info(account))
+    }
+
+    async fn update_profile(
+        &self,
+        account_id: &AccountId,
+        update: ProfileUpdate,
+    ) -> Result<Account, Error> {
+        let mut data = self.write();
+        let account = data
+            .live_account_mut(account_id.0)
+            .ok_or(Error::AccountNotFound)?;
+        if let Some(display_name) = update.display_name {
+            account.display_name = display_name;
+        }
+        if let Some(bio) = update.bio {
+            account.bio = bio;
+        }
+        if let Some(avatar_url) = update.avatar_url {
+            account.avatar_url = Some(avatar_url).filter(|url| !url.is_empty());
+        }
+
+        Ok(account.clone())
+    }
+
+    async fn update_email(&self, account_id: &AccountId, email: String) -> Result<Account, Error> {
+        let mut data = self.write();
+        if data
+            .accounts
+            .values()
+            .any(|a| a.email == email && a.id.as_ref() != Some(account_id))
+        {
+            return Err(Error::AccountAlreadyExists);
+        }
+        let account = data
+            .live_account_mut(account_id.0)
+            .ok_or(Error::AccountNotFound)?;
+        account.email = email;
+        account.email_verified = false;
+
+        Ok(account.clone())
+    }
+
+    async fn update_password(
+        &self,
+        account_id: &AccountId,
+        password_hash: String,
+    ) -> Result<(), Error> {
+        let mut data = self.write();
+        let account = data
+            .live_account_mut(account_id.0)
+            .ok_or(Error::AccountNotFound)?;
+        account.password = password_hash;
+
+        Ok(())
+    }
+
+    async fn mark_email_verified(
+        &self,
+        account_id: &AccountId,
+        email: &str,
+    ) -> Result<bool, Error> {
+        let mut data = self.write();
+        match data.live_account_mut(account_id.0) {
+            Some(account) if account.email == email => {
+                account.email_verified = true;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn delete_account(&self, account_id: &AccountId) -> Result<(), Error> {
+        let mut data = self.write();
+        let account = data
+            .live_account_mut(account_id.0)
+            .ok_or(Error::AccountNotFound)?;
+        account.email = format!("deleted-{}@deleted.invalid", account_id.0);
+        account.password = String::new();
+        account.display_name = String::from(DELETED_DISPLAY_NAME);
+        account.bio = String::new();
+        account.avatar_url = None;
+        account.email_verified = false;
+        account.two_factor_enabled = false;
+        data.deleted_accounts.insert(account_id.0);
+        data.two_factor.remove(&account_id.0);
+        data.recovery_codes
+            .retain(|_, stored| &stored.code.account_id != account_id);
+        data.sessions
+            .values_mut()
+            .filter(|session| &session.account_id == account_id)
+            .for_each(|session| {
+                session.revoked_on.get_or_insert_with(Utc::now);
+            });
+        data.api_keys
+            .values_mut()
+            .filter(|key| &key.account_id == account_id)
+            .for_each(|key| {
+                key.revoked_at.get_or_insert_with(Utc::now);
+            });
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SessionRepository for MemoryStore {
+    async fn add_session(&self, session: AuthSession) -> Result<(), Error> {
+        self.write().sessions.insert(session.id.clone(), session);
+        Ok(())
+    }
+
+    async fn get_session(&self, session_id: &SessionId) -> Result<Option<AuthSession>, Error> {
+        Ok(self.read().sessions.get(session_id).cloned())
+    }
+
+    async fn rotate_refresh_token(
+        &self,
+        session_id: &SessionId,
+        refresh_token_hash: String,
+        expires_on: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let mut data = self.write();
+        match data.sessions.get_mut(session_id) {
+            Some(session) if session.revoked_on.is_none() => {
+                session.refresh_token_hash = refresh_token_hash;
+                session.expires_on = expires_on;
+                Ok(())
+            }
+            _ => Err(Error::CannotDecryptToken),
+        }
+    }
+
+    async fn revoke_session(&self, session_id: &SessionId) -> Result<(), Error> {
+        if let Some(session) = self.write().sessions.get_mut(session_id) {
+            session.revoked_on.get_or_insert_with(Utc::now);
+        }
+        Ok(())
+    }
+
+    async fn revoke_account_sessions(&self, account_id: &AccountId) -> Result<(), Error> {
+        let mut data = self.write();
+        data.sessions
+            .values_mut()
+            .filter(|session| &session.account_id == account_id)
+            .for_each(|session| {
+                session.revoked_on.get_or_insert_with(Utc::now);
+            });
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AccountTokenRepository for MemoryStore {
+    async fn add_account_token(&self, token: AccountToken) -> Result<(), Error> {
+        self.write().account_tokens.insert(token.id.clone(), token);
+        Ok(())
+    }
+
+    async fn get_account_token(
+        &self,
+        token_id: &AccountTokenId,
+    ) -> Result<Option<AccountToken>, Error> {
+        Ok(self.read().account_tokens.get(token_id).cloned())
+    }
+
+    async fn use_account_token(&self, token_id: &AccountTokenId) -> Result<bool, Error> {
+        match self.write().account_tokens.get_mut(token_id) {
+            Some(token) if token.used_on.is_none() => {
+                token.used_on = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn revoke_account_tokens(
+        &self,
+        account_id: &AccountId,
+        purpose: TokenPurpose,
+    ) -> Result<(), Error> {
+        let mut data = self.write();
+        data.account_tokens
+            .values_mut()
+            .filter(|token| &token.account_id == account_id && token.purpose == purpose)
+            .for_each(|token| {
+                token.used_on.get_or_insert_with(Utc::now);
+            });
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LoginAttemptRepository for MemoryStore {
+    async fn get_login_attempts(&self, key: &str) -> Result<Option<LoginAttempts>, Error> {
+        Ok(self.read().login_attempts.get(key).cloned())
+    }
+
+    async fn record_login_failure(
+        &self,
+        key: &str,
+        window_start: DateTime<Utc>,
+    ) -> Result<LoginAttempts, Error> {
+        let now = Utc::now();
+        let mut data = self.write();
+        let attempts = data
+            .login_attempts
+            .entry(key.to_string())
+            .and_modify(|attempts| {
+                attempts.failures = if attempts.last_failure_at < window_start {
+                    1
+                } else {
+                    attempts.failures + 1
+                };
+                attempts.last_failure_at = now;
+            })
+            .or_insert(LoginAttempts {
+                failures: 1,
+                last_failure_at: now,
+                locked_until: None,
+            });
+
+        Ok(attempts.clone())
+    }
+
+    async fn lock_login(&self, key: &str, until: DateTime<Utc>) -> Result<(), Error> {
+        if let Some(attempts) = self.write().login_attempts.get_mut(key) {
+            attempts.locked_until = Some(until);
+        }
+        Ok(())
+    }
+
+    async fn clear_login_attempts(&self, key: &str) -> Result<(), Error> {
+        self.write().login_attempts.remove(key);
+        Ok(())
+    }
+
+    async fn purge_login_attempts(&self, before: DateTime<Utc>) -> Result<u64, Error> {
+        let now = Utc::now();
+        let mut data = self.write();
+        let count = data.login_attempts.len();
+        data.login_attempts.retain(|_, attempts| {
+            attempts.last_failure_at >= before
+                || attempts.locked_until.is_some_and(|until| until >= now)
+        });
+
+        Ok((count - data.login_attempts.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl ApiKeyRepository for MemoryStore {
+    async fn add_api_key(&self, key: ApiKey) -> Result<ApiKey, Error> {
+        let mut data = self.write();
+        data.live_account(key.account_id.0)
+            .ok_or(Error::AccountNotFound)?;
+        let key = ApiKey {
+            created_at: Utc::now(),
+            ..key
+        };
+        data.api_keys.insert(key.id.clone(), key.clone());
+
+        Ok(key)
+    }
+
+    async fn get_api_key(&self, key_id: &ApiKeyId) -> Result<Option<ApiKey>, Error> {
+        Ok(self
+            .read()
+            .api_keys
+            .get(key_id)
+            .filter(|key| key.revoked_at.is_none())
+            .cloned())
+    }
+
+    async fn get_api_keys(&self, account_id: &AccountId) -> Result<Vec<ApiKey>, Error> {
+        let mut keys: Vec<ApiKey> = self
+            .read()
+            .api_keys
+            .values()
+            .filter(|key| &key.account_id == account_id && key.revoked_at.is_none())
+            .cloned()
+            .collect();
+        keys.sort_by_key(|key| key.created_at);
+
+        Ok(keys)
+    }
+
+    async fn revoke_api_key(
+        &self,
+        account_id: &AccountId,
+        key_id: &ApiKeyId,
+    ) -> Result<bool, Error> {
+        match self.write().api_keys.get_mut(key_id) {
+            Some(key) if &key.account_id == account_id && key.revoked_at.is_none() => {
+                key.revoked_at = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn touch_api_key(&self, key_id: &ApiKeyId) -> Result<(), Error> {
+        if let Some(key) = self.write().api_keys.get_mut(key_id) {
+            key.last_used_at = Some(Utc::now());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TwoFactorRepository for MemoryStore {
+    async fn get_two_factor(&self, account_id: &AccountId) -> Result<TwoFactor, Error> {
+        let data = self.read();
+        data.live_account(account_id.0)
+            .ok_or(Error::AccountNotFound)?;
+        Ok(data
+            .two_factor
+            .get(&account_id.0)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn set_totp_secret(&self, account_id: &AccountId, secret: String) -> Result<(), Error> {
+        let mut data = self.write();
+        match data.live_account(account_id.0) {
+            Some(account) if !account.two_factor_enabled => (),
+            _ => return Err(Error::AccountNotFound),
+        }
+        data.two_factor.insert(
+            account_id.0,
+            TwoFactor {
+                secret: Some(secret),
+                enabled: false,
+                last_step: None,
+            },
+        );
+
+        Ok(())
+    }
+
+    async fn enable_two_factor(
+        &self,
+        account_id: &AccountId,
+        step: i64,
+        recovery_code_hashes: Vec<String>,
+    ) -> Result<(), Error> {
+        let mut data = self.write();
+        match data.two_factor.get_mut(&account_id.0) {
+            Some(two_factor) if two_factor.secret.is_some() => {
+                two_factor.enabled = true;
+                two_factor.last_step = Some(step);
+            }
+            _ => return Err(Error::AccountNotFound),
+        }
+        let account = data
+            .live_account_mut(account_id.0)
+            .ok_or(Error::AccountNotFound)?;
+        account.two_factor_enabled = true;
+        data.insert_recovery_codes(account_id, recovery_code_hashes);
+
+        Ok(())
+    }
+
+    async fn disable_two_factor(&self, account_id: &AccountId) -> Result<(), Error> {
+        let mut data = self.write();
+        let account = data
+            .live_account_mut(account_id.0)
+            .ok_or(Error::AccountNotFound)?;
+        account.two_factor_enabled = false;
+        data.two_factor.remove(&account_id.0);
+        data.recovery_codes
+            .retain(|_, stored| &stored.code.account_id != account_id);
+
+        Ok(())
+    }
+
+    async fn use_totp_step(&self, account_id: &AccountId, step: i64) -> Result<bool, Error> {
+        let mut data = self.write();
+        match data.two_factor.get_mut(&account_id.0) {
+            Some(two_factor) if two_factor.last_step.is_none_or(|last| last < step) => {
+                two_factor.last_step = Some(step);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn get_recovery_codes(&self, account_id: &AccountId) -> Result<Vec<RecoveryCode>, Error> {
+        Ok(self
+            .read()
+            .recovery_codes
+            .values()
+            .filter(|stored| &stored.code.account_id == account_id && !stored.used)
+            .map(|stored| stored.code.clone())
+            .collect())
+    }
+
+    async fn use_recovery_code(&self, code_id: i32) -> Result<bool, Error> {
+        match self.write().recovery_codes.get_mut(&code_id) {
+            Some(stored) if !stored.used => {
+                stored.used = true;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        account_id: &AccountId,
+        code_hashes: Vec<String>,
+    ) -> Result<(), Error> {
+        let mut data = self.write();
+        data.recovery_codes
+            .retain(|_, stored| &stored.code.account_id != account_id);
+        data.insert_recovery_codes(account_id, code_hashes);
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl VoteRepository for MemoryStore {
+    async fn cast_vote(
+        &self,
+        target: &VoteTarget,
+        account_id: &AccountId,
+        direction: VoteDirection,
+    ) -> Result<(), Error> {
+        let mut data = self.write();
+        data.votes
+            .insert((target.clone(), account_id.clone()), direction);
+        data.update_score(target);
+        Ok(())
+    }
+
+    async fn retract_vote(&self, target: &VoteTarget, account_id: &AccountId) -> Result<(), Error> {
+        let mut data = self.write();
+        data.votes.remove(&(target.clone(), account_id.clone()));
+        data.update_score(target);
+        Ok(())
+    }
+}
+
+/// Plain substring matching standing in for Postgres full-text search: every
+/// term has to occur, title hits count double.
+#[async_trait]
+impl SearchRepository for MemoryStore {
+    async fn search(
+        &self,
+        query: &SearchQuery,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<SearchResult>, Error> {
+        let terms = search_terms(&query.q);
+        let tags = query.tag_list();
+        let data = self.read();
+        let has_tags = |question: &Question| match &tags {
+            Some(tags) => {
+                let question_tags = question.tags.clone().unwrap_or_default();
+                tags.iter().all(|tag| question_tags.contains(tag))
+            }
+            None => true,
+        };
+
+        let mut results = Vec::new();
+        for stored in data.questions.values() {
+            let question = &stored.question;
+            if question.deleted_at.is_some() || !has_tags(question) {
+                continue;
+            }
+            if let Some(rank) = match_rank(&terms, &question.title, &question.content) {
+                results.push(SearchResult {
+                    kind: SearchResultKind::Question,
+                    question_id: question.id.clone(),
+                    answer_id: None,
+                    title: question.title.clone(),
+                    snippet: snippet(&question.content, &terms),
+                    rank,
+                });
+            }
+        }
+        for stored in data.live_answers() {
+            let answer = &stored.answer;
+            let question = match data.questions.get(&answer.question_id.0) {
+                Some(stored) if has_tags(&stored.question) => &stored.question,
+                _ => continue,
+            };
+            if let Some(rank) = match_rank(&terms, "", &answer.content) {
+                results.push(SearchResult {
+                    kind: SearchResultKind::Answer,
+                    question_id: question.id.clone(),
+                    answer_id: Some(answer.id.clone()),
+                    title: question.title.clone(),
+                    snippet: snippet(&answer.content, &terms),
+                    rank,
+                });
+            }
+        }
+        results.sort_by(|a, b| {
+            b.rank
+                .total_cmp(&a.rank)
+                .then(a.question_id.0.cmp(&b.question_id.0))
+                .then(
+                    a.answer_id
+                        .as_ref()
+                        .map(|id| id.0)
+                        .cmp(&b.answer_id.as_ref().map(|id| id.0)),
+                )
+        });
+
+        Ok(results
+            .into_iter()
+            .skip(offset.max(0) as usize)
+            .take(limit.max(0) as usize)
+            .collect())
+    }
+}
+
+fn search_terms(q: &str) -> Vec<String> {
+    q.split_whitespace()
+        .map(|term| {
+            term.trim_matches(|c: char| !c.is_alphanumeric())
+                .to_ascii_lowercase()
+        })
+        .filter(|term| !term.is_empty())
+        .collect()
+}
+
+fn match_rank(terms: &[String], title: &str, content: &str) -> Option<f32> {
+    if terms.is_empty() {
+        return None;
+    }
+    let title = title.to_ascii_lowercase();
+    let content = content.to_ascii_lowercase();
+    let mut rank = 0;
+    for term in terms {
+        let hits =
+            title.matches(term.as_str()).count() * 2 + content.matches(term.as_str()).count();
+        if hits == 0 {
+            return None;
+        }
+        rank += hits;
+    }
+
+    Some(rank as f32)
+}
+
+/// Cuts a window around the first hit and marks every hit, the way
+/// `ts_headline` does for Postgres.
+fn snippet(text: &str, terms: &[String]) -> String {
+    const BEFORE: usize = 40;
+    const LENGTH: usize = 200;
+
+    // ASCII lowercasing keeps byte offsets identical to `text`
+    let lower = text.to_ascii_lowercase();
+    let first_hit = terms
+        .iter()
+        .filter_map(|term| lower.find(term.as_str()))
+        .min()
+        .unwrap_or(0);
+    let mut start = first_hit.saturating_sub(BEFORE);
+    while !text.is_char_boundary(start) {
+        start -= 1;
+    }
+    let mut end = (start + LENGTH).min(text.len());
+    while !text.is_char_boundary(end) {
+        end += 1;
+    }
+
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push_str("...");
+    }
+    let mut i = start;
+    while i < end {
+        match terms
+            .iter()
+            .find(|term| lower[i..].starts_with(term.as_str()))
+        {
+            Some(term) => {
+                snippet.push(HIT_START);
+                snippet.push_str(&text[i..i + term.len()]);
+                snippet.push(HIT_END);
+                i += term.len();
+            }
+            None => {
+                let c = text[i..].chars().next().expect("index is a char boundary");
+                if c != HIT_START && c != HIT_END {
+                    snippet.push(c);
+                }
+                i += c.len_utf8();
+            }
+        }
+    }
+    if end < text.len() {
+        snippet.push_str("...");
+    }
+
+    highlight(&snippet)
+}
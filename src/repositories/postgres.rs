@@ -0,0 +1,2375 @@
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::{
+    postgres::{PgPoolOptions, PgRow},
+    PgPool, Postgres, QueryBuilder, Row, Transaction,
+};
+use tracing::event;
+
+use crate::{
+    common::{
+        config::DatabaseConfig,
+        error::Error,
+        rate_limit::{Bucket, Decision, Quota, RateLimitStore},
+    },
+    models::{
+        account::{
+            Account, AccountId, AccountInfo, AuthorSummary, ProfileUpdate, Role,
+            DELETED_DISPLAY_NAME,
+        },
+        account_token::{AccountToken, AccountTokenId, TokenPurpose},
+        answer::{Answer, AnswerId, AnswerUpdate, NewAnswer},
+        api_key::{ApiKey, ApiKeyId, ApiKeyScope},
+        comment::{Comment, CommentId, CommentTarget, CommentUpdate, NewComment},
+        login_attempt::LoginAttempts,
+        question::{
+            NewQuestion, Question, QuestionFilter, QuestionId, QuestionSort, QuestionStatus,
+            QuestionUpdate, TagMatch,
+        },
+        revision::{AnswerRevision, QuestionRevision},
+        search::{highlight, SearchQuery, SearchResult, SearchResultKind, HIT_END, HIT_START},
+        session::{AuthSession, SessionId},
+        tag::{Tag, TagQuery, TagSort},
+        two_factor::{RecoveryCode, TwoFactor},
+        vote::{VoteDirection, VoteTarget},
+        Cursor, CursorKey, Page, PageRequest,
+    },
+    repositories::store::{
+        AccountRepository, AccountTokenRepository, AnswerRepository, ApiKeyRepository,
+        CommentRepository, LoginAttemptRepository, QuestionRepository, RevisionRepository,
+        SearchRepository, SessionRepository, TagRepository, TrashRepository, TwoFactorRepository,
+        VoteRepository,
+    },
+};
+
+#[derive(Debug, Clone)]
+pub struct PgStore {
+    pub connection: PgPool,
+}
+
+impl PgStore {
+    pub async fn new(config: &DatabaseConfig) -> Self {
+        let db_pool = match PgPoolOptions::new()
+            .max_connections(config.max_connections)
+            .connect(&config.url)
+            .await
+        {
+            Ok(pool) => pool,
+            Err(e) => panic!("Couldn't establish database connection: {}", e),
+        };
+
+        PgStore {
+            connection: db_pool,
+        }
+    }
+}
+
+#[async_trait]
+impl QuestionRepository for PgStore {
+    async fn add_question(
+        &self,
+        new_question: NewQuestion,
+        account_id: &AccountId,
+    ) -> Result<Question, Error> {
+        match sqlx::query(
+            "WITH question AS (
+                 INSERT INTO questions (title, content, tags, account_id) VALUES ($1, $2, $3, $4)
+                 RETURNING *),
+             revision AS (
+                 INSERT INTO question_revisions
+                     (question_id, revision, title, content, tags, account_id, created_on)
+                 SELECT id, revision, title, content, tags, account_id, created_on FROM question)
+             SELECT question.*, accounts.display_name as author_name from question
+                 LEFT JOIN accounts ON accounts.id = question.account_id",
+        )
+        .bind(new_question.title)
+        .bind(new_question.content)
+        .bind(new_question.tags)
+        .bind(account_id.0)
+        .map(question_from_row)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(question) => Ok(question),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn get_questions(
+        &self,
+        filter: &QuestionFilter,
+        page: &PageRequest,
+    ) -> Result<Page<Question>, Error> {
+        // `sort_key` is whatever the listing is ordered by, cursors compare against it
+        let mut query = QueryBuilder::<Postgres>::new(
+            "SELECT * from (SELECT questions.*, accounts.display_name as author_name, ",
+        );
+        query.push(match filter.sort {
+            QuestionSort::Newest | QuestionSort::Oldest => "questions.created_on",
+            QuestionSort::Title => "lower(questions.title)",
+            QuestionSort::Activity => {
+                "greatest(questions.created_on, (SELECT max(created_on) from answers
+                     where corresponding_question = questions.id and deleted_at IS NULL))"
+            }
+            QuestionSort::Score => "questions.score",
+        });
+        query.push(
+            " as sort_key from questions
+                 LEFT JOIN accounts ON accounts.id = questions.account_id) questions WHERE true",
+        );
+        push_question_filter(&mut query, filter);
+        if let Some(after) = &page.after {
+            match filter.sort {
+                QuestionSort::Newest | QuestionSort::Activity => {
+                    query.push(" AND (sort_key, id) < (");
+                    push_cursor_key(&mut query, &after.key);
+                    query.push(", ").push_bind(after.id).push(")");
+                }
+                QuestionSort::Oldest | QuestionSort::Title => {
+                    query.push(" AND (sort_key, id) > (");
+                    push_cursor_key(&mut query, &after.key);
+                    query.push(", ").push_bind(after.id).push(")");
+                }
+                QuestionSort::Score => {
+                    query.push(" AND (sort_key < ");
+                    push_cursor_key(&mut query, &after.key);
+                    query.push(" OR (sort_key = ");
+                    push_cursor_key(&mut query, &after.key);
+                    query.push(" AND id > ").push_bind(after.id).push("))");
+                }
+            }
+        }
+        query.push(match filter.sort {
+            QuestionSort::Newest | QuestionSort::Activity => " order by sort_key desc, id desc",
+            QuestionSort::Oldest | QuestionSort::Title => " order by sort_key, id",
+            QuestionSort::Score => " order by sort_key desc, id",
+        });
+        query.push(" offset ").push_bind(page.offset);
+        query.push(" limit ").push_bind(page.limit + 1);
+
+        let sort = filter.sort;
+        match query
+            .build()
+            .map(|row: PgRow| {
+                let cursor = question_cursor(sort, &row);
+                (question_from_row(row), cursor)
+            })
+            .fetch_all(&self.connection)
+            .await
+        {
+            Ok(rows) => Ok(Page::from_rows(rows, page.limit)),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn count_questions(&self, filter: &QuestionFilter) -> Result<i64, Error> {
+        let mut query = QueryBuilder::<Postgres>::new("SELECT count(*) from questions WHERE true");
+        push_question_filter(&mut query, filter);
+
+        match query
+            .build()
+            .map(|row: PgRow| row.get::<i64, _>(0))
+            .fetch_one(&self.connection)
+            .await
+        {
+            Ok(total) => Ok(total),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn get_question_byid(&self, id: i64) -> Result<Question, Error> {
+        match sqlx::query(
+            "SELECT questions.*, accounts.display_name as author_name from questions
+                 LEFT JOIN accounts ON accounts.id = questions.account_id
+                 WHERE questions.id = $1 AND questions.deleted_at IS NULL",
+        )
+        .bind(id)
+        .map(question_from_row)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(question) => Ok(question),
+            Err(sqlx::Error::RowNotFound) => Err(Error::QuestionNotFound),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn update_question(
+        &self,
+        question: QuestionUpdate,
+        question_id: i64,
+        editor: &AccountId,
+    ) -> Result<Question, Error> {
+        // the row lock taken by UPDATE serializes concurrent edits, so
+        // revision numbers never collide
+        match sqlx::query(
+            "WITH question AS (
+                 UPDATE questions
+                 SET title = $1, content = $2, tags = $3, updated_on = NOW(),
+                     revision = revision + 1
+                 WHERE id = $4 AND deleted_at IS NULL
+                 RETURNING *),
+             revision AS (
+                 INSERT INTO question_revisions
+                     (question_id, revision, title, content, tags, account_id, comment, created_on)
+                 SELECT id, revision, title, content, tags, $5, $6, updated_on FROM question)
+             SELECT question.*, accounts.display_name as author_name from question
+                 LEFT JOIN accounts ON accounts.id = question.account_id",
+        )
+        .bind(question.title)
+        .bind(question.content)
+        .bind(question.tags)
+        .bind(question_id)
+        .bind(editor.0)
+        .bind(question.comment)
+        .map(question_from_row)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(question) => Ok(question),
+            Err(sqlx::Error::RowNotFound) => Err(Error::QuestionNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn delete_question(
+        &self,
+        question_id: i64,
+        deleted_by: &AccountId,
+    ) -> Result<bool, Error> {
+        match sqlx::query(
+            "UPDATE questions SET deleted_at = NOW(), deleted_by = $2
+                 WHERE id = $1 AND deleted_at IS NULL",
+        )
+        .bind(question_id)
+        .bind(deleted_by.0)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 0 => Err(Error::QuestionNotFound),
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn set_accepted_answer(
+        &self,
+        question_id: i64,
+        answer_id: Option<i64>,
+    ) -> Result<Question, Error> {
+        match sqlx::query(
+            "WITH question AS (
+                 UPDATE questions SET accepted_answer_id = $1
+                 WHERE id = $2 AND deleted_at IS NULL
+                 RETURNING *)
+             SELECT question.*, accounts.display_name as author_name from question
+                 LEFT JOIN accounts ON accounts.id = question.account_id",
+        )
+        .bind(answer_id)
+        .bind(question_id)
+        .map(question_from_row)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(question) => Ok(question),
+            Err(sqlx::Error::RowNotFound) => Err(Error::QuestionNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn is_question_owner(
+        &self,
+        question_id: i64,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        match sqlx::query("SELECT * from questions where id = $1 and account_id = $2")
+            .bind(question_id)
+            .bind(account_id.0)
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(question) => Ok(question.is_some()),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl AnswerRepository for PgStore {
+    async fn add_answer(
+        &self,
+        new_answer: NewAnswer,
+        account_id: &AccountId,
+    ) -> Result<Answer, Error> {
+        match sqlx::query(
+            "WITH answer AS (
+                 INSERT INTO answers (content, corresponding_question, account_id)
+                 SELECT $1, id, $3 from questions WHERE id = $2 AND deleted_at IS NULL
+                 RETURNING *),
+             revision AS (
+                 INSERT INTO answer_revisions (answer_id, revision, content, account_id, created_on)
+                 SELECT id, revision, content, account_id, created_on FROM answer)
+             SELECT answer.*, accounts.display_name as author_name from answer
+                 LEFT JOIN accounts ON accounts.id = answer.account_id",
+        )
+        .bind(new_answer.content)
+        .bind(new_answer.question_id.0)
+        .bind(account_id.0)
+        .map(answer_from_row)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(answer) => Ok(answer),
+            Err(sqlx::Error::RowNotFound) => Err(Error::QuestionNotFound),
+            Err(e) if is_foreign_key_violation(&e) => Err(Error::QuestionNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn get_answers(
+        &self,
+        question_id: i64,
+        page: &PageRequest,
+    ) -> Result<Page<Answer>, Error> {
+        match sqlx::query(
+            "SELECT answers.*, accounts.display_name as author_name from answers
+                 LEFT JOIN accounts ON accounts.id = answers.account_id
+                 WHERE answers.corresponding_question = $1 and answers.id > $2
+                     and answers.deleted_at IS NULL
+                 order by answers.id offset $3 limit $4",
+        )
+        .bind(question_id)
+        .bind(page.after.as_ref().map_or(0, |after| after.id))
+        .bind(page.offset)
+        .bind(page.limit + 1)
+        .map(|row: PgRow| {
+            let cursor = Cursor::new(CursorKey::Id, row.get("id"));
+            (answer_from_row(row), cursor)
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(rows) => Ok(Page::from_rows(rows, page.limit)),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn count_answers(&self, question_id: i64) -> Result<i64, Error> {
+        match sqlx::query(
+            "SELECT count(*) from answers where corresponding_question = $1 and deleted_at IS NULL",
+        )
+        .bind(question_id)
+        .map(|row: PgRow| row.get::<i64, _>(0))
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(total) => Ok(total),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn get_answer_byid(&self, id: i64) -> Result<Answer, Error> {
+        match sqlx::query(
+            "SELECT answers.*, accounts.display_name as author_name from answers
+                 JOIN questions ON questions.id = answers.corresponding_question
+                 LEFT JOIN accounts ON accounts.id = answers.account_id
+                 WHERE answers.id = $1 AND answers.deleted_at IS NULL
+                     AND questions.deleted_at IS NULL",
+        )
+        .bind(id)
+        .map(answer_from_row)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(answer) => Ok(answer),
+            Err(sqlx::Error::RowNotFound) => Err(Error::AnswerNotFound),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn update_answer(
+        &self,
+        answer: AnswerUpdate,
+        answer_id: i64,
+        editor: &AccountId,
+    ) -> Result<Answer, Error> {
+        match sqlx::query(
+            "WITH answer AS (
+                 UPDATE answers SET content = $1, updated_on = NOW(), revision = revision + 1
+                 WHERE id = $2 AND deleted_at IS NULL
+                 RETURNING *),
+             revision AS (
+                 INSERT INTO answer_revisions
+                     (answer_id, revision, content, account_id, comment, created_on)
+                 SELECT id, revision, content, $3, $4, updated_on FROM answer)
+             SELECT answer.*, accounts.display_name as author_name from answer
+                 LEFT JOIN accounts ON accounts.id = answer.account_id",
+        )
+        .bind(answer.content)
+        .bind(answer_id)
+        .bind(editor.0)
+        .bind(answer.comment)
+        .map(answer_from_row)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(answer) => Ok(answer),
+            Err(sqlx::Error::RowNotFound) => Err(Error::AnswerNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn delete_answer(&self, answer_id: i64, deleted_by: &AccountId) -> Result<bool, Error> {
+        match sqlx::query(
+            "WITH answer AS (
+                 UPDATE answers SET deleted_at = NOW(), deleted_by = $2
+                 WHERE id = $1 AND deleted_at IS NULL
+                 RETURNING id),
+             question AS (
+                 UPDATE questions SET accepted_answer_id = NULL
+                 WHERE accepted_answer_id IN (SELECT id from answer))
+             SELECT id from answer",
+        )
+        .bind(answer_id)
+        .bind(deleted_by.0)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(sqlx::Error::RowNotFound) => Err(Error::AnswerNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn is_answer_owner(&self, answer_id: i64, account_id: &AccountId) -> Result<bool, Error> {
+        match sqlx::query("SELECT * from answers where id = $1 and account_id = $2")
+            .bind(answer_id)
+            .bind(account_id.0)
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(answer) => Ok(answer.is_some()),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl CommentRepository for PgStore {
+    async fn add_comment(
+        &self,
+        target: &CommentTarget,
+        new_comment: NewComment,
+        account_id: &AccountId,
+    ) -> Result<Comment, Error> {
+        let (table, column, target_id) = comment_target_columns(target);
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+        let comment = match sqlx::query(&format!(
+            "WITH comment AS (
+                 INSERT INTO comments ({column}, parent_id, content, account_id)
+                 VALUES ($1, $2, $3, $4)
+                 RETURNING *)
+             SELECT comment.*, accounts.display_name as author_name from comment
+                 LEFT JOIN accounts ON accounts.id = comment.account_id"
+        ))
+        .bind(target_id)
+        .bind(new_comment.parent_id.map(|parent_id| parent_id.0))
+        .bind(new_comment.content)
+        .bind(account_id.0)
+        .map(comment_from_row)
+        .fetch_one(&mut *tx)
+        .await
+        {
+            Ok(comment) => comment,
+            Err(e) if is_foreign_key_violation(&e) => {
+                return Err(match target {
+                    CommentTarget::Question(_) => Error::QuestionNotFound,
+                    CommentTarget::Answer(_) => Error::AnswerNotFound,
+                })
+            }
+            Err(e) => return Err(database_error(e)),
+        };
+        update_comment_count(&mut tx, table, column, target_id).await?;
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(comment)
+    }
+
+    async fn get_comments(
+        &self,
+        target: &CommentTarget,
+        page: &PageRequest,
+    ) -> Result<Page<Comment>, Error> {
+        let (_, column, target_id) = comment_target_columns(target);
+        match sqlx::query(&format!(
+            "SELECT comments.*, accounts.display_name as author_name from comments
+                 LEFT JOIN accounts ON accounts.id = comments.account_id
+                 WHERE comments.{column} = $1 and comments.id > $2
+                 order by comments.id offset $3 limit $4"
+        ))
+        .bind(target_id)
+        .bind(page.after.as_ref().map_or(0, |after| after.id))
+        .bind(page.offset)
+        .bind(page.limit + 1)
+        .map(|row: PgRow| {
+            let cursor = Cursor::new(CursorKey::Id, row.get("id"));
+            (comment_from_row(row), cursor)
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(rows) => Ok(Page::from_rows(rows, page.limit)),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn get_comment(&self, comment_id: i64) -> Result<Comment, Error> {
+        match sqlx::query(
+            "SELECT comments.*, accounts.display_name as author_name from comments
+                 LEFT JOIN answers ON answers.id = comments.answer_id
+                 JOIN questions
+                     ON questions.id = coalesce(comments.question_id, answers.corresponding_question)
+                 LEFT JOIN accounts ON accounts.id = comments.account_id
+                 WHERE comments.id = $1 AND questions.deleted_at IS NULL
+                     AND answers.deleted_at IS NULL",
+        )
+        .bind(comment_id)
+        .map(comment_from_row)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(comment) => Ok(comment),
+            Err(sqlx::Error::RowNotFound) => Err(Error::CommentNotFound),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn update_comment(
+        &self,
+        comment: CommentUpdate,
+        comment_id: i64,
+    ) -> Result<Comment, Error> {
+        match sqlx::query(
+            "WITH comment AS (
+                 UPDATE comments SET content = $1, updated_on = NOW()
+                 WHERE id = $2
+                 RETURNING *)
+             SELECT comment.*, accounts.display_name as author_name from comment
+                 LEFT JOIN accounts ON accounts.id = comment.account_id",
+        )
+        .bind(comment.content)
+        .bind(comment_id)
+        .map(comment_from_row)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(comment) => Ok(comment),
+            Err(sqlx::Error::RowNotFound) => Err(Error::CommentNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn delete_comment(&self, comment_id: i64) -> Result<bool, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+        // replies are removed by the parent_id cascade
+        let target = match sqlx::query(
+            "DELETE FROM comments WHERE id = $1 RETURNING question_id, answer_id",
+        )
+        .bind(comment_id)
+        .map(|row: PgRow| comment_target_from_row(&row))
+        .fetch_one(&mut *tx)
+        .await
+        {
+            Ok(target) => target,
+            Err(sqlx::Error::RowNotFound) => return Err(Error::CommentNotFound),
+            Err(e) => return Err(database_error(e)),
+        };
+        let (table, column, target_id) = comment_target_columns(&target);
+        update_comment_count(&mut tx, table, column, target_id).await?;
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(true)
+    }
+
+    async fn is_comment_owner(
+        &self,
+        comment_id: i64,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        match sqlx::query("SELECT * from comments where id = $1 and account_id = $2")
+            .bind(comment_id)
+            .bind(account_id.0)
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(comment) => Ok(comment.is_some()),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl RevisionRepository for PgStore {
+    async fn get_question_revisions(
+        &self,
+        question_id: i64,
+        page: &PageRequest,
+    ) -> Result<Page<QuestionRevision>, Error> {
+        match sqlx::query(
+            "SELECT question_revisions.*, accounts.display_name as author_name
+                 from question_revisions
+                 LEFT JOIN accounts ON accounts.id = question_revisions.account_id
+                 WHERE question_id = $1 and revision > $2
+                 order by revision offset $3 limit $4",
+        )
+        .bind(question_id)
+        .bind(page.after.as_ref().map_or(0, |after| after.id))
+        .bind(page.offset)
+        .bind(page.limit + 1)
+        .map(|row: PgRow| {
+            let cursor = Cursor::new(CursorKey::Id, row.get("revision"));
+            (question_revision_from_row(row), cursor)
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(rows) => Ok(Page::from_rows(rows, page.limit)),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn get_question_revision(
+        &self,
+        question_id: i64,
+        revision: i32,
+    ) -> Result<QuestionRevision, Error> {
+        match sqlx::query(
+            "SELECT question_revisions.*, accounts.display_name as author_name
+                 from question_revisions
+                 LEFT JOIN accounts ON accounts.id = question_revisions.account_id
+                 WHERE question_id = $1 and revision = $2",
+        )
+        .bind(question_id)
+        .bind(revision)
+        .map(question_revision_from_row)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(revision) => Ok(revision),
+            Err(sqlx::Error::RowNotFound) => Err(Error::RevisionNotFound),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn get_answer_revisions(
+        &self,
+        answer_id: i64,
+        page: &PageRequest,
+    ) -> Result<Page<AnswerRevision>, Error> {
+        match sqlx::query(
+            "SELECT answer_revisions.*, accounts.display_name as author_name
+                 from answer_revisions
+                 LEFT JOIN accounts ON accounts.id = answer_revisions.account_id
+                 WHERE answer_id = $1 and revision > $2
+                 order by revision offset $3 limit $4",
+        )
+        .bind(answer_id)
+        .bind(page.after.as_ref().map_or(0, |after| after.id))
+        .bind(page.offset)
+        .bind(page.limit + 1)
+        .map(|row: PgRow| {
+            let cursor = Cursor::new(CursorKey::Id, row.get("revision"));
+            (answer_revision_from_row(row), cursor)
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(rows) => Ok(Page::from_rows(rows, page.limit)),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn get_answer_revision(
+        &self,
+        answer_id: i64,
+        revision: i32,
+    ) -> Result<AnswerRevision, Error> {
+        match sqlx::query(
+            "SELECT answer_revisions.*, accounts.display_name as author_name
+                 from answer_revisions
+                 LEFT JOIN accounts ON accounts.id = answer_revisions.account_id
+                 WHERE answer_id = $1 and revision = $2",
+        )
+        .bind(answer_id)
+        .bind(revision)
+        .map(answer_revision_from_row)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(revision) => Ok(revision),
+            Err(sqlx::Error::RowNotFound) => Err(Error::RevisionNotFound),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl TrashRepository for PgStore {
+    async fn get_deleted_questions(&self, page: &PageRequest) -> Result<Page<Question>, Error> {
+        let mut query = QueryBuilder::<Postgres>::new(
+            "SELECT questions.*, accounts.display_name as author_name from questions
+                 LEFT JOIN accounts ON accounts.id = questions.account_id
+                 WHERE questions.deleted_at IS NOT NULL",
+        );
+        push_trash_page(&mut query, "questions", page);
+
+        match query
+            .build()
+            .map(|row: PgRow| {
+                let cursor = trash_cursor(&row);
+                (question_from_row(row), cursor)
+            })
+            .fetch_all(&self.connection)
+            .await
+        {
+            Ok(rows) => Ok(Page::from_rows(rows, page.limit)),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn get_deleted_answers(&self, page: &PageRequest) -> Result<Page<Answer>, Error> {
+        let mut query = QueryBuilder::<Postgres>::new(
+            "SELECT answers.*, accounts.display_name as author_name from answers
+                 LEFT JOIN accounts ON accounts.id = answers.account_id
+                 WHERE answers.deleted_at IS NOT NULL",
+        );
+        push_trash_page(&mut query, "answers", page);
+
+        match query
+            .build()
+            .map(|row: PgRow| {
+                let cursor = trash_cursor(&row);
+                (answer_from_row(row), cursor)
+            })
+            .fetch_all(&self.connection)
+            .await
+        {
+            Ok(rows) => Ok(Page::from_rows(rows, page.limit)),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn restore_question(&self, question_id: i64) -> Result<Question, Error> {
+        match sqlx::query(
+            "WITH question AS (
+                 UPDATE questions SET deleted_at = NULL, deleted_by = NULL
+                 WHERE id = $1 AND deleted_at IS NOT NULL
+                 RETURNING *)
+             SELECT question.*, accounts.display_name as author_name from question
+                 LEFT JOIN accounts ON accounts.id = question.account_id",
+        )
+        .bind(question_id)
+        .map(question_from_row)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(question) => Ok(question),
+            Err(sqlx::Error::RowNotFound) => Err(Error::QuestionNotFound),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn restore_answer(&self, answer_id: i64) -> Result<Answer, Error> {
+        match sqlx::query(
+            "WITH answer AS (
+                 UPDATE answers SET deleted_at = NULL, deleted_by = NULL
+                 WHERE id = $1 AND deleted_at IS NOT NULL
+                 RETURNING *)
+             SELECT answer.*, accounts.display_name as author_name from answer
+                 LEFT JOIN accounts ON accounts.id = answer.account_id",
+        )
+        .bind(answer_id)
+        .map(answer_from_row)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(answer) => Ok(answer),
+            Err(sqlx::Error::RowNotFound) => Err(Error::AnswerNotFound),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<u64, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+        // answers of purged questions go through ON DELETE CASCADE, votes
+        // and revisions of both follow the same way
+        let answers = sqlx::query("DELETE FROM answers WHERE deleted_at < $1")
+            .bind(deleted_before.naive_utc())
+            .execute(&mut *tx)
+            .await
+            .map_err(database_error)?;
+        let questions = sqlx::query("DELETE FROM questions WHERE deleted_at < $1")
+            .bind(deleted_before.naive_utc())
+            .execute(&mut *tx)
+            .await
+            .map_err(database_error)?;
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(answers.rows_affected() + questions.rows_affected())
+    }
+}
+
+/// Tag columns with the usage count and synonyms, callers add the filter.
+const TAG_SELECT: &str = "SELECT tags.slug, tags.description,
+         (SELECT count(*) from questions
+             WHERE questions.tags @> ARRAY[tags.slug] AND questions.deleted_at IS NULL)
+             as question_count,
+         ARRAY(SELECT synonym from tag_synonyms WHERE tag_slug = tags.slug ORDER BY synonym)
+             as synonyms
+     from tags";
+
+/// Slug of the tag `$1` names, directly or as a synonym.
+const CANONICAL_SLUG: &str = "coalesce((SELECT tag_slug from tag_synonyms WHERE synonym = $1), $1)";
+
+#[async_trait]
+impl TagRepository for PgStore {
+    async fn resolve_tags(&self, slugs: Vec<String>) -> Result<Vec<String>, Error> {
+        match sqlx::query(
+            "SELECT coalesce(tag_synonyms.tag_slug, input.slug)
+                 from unnest($1::text[]) WITH ORDINALITY AS input(slug, position)
+                 LEFT JOIN tag_synonyms ON tag_synonyms.synonym = input.slug
+                 order by input.position",
+        )
+        .bind(slugs)
+        .map(|row: PgRow| row.get::<String, _>(0))
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(slugs) => Ok(slugs),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn ensure_tags(&self, slugs: &[String]) -> Result<(), Error> {
+        match sqlx::query(
+            "INSERT INTO tags (slug) SELECT unnest($1::text[]) ON CONFLICT DO NOTHING",
+        )
+        .bind(slugs)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn get_tags(&self, query: &TagQuery, offset: i64, limit: i64) -> Result<Vec<Tag>, Error> {
+        let mut builder = QueryBuilder::<Postgres>::new(TAG_SELECT);
+        builder.push(" WHERE true");
+        if let Some(prefix) = &query.q {
+            let pattern = format!("{}%", prefix);
+            builder
+                .push(" AND (tags.slug LIKE ")
+                .push_bind(pattern.clone())
+                .push(
+                    " OR EXISTS (SELECT 1 from tag_synonyms
+                         WHERE tag_slug = tags.slug AND synonym LIKE ",
+                )
+                .push_bind(pattern)
+                .push("))");
+        }
+        builder.push(match query.sort {
+            TagSort::Popular => " order by question_count desc, slug",
+            TagSort::Name => " order by slug",
+        });
+        builder.push(" offset ").push_bind(offset);
+        builder.push(" limit ").push_bind(limit);
+
+        match builder
+            .build()
+            .map(tag_from_row)
+            .fetch_all(&self.connection)
+            .await
+        {
+            Ok(tags) => Ok(tags),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn get_tag(&self, slug: &str) -> Result<Tag, Error> {
+        match sqlx::query(&format!("{TAG_SELECT} WHERE tags.slug = {CANONICAL_SLUG}"))
+            .bind(slug)
+            .map(tag_from_row)
+            .fetch_one(&self.connection)
+            .await
+        {
+            Ok(tag) => Ok(tag),
+            Err(sqlx::Error::RowNotFound) => Err(Error::TagNotFound),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn update_tag_description(&self, slug: &str, description: String) -> Result<Tag, Error> {
+        let slug: String = match sqlx::query(&format!(
+            "UPDATE tags SET description = $2 WHERE slug = {CANONICAL_SLUG} RETURNING slug"
+        ))
+        .bind(slug)
+        .bind(description)
+        .map(|row: PgRow| row.get("slug"))
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(slug) => slug,
+            Err(sqlx::Error::RowNotFound) => return Err(Error::TagNotFound),
+            Err(e) => return Err(database_error(e)),
+        };
+
+        self.get_tag(&slug).await
+    }
+
+    async fn add_tag_synonym(&self, slug: &str, synonym: &str) -> Result<Tag, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+        let slug: String = match sqlx::query(&format!(
+            "SELECT slug from tags WHERE slug = {CANONICAL_SLUG} FOR UPDATE"
+        ))
+        .bind(slug)
+        .map(|row: PgRow| row.get("slug"))
+        .fetch_one(&mut *tx)
+        .await
+        {
+            Ok(slug) => slug,
+            Err(sqlx::Error::RowNotFound) => return Err(Error::TagNotFound),
+            Err(e) => return Err(database_error(e)),
+        };
+        if slug == synonym {
+            return Err(Error::InvalidParameter(String::from(
+                "a tag cannot be a synonym of itself",
+            )));
+        }
+
+        // retag first, dropping the synonym where the tag is already present
+        sqlx::query(
+            "UPDATE questions SET tags = CASE
+                 WHEN tags @> ARRAY[$2] THEN array_remove(tags, $1)
+                 ELSE array_replace(tags, $1, $2) END
+             WHERE tags @> ARRAY[$1]",
+        )
+        .bind(synonym)
+        .bind(&slug)
+        .execute(&mut *tx)
+        .await
+        .map_err(database_error)?;
+        sqlx::query("UPDATE tag_synonyms SET tag_slug = $2 WHERE tag_slug = $1")
+            .bind(synonym)
+            .bind(&slug)
+            .execute(&mut *tx)
+            .await
+            .map_err(database_error)?;
+        sqlx::query("DELETE FROM tags WHERE slug = $1")
+            .bind(synonym)
+            .execute(&mut *tx)
+            .await
+            .map_err(database_error)?;
+        sqlx::query(
+            "INSERT INTO tag_synonyms (synonym, tag_slug) VALUES ($1, $2)
+             ON CONFLICT (synonym) DO UPDATE SET tag_slug = EXCLUDED.tag_slug",
+        )
+        .bind(synonym)
+        .bind(&slug)
+        .execute(&mut *tx)
+        .await
+        .map_err(database_error)?;
+        tx.commit().await.map_err(database_error)?;
+
+        self.get_tag(&slug).await
+    }
+
+    async fn remove_tag_synonym(&self, slug: &str, synonym: &str) -> Result<Tag, Error> {
+        let slug: String = match sqlx::query(&format!(
+            "DELETE FROM tag_synonyms WHERE synonym = $2 AND tag_slug = {CANONICAL_SLUG}
+             RETURNING tag_slug"
+        ))
+        .bind(slug)
+        .bind(synonym)
+        .map(|row: PgRow| row.get("tag_slug"))
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(slug) => slug,
+            Err(sqlx::Error::RowNotFound) => return Err(Error::TagNotFound),
+            Err(e) => return Err(database_error(e)),
+        };
+
+        self.get_tag(&slug).await
+    }
+}
+
+#[async_trait]
+impl AccountRepository for PgStore {
+    async fn add_account(&self, account: Account) -> Result<bool, Error> {
+        match sqlx::query(
+            "INSERT INTO accounts (email, password, display_name, role) VALUES($1, $2, $3, $4)",
+        )
+        .bind(account.email)
+        .bind(account.password)
+        .bind(account.display_name)
+        .bind(account.role.as_str())
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(error) if is_unique_violation(&error) => Err(Error::AccountAlreadyExists),
+            Err(error) => Err(database_error(error)),
+        }
+    }
+
+    async fn get_account(&self, email: String) -> Result<Account, Error> {
+        match sqlx::query("SELECT * from accounts where email = $1 AND deleted_at IS NULL")
+            .bind(email)
+            .map(account_from_row)
+            .fetch_one(&self.connection)
+            .await
+        {
+            Ok(account) => Ok(account),
+            Err(sqlx::Error::RowNotFound) => Err(Error::AccountNotFound),
+            Err(error) => {
+                event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn get_account_byid(&self, account_id: &AccountId) -> Result<Account, Error> {
+        match sqlx::query("SELECT * from accounts where id = $1 AND deleted_at IS NULL")
+            .bind(account_id.0)
+            .map(account_from_row)
+            .fetch_one(&self.connection)
+            .await
+        {
+            Ok(account) => Ok(account),
+            Err(sqlx::Error::RowNotFound) => Err(Error::AccountNotFound),
+            Err(error) => {
+                event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn get_accounts(&self, offset: i64, limit: i64) -> Result<Vec<AccountInfo>, Error> {
+        match sqlx::query(
+            "SELECT id, email, role from accounts WHERE deleted_at IS NULL
+                 order by id offset $1 limit $2",
+        )
+        .bind(offset)
+        .bind(limit)
+        .map(|row: PgRow| AccountInfo {
+            id: AccountId(row.get("id")),
+            email: row.get("email"),
+            role: row_role(&row),
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(accounts) => Ok(accounts),
+            Err(error) => {
+                event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn update_account_role(
+        &self,
+        account_id: &AccountId,
+        role: Role,
+    ) -> Result<AccountInfo, Error> {
+        match sqlx::query(
+            "UPDATE accounts SET role = $1 WHERE id = $2 AND deleted_at IS NULL
+                 RETURNING id, email, role",
+        )
+        .bind(role.as_str())
+        .bind(account_id.0)
+        .map(|row: PgRow| AccountInfo {
+            id: AccountId(row.get("id")),
+            email: row.get("email"),
+            role: row_role(&row),
+        })
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(account) => Ok(account),
+            Err(sqlx::Error::RowNotFound) => Err(Error::AccountNotFound),
+            Err(error) => {
+                event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn update_profile(
+        &self,
+        account_id: &AccountId,
+        update: ProfileUpdate,
+    ) -> Result<Account, Error> {
+        match sqlx::query(
+            "UPDATE accounts
+                 SET display_name = coalesce($1, display_name), bio = coalesce($2, bio),
+                     avatar_url = CASE WHEN $3::text IS NULL THEN avatar_url
+                                       ELSE nullif($3, '') END
+                 WHERE id = $4 AND deleted_at IS NULL
+                 RETURNING *",
+        )
+        .bind(update.display_name)
+        .bind(update.bio)
+        .bind(update.avatar_url)
+        .bind(account_id.0)
+        .map(account_from_row)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(account) => Ok(account),
+            Err(sqlx::Error::RowNotFound) => Err(Error::AccountNotFound),
+            Err(error) => {
+                event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn update_email(&self, account_id: &AccountId, email: String) -> Result<Account, Error> {
+        match sqlx::query(
+            "UPDATE accounts SET email = $1, email_verified = false
+                 WHERE id = $2 AND deleted_at IS NULL
+                 RETURNING *",
+        )
+        .bind(email)
+        .bind(account_id.0)
+        .map(account_from_row)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(account) => Ok(account),
+            Err(sqlx::Error::RowNotFound) => Err(Error::AccountNotFound),
+            Err(error) if is_unique_violation(&error) => Err(Error::AccountAlreadyExists),
+            Err(error) => {
+                event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn update_password(
+        &self,
+        account_id: &AccountId,
+        password_hash: String,
+    ) -> Result<(), Error> {
+        match sqlx::query("UPDATE accounts SET password = $1 WHERE id = $2 AND deleted_at IS NULL")
+            .bind(password_hash)
+            .bind(account_id.0)
+            .execute(&self.connection)
+            .await
+        {
+            Ok(result) if result.rows_affected() == 0 => Err(Error::AccountNotFound),
+            Ok(_) => Ok(()),
+            Err(error) => {
+                event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn mark_email_verified(
+        &self,
+        account_id: &AccountId,
+        email: &str,
+    ) -> Result<bool, Error> {
+        match sqlx::query(
+            "UPDATE accounts SET email_verified = true
+                 WHERE id = $1 AND email = $2 AND deleted_at IS NULL",
+        )
+        .bind(account_id.0)
+        .bind(email)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(error) => {
+                event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn delete_account(&self, account_id: &AccountId) -> Result<(), Error> {
+        // the email is the primary key, so it gets a unique placeholder
+        match sqlx::query(
+            "WITH account AS (
+                 UPDATE accounts
+                 SET email = 'deleted-' || id || '@deleted.invalid', password = '',
+                     display_name = $2, bio = '', avatar_url = NULL, email_verified = false,
+                     totp_secret = NULL, totp_enabled = false, deleted_at = NOW()
+                 WHERE id = $1 AND deleted_at IS NULL
+                 RETURNING id),
+             session AS (
+                 UPDATE sessions SET revoked_on = NOW()
+                 WHERE account_id IN (SELECT id from account) AND revoked_on IS NULL),
+             recovery_code AS (
+                 DELETE FROM recovery_codes WHERE account_id IN (SELECT id from account)),
+             api_key AS (
+                 UPDATE api_keys SET revoked_on = NOW()
+                 WHERE account_id IN (SELECT id from account) AND revoked_on IS NULL)
+             SELECT id from account",
+        )
+        .bind(account_id.0)
+        .bind(DELETED_DISPLAY_NAME)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::RowNotFound) => Err(Error::AccountNotFound),
+            Err(error) => {
+                event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl SessionRepository for PgStore {
+    async fn add_session(&self, session: AuthSession) -> Result<(), Error> {
+        match sqlx::query(
+            "INSERT INTO sessions (id, account_id, refresh_token_hash, expires_on)
+                 VALUES ($1, $2, $3, $4)",
+        )
+        .bind(session.id.0)
+        .bind(session.account_id.0)
+        .bind(session.refresh_token_hash)
+        .bind(session.expires_on)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn get_session(&self, session_id: &SessionId) -> Result<Option<AuthSession>, Error> {
+        match sqlx::query("SELECT * from sessions where id = $1")
+            .bind(session_id.0)
+            .map(|row: PgRow| AuthSession {
+                id: SessionId(row.get("id")),
+                account_id: AccountId(row.get("account_id")),
+                refresh_token_hash: row.get("refresh_token_hash"),
+                expires_on: row.get("expires_on"),
+                revoked_on: row.get("revoked_on"),
+            })
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(session) => Ok(session),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn rotate_refresh_token(
+        &self,
+        session_id: &SessionId,
+        refresh_token_hash: String,
+        expires_on: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        match sqlx::query(
+            "UPDATE sessions SET refresh_token_hash = $1, expires_on = $2
+                 WHERE id = $3 AND revoked_on IS NULL",
+        )
+        .bind(refresh_token_hash)
+        .bind(expires_on)
+        .bind(session_id.0)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 0 => Err(Error::CannotDecryptToken),
+            Ok(_) => Ok(()),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn revoke_session(&self, session_id: &SessionId) -> Result<(), Error> {
+        match sqlx::query(
+            "UPDATE sessions SET revoked_on = NOW() WHERE id = $1 AND revoked_on IS NULL",
+        )
+        .bind(session_id.0)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn revoke_account_sessions(&self, account_id: &AccountId) -> Result<(), Error> {
+        match sqlx::query(
+            "UPDATE sessions SET revoked_on = NOW() WHERE account_id = $1 AND revoked_on IS NULL",
+        )
+        .bind(account_id.0)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl AccountTokenRepository for PgStore {
+    async fn add_account_token(&self, token: AccountToken) -> Result<(), Error> {
+        match sqlx::query(
+            "INSERT INTO account_tokens (id, account_id, purpose, secret_hash, email, expires_on)
+                 VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(token.id.0)
+        .bind(token.account_id.0)
+        .bind(token.purpose.as_str())
+        .bind(token.secret_hash)
+        .bind(token.email)
+        .bind(token.expires_on)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn get_account_token(
+        &self,
+        token_id: &AccountTokenId,
+    ) -> Result<Option<AccountToken>, Error> {
+        match sqlx::query("SELECT * from account_tokens where id = $1")
+            .bind(token_id.0)
+            .map(|row: PgRow| AccountToken {
+                id: AccountTokenId(row.get("id")),
+                account_id: AccountId(row.get("account_id")),
+                // guarded by the CHECK constraint
+                purpose: row
+                    .get::<&str, _>("purpose")
+                    .parse()
+                    .unwrap_or(TokenPurpose::VerifyEmail),
+                secret_hash: row.get("secret_hash"),
+                email: row.get("email"),
+                expires_on: row.get("expires_on"),
+                used_on: row.get("used_on"),
+            })
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(token) => Ok(token),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn use_account_token(&self, token_id: &AccountTokenId) -> Result<bool, Error> {
+        match sqlx::query(
+            "UPDATE account_tokens SET used_on = NOW() WHERE id = $1 AND used_on IS NULL",
+        )
+        .bind(token_id.0)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn revoke_account_tokens(
+        &self,
+        account_id: &AccountId,
+        purpose: TokenPurpose,
+    ) -> Result<(), Error> {
+        match sqlx::query(
+            "UPDATE account_tokens SET used_on = NOW()
+                 WHERE account_id = $1 AND purpose = $2 AND used_on IS NULL",
+        )
+        .bind(account_id.0)
+        .bind(purpose.as_str())
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl LoginAttemptRepository for PgStore {
+    async fn get_login_attempts(&self, key: &str) -> Result<Option<LoginAttempts>, Error> {
+        match sqlx::query("SELECT * from login_attempts where key = $1")
+            .bind(key)
+            .map(login_attempts_from_row)
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(attempts) => Ok(attempts),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn record_login_failure(
+        &self,
+        key: &str,
+        window_start: DateTime<Utc>,
+    ) -> Result<LoginAttempts, Error> {
+        match sqlx::query(
+            "INSERT INTO login_attempts (key, failures, last_failure_on) VALUES ($1, 1, NOW())
+             ON CONFLICT (key) DO UPDATE SET
+                 failures = CASE WHEN login_attempts.last_failure_on < $2 THEN 1
+                                 ELSE login_attempts.failures + 1 END,
+                 last_failure_on = NOW()
+             RETURNING *",
+        )
+        .bind(key)
+        .bind(window_start)
+        .map(login_attempts_from_row)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(attempts) => Ok(attempts),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn lock_login(&self, key: &str, until: DateTime<Utc>) -> Result<(), Error> {
+        match sqlx::query("UPDATE login_attempts SET locked_until = $1 WHERE key = $2")
+            .bind(until)
+            .bind(key)
+            .execute(&self.connection)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn clear_login_attempts(&self, key: &str) -> Result<(), Error> {
+        match sqlx::query("DELETE FROM login_attempts WHERE key = $1")
+            .bind(key)
+            .execute(&self.connection)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn purge_login_attempts(&self, before: DateTime<Utc>) -> Result<u64, Error> {
+        match sqlx::query(
+            "DELETE FROM login_attempts
+                 WHERE last_failure_on < $1 AND (locked_until IS NULL OR locked_until < NOW())",
+        )
+        .bind(before)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(result) => Ok(result.rows_affected()),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for PgStore {
+    async fn acquire(&self, key: &str, quota: Quota) -> Result<Decision, Error> {
+        let now = Utc::now();
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+        // the no-op update locks an existing row, so concurrent requests
+        // from other instances wait for this one
+        let mut bucket = sqlx::query(
+            "INSERT INTO rate_limit_buckets (key, tokens, updated_on) VALUES ($1, $2, $3)
+             ON CONFLICT (key) DO UPDATE SET key = EXCLUDED.key
+             RETURNING tokens, updated_on",
+        )
+        .bind(key)
+        .bind(quota.requests as f64)
+        .bind(now)
+        .map(|row: PgRow| Bucket {
+            tokens: row.get("tokens"),
+            updated_at: row.get("updated_on"),
+        })
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(database_error)?;
+        let decision = bucket.acquire(quota, now);
+        sqlx::query("UPDATE rate_limit_buckets SET tokens = $1, updated_on = $2 WHERE key = $3")
+            .bind(bucket.tokens)
+            .bind(bucket.updated_at)
+            .bind(key)
+            .execute(&mut *tx)
+            .await
+            .map_err(database_error)?;
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(decision)
+    }
+
+    async fn peek(&self, key: &str, quota: Quota) -> Result<Decision, Error> {
+        let now = Utc::now();
+        let bucket =
+            sqlx::query("SELECT tokens, updated_on FROM rate_limit_buckets WHERE key = $1")
+                .bind(key)
+                .map(|row: PgRow| Bucket {
+                    tokens: row.get("tokens"),
+                    updated_at: row.get("updated_on"),
+                })
+                .fetch_optional(&self.connection)
+                .await
+                .map_err(database_error)?;
+
+        Ok(bucket
+            .unwrap_or_else(|| Bucket::full(quota, now))
+            .peek(quota, now))
+    }
+
+    async fn purge(&self, before: DateTime<Utc>) -> Result<u64, Error> {
+        match sqlx::query("DELETE FROM rate_limit_buckets WHERE updated_on < $1")
+            .bind(before)
+            .execute(&self.connection)
+            .await
+        {
+            Ok(result) => Ok(result.rows_affected()),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ApiKeyRepository for PgStore {
+    async fn add_api_key(&self, key: ApiKey) -> Result<ApiKey, Error> {
+        match sqlx::query(
+            "INSERT INTO api_keys (id, account_id, name, scope, secret_hash)
+                 VALUES ($1, $2, $3, $4, $5)
+                 RETURNING *",
+        )
+        .bind(key.id.0)
+        .bind(key.account_id.0)
+        .bind(key.name)
+        .bind(key.scope.as_str())
+        .bind(key.secret_hash)
+        .map(api_key_from_row)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(key) => Ok(key),
+            Err(e) if is_foreign_key_violation(&e) => Err(Error::AccountNotFound),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn get_api_key(&self, key_id: &ApiKeyId) -> Result<Option<ApiKey>, Error> {
+        match sqlx::query("SELECT * from api_keys where id = $1 AND revoked_on IS NULL")
+            .bind(key_id.0)
+            .map(api_key_from_row)
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(key) => Ok(key),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn get_api_keys(&self, account_id: &AccountId) -> Result<Vec<ApiKey>, Error> {
+        match sqlx::query(
+            "SELECT * from api_keys where account_id = $1 AND revoked_on IS NULL
+                 ORDER BY created_on, id",
+        )
+        .bind(account_id.0)
+        .map(api_key_from_row)
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(keys) => Ok(keys),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn revoke_api_key(
+        &self,
+        account_id: &AccountId,
+        key_id: &ApiKeyId,
+    ) -> Result<bool, Error> {
+        match sqlx::query(
+            "UPDATE api_keys SET revoked_on = NOW()
+                 WHERE id = $1 AND account_id = $2 AND revoked_on IS NULL",
+        )
+        .bind(key_id.0)
+        .bind(account_id.0)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn touch_api_key(&self, key_id: &ApiKeyId) -> Result<(), Error> {
+        match sqlx::query("UPDATE api_keys SET last_used_on = NOW() WHERE id = $1")
+            .bind(key_id.0)
+            .execute(&self.connection)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl TwoFactorRepository for PgStore {
+    async fn get_two_factor(&self, account_id: &AccountId) -> Result<TwoFactor, Error> {
+        match sqlx::query(
+            "SELECT totp_secret, totp_enabled, totp_last_step from accounts
+                 where id = $1 AND deleted_at IS NULL",
+        )
+        .bind(account_id.0)
+        .map(|row: PgRow| TwoFactor {
+            secret: row.get("totp_secret"),
+            enabled: row.get("totp_enabled"),
+            last_step: row.get("totp_last_step"),
+        })
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(two_factor) => Ok(two_factor),
+            Err(sqlx::Error::RowNotFound) => Err(Error::AccountNotFound),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn set_totp_secret(&self, account_id: &AccountId, secret: String) -> Result<(), Error> {
+        match sqlx::query(
+            "UPDATE accounts SET totp_secret = $1, totp_last_step = NULL
+                 WHERE id = $2 AND totp_enabled = false AND deleted_at IS NULL",
+        )
+        .bind(secret)
+        .bind(account_id.0)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 0 => Err(Error::AccountNotFound),
+            Ok(_) => Ok(()),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn enable_two_factor(
+        &self,
+        account_id: &AccountId,
+        step: i64,
+        recovery_code_hashes: Vec<String>,
+    ) -> Result<(), Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+        match sqlx::query(
+            "UPDATE accounts SET totp_enabled = true, totp_last_step = $1
+                 WHERE id = $2 AND totp_secret IS NOT NULL AND deleted_at IS NULL",
+        )
+        .bind(step)
+        .bind(account_id.0)
+        .execute(&mut *tx)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 0 => return Err(Error::AccountNotFound),
+            Ok(_) => (),
+            Err(e) => return Err(database_error(e)),
+        }
+        insert_recovery_codes(&mut tx, account_id, recovery_code_hashes).await?;
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(())
+    }
+
+    async fn disable_two_factor(&self, account_id: &AccountId) -> Result<(), Error> {
+        match sqlx::query(
+            "WITH account AS (
+                 UPDATE accounts
+                 SET totp_secret = NULL, totp_enabled = false, totp_last_step = NULL
+                 WHERE id = $1 AND deleted_at IS NULL
+                 RETURNING id),
+             recovery_code AS (
+                 DELETE FROM recovery_codes WHERE account_id IN (SELECT id from account))
+             SELECT id from account",
+        )
+        .bind(account_id.0)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::RowNotFound) => Err(Error::AccountNotFound),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn use_totp_step(&self, account_id: &AccountId, step: i64) -> Result<bool, Error> {
+        match sqlx::query(
+            "UPDATE accounts SET totp_last_step = $1
+                 WHERE id = $2 AND (totp_last_step IS NULL OR totp_last_step < $1)",
+        )
+        .bind(step)
+        .bind(account_id.0)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn get_recovery_codes(&self, account_id: &AccountId) -> Result<Vec<RecoveryCode>, Error> {
+        match sqlx::query(
+            "SELECT id, account_id, code_hash from recovery_codes
+                 where account_id = $1 AND used_on IS NULL ORDER BY id",
+        )
+        .bind(account_id.0)
+        .map(|row: PgRow| RecoveryCode {
+            id: row.get("id"),
+            account_id: AccountId(row.get("account_id")),
+            code_hash: row.get("code_hash"),
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(codes) => Ok(codes),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn use_recovery_code(&self, code_id: i32) -> Result<bool, Error> {
+        match sqlx::query(
+            "UPDATE recovery_codes SET used_on = NOW() WHERE id = $1 AND used_on IS NULL",
+        )
+        .bind(code_id)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(e) => {
+                event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        account_id: &AccountId,
+        code_hashes: Vec<String>,
+    ) -> Result<(), Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+        sqlx::query("DELETE FROM recovery_codes WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&mut *tx)
+            .await
+            .map_err(database_error)?;
+        insert_recovery_codes(&mut tx, account_id, code_hashes).await?;
+        tx.commit().await.map_err(database_error)?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl VoteRepository for PgStore {
+    async fn cast_vote(
+        &self,
+        target: &VoteTarget,
+        account_id: &AccountId,
+        direction: VoteDirection,
+    ) -> Result<(), Error> {
+        let (table, column, target_id) = vote_target_columns(target);
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+        sqlx::query(&format!(
+            "INSERT INTO votes (account_id, {column}, value) VALUES ($1, $2, $3)
+                 ON CONFLICT (account_id, {column}) WHERE {column} IS NOT NULL
+                 DO UPDATE SET value = EXCLUDED.value"
+        ))
+        .bind(account_id.0)
+        .bind(target_id)
+        .bind(direction.value())
+        .execute(&mut *tx)
+        .await
+        .map_err(database_error)?;
+        update_score(&mut tx, table, column, target_id).await?;
+
+        tx.commit().await.map_err(database_error)
+    }
+
+    async fn retract_vote(&self, target: &VoteTarget, account_id: &AccountId) -> Result<(), Error> {
+        let (table, column, target_id) = vote_target_columns(target);
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+        sqlx::query(&format!(
+            "DELETE FROM votes WHERE account_id = $1 AND {column} = $2"
+        ))
+        .bind(account_id.0)
+        .bind(target_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(database_error)?;
+        update_score(&mut tx, table, column, target_id).await?;
+
+        tx.commit().await.map_err(database_error)
+    }
+}
+
+#[async_trait]
+impl SearchRepository for PgStore {
+    async fn search(
+        &self,
+        query: &SearchQuery,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<SearchResult>, Error> {
+        match sqlx::query(
+            "WITH search AS (SELECT websearch_to_tsquery('english', $1) AS query)
+             SELECT 'question' AS kind, q.id AS question_id, NULL::integer AS answer_id, q.title,
+                    ts_headline('english', q.content, search.query, $2) AS snippet,
+                    ts_rank(q.search_vector, search.query) AS rank
+               FROM questions q, search
+              WHERE q.search_vector @@ search.query
+                AND q.deleted_at IS NULL
+                AND ($3::text[] IS NULL OR q.tags @> $3)
+             UNION ALL
+             SELECT 'answer', q.id, a.id, q.title,
+                    ts_headline('english', a.content, search.query, $2),
+                    ts_rank(a.search_vector, search.query)
+               FROM answers a
+               JOIN questions q ON q.id = a.corresponding_question, search
+              WHERE a.search_vector @@ search.query
+                AND a.deleted_at IS NULL AND q.deleted_at IS NULL
+                AND ($3::text[] IS NULL OR q.tags @> $3)
+             ORDER BY rank DESC, question_id, answer_id NULLS FIRST
+             OFFSET $4 LIMIT $5",
+        )
+        .bind(&query.q)
+        .bind(format!(
+            "StartSel={}, StopSel={}, MaxFragments=2, MaxWords=30, MinWords=10",
+            HIT_START, HIT_END
+        ))
+        .bind(query.tag_list())
+        .bind(offset)
+        .bind(limit)
+        .map(|row: PgRow| SearchResult {
+            kind: match row.get::<&str, _>("kind") {
+                "answer" => SearchResultKind::Answer,
+                _ => SearchResultKind::Question,
+            },
+            question_id: QuestionId(row.get("question_id")),
+            answer_id: row.get::<Option<i32>, _>("answer_id").map(AnswerId),
+            title: row.get("title"),
+            // ts_headline leaves the content as it is
+            snippet: highlight(row.get("snippet")),
+            rank: row.get("rank"),
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(results) => Ok(results),
+            Err(e) => {
+                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+}
+
+/// Table holding the score and `votes` column referencing the target.
+fn vote_target_columns(target: &VoteTarget) -> (&'static str, &'static str, i32) {
+    match target {
+        VoteTarget::Question(id) => ("questions", "question_id", id.0),
+        VoteTarget::Answer(id) => ("answers", "answer_id", id.0),
+    }
+}
+
+async fn update_score(
+    tx: &mut Transaction<'_, Postgres>,
+    table: &str,
+    column: &str,
+    target_id: i32,
+) -> Result<(), Error> {
+    sqlx::query(&format!(
+        "UPDATE {table} SET score =
+             (SELECT COALESCE(SUM(value), 0)::integer FROM votes WHERE {column} = $1)
+             WHERE id = $1"
+    ))
+    .bind(target_id)
+    .execute(&mut **tx)
+    .await
+    .map_err(database_error)?;
+
+    Ok(())
+}
+
+/// Table holding the comment count and `comments` column referencing the
+/// target.
+fn comment_target_columns(target: &CommentTarget) -> (&'static str, &'static str, i32) {
+    match target {
+        CommentTarget::Question(id) => ("questions", "question_id", id.0),
+        CommentTarget::Answer(id) => ("answers", "answer_id", id.0),
+    }
+}
+
+async fn update_comment_count(
+    tx: &mut Transaction<'_, Postgres>,
+    table: &str,
+    column: &str,
+    target_id: i32,
+) -> Result<(), Error> {
+    sqlx::query(&format!(
+        "UPDATE {table} SET comment_count =
+             (SELECT count(*)::integer FROM comments WHERE {column} = $1)
+             WHERE id = $1"
+    ))
+    .bind(target_id)
+    .execute(&mut **tx)
+    .await
+    .map_err(database_error)?;
+
+    Ok(())
+}
+
+async fn insert_recovery_codes(
+    tx: &mut Transaction<'_, Postgres>,
+    account_id: &AccountId,
+    code_hashes: Vec<String>,
+) -> Result<(), Error> {
+    if code_hashes.is_empty() {
+        return Ok(());
+    }
+    let mut query = QueryBuilder::new("INSERT INTO recovery_codes (account_id, code_hash) ");
+    query.push_values(code_hashes, |mut row, code_hash| {
+        row.push_bind(account_id.0).push_bind(code_hash);
+    });
+    query
+        .build()
+        .execute(&mut **tx)
+        .await
+        .map_err(database_error)?;
+
+    Ok(())
+}
+
+fn database_error(e: sqlx::Error) -> Error {
+    event!(tracing::Level::ERROR, "{:?}", e);
+    Error::DatabaseQueryError(e)
+}
+
+fn push_question_filter(query: &mut QueryBuilder<Postgres>, filter: &QuestionFilter) {
+    query.push(" AND deleted_at IS NULL");
+    match filter.status {
+        Some(QuestionStatus::Answered) => {
+            query.push(
+                " AND EXISTS (SELECT 1 from answers where corresponding_question = questions.id
+                     and answers.deleted_at IS NULL)",
+            );
+        }
+        Some(QuestionStatus::Unanswered) => {
+            query.push(
+                " AND NOT EXISTS (SELECT 1 from answers where corresponding_question = questions.id
+                     and answers.deleted_at IS NULL)",
+            );
+        }
+        Some(QuestionStatus::Resolved) => {
+            query.push(" AND accepted_answer_id IS NOT NULL");
+        }
+        None => (),
+    }
+    if let Some(tags) = filter.tag_list() {
+        match filter.tag_match {
+            TagMatch::Any => query.push(" AND tags && "),
+            TagMatch::All => query.push(" AND tags @> "),
+        };
+        query.push_bind(tags);
+    }
+    if let Some(author) = filter.author_id() {
+        query.push(" AND account_id = ").push_bind(author.0);
+    }
+    if let Some(created_after) = filter.created_after {
+        query
+            .push(" AND created_on >= ")
+            .push_bind(created_after.naive_utc());
+    }
+    if let Some(created_before) = filter.created_before {
+        query
+            .push(" AND created_on <= ")
+            .push_bind(created_before.naive_utc());
+    }
+}
+
+/// Trash listings run from the most recently deleted post backwards.
+fn push_trash_page(query: &mut QueryBuilder<Postgres>, table: &str, page: &PageRequest) {
+    if let Some(after) = &page.after {
+        query.push(format!(" AND ({table}.deleted_at, {table}.id) < ("));
+        push_cursor_key(query, &after.key);
+        query.push(", ").push_bind(after.id).push(")");
+    }
+    query.push(format!(
+        " order by {table}.deleted_at desc, {table}.id desc"
+    ));
+    query.push(" offset ").push_bind(page.offset);
+    query.push(" limit ").push_bind(page.limit + 1);
+}
+
+fn trash_cursor(row: &PgRow) -> Cursor {
+    let deleted_at = row.get::<NaiveDateTime, _>("deleted_at").and_utc();
+    Cursor::new(CursorKey::Time(deleted_at), row.get("id"))
+}
+
+fn push_cursor_key(query: &mut QueryBuilder<Postgres>, key: &CursorKey) {
+    match key {
+        CursorKey::Id => query.push("id"),
+        CursorKey::Time(time) => query.push_bind(time.naive_utc()),
+        CursorKey::Text(text) => query.push_bind(text.clone()),
+        CursorKey::Score(score) => query.push_bind(*score),
+    };
+}
+
+fn question_cursor(sort: QuestionSort, row: &PgRow) -> Cursor {
+    let key = match sort {
+        QuestionSort::Newest | QuestionSort::Oldest | QuestionSort::Activity => {
+            CursorKey::Time(row.get::<NaiveDateTime, _>("sort_key").and_utc())
+        }
+        QuestionSort::Title => CursorKey::Text(row.get("sort_key")),
+        QuestionSort::Score => CursorKey::Score(row.get("sort_key")),
+    };
+    Cursor::new(key, row.get("id")).with_sort(sort)
+}
+
+fn question_from_row(row: PgRow) -> Question {
+    Question {
+        id: QuestionId(row.get("id")),
+        title: row.get("title"),
+        content: row.get("content"),
+        tags: row.get("tags"),
+        accepted_answer_id: row
+            .get::<Option<i32>, _>("accepted_answer_id")
+            .map(AnswerId),
+        score: row.get("score"),
+        comment_count: row.get("comment_count"),
+        created_at: row.get::<NaiveDateTime, _>("created_on").and_utc(),
+        updated_at: row.get::<NaiveDateTime, _>("updated_on").and_utc(),
+        author: author_from_row(&row),
+        revision: row.get("revision"),
+        deleted_at: deleted_at_from_row(&row),
+        deleted_by: row.get::<Option<i32>, _>("deleted_by").map(AccountId),
+    }
+}
+
+fn answer_from_row(row: PgRow) -> Answer {
+    Answer {
+        id: AnswerId(row.get("id")),
+        content: row.get("content"),
+        question_id: QuestionId(row.get("corresponding_question")),
+        score: row.get("score"),
+        comment_count: row.get("comment_count"),
+        created_at: row.get::<NaiveDateTime, _>("created_on").and_utc(),
+        updated_at: row.get::<NaiveDateTime, _>("updated_on").and_utc(),
+        author: author_from_row(&row),
+        revision: row.get("revision"),
+        deleted_at: deleted_at_from_row(&row),
+        deleted_by: row.get::<Option<i32>, _>("deleted_by").map(AccountId),
+    }
+}
+
+fn comment_from_row(row: PgRow) -> Comment {
+    Comment {
+        id: CommentId(row.get("id")),
+        question_id: row.get::<Option<i32>, _>("question_id").map(QuestionId),
+        answer_id: row.get::<Option<i32>, _>("answer_id").map(AnswerId),
+        parent_id: row.get::<Option<i32>, _>("parent_id").map(CommentId),
+        content: row.get("content"),
+        created_at: row.get::<NaiveDateTime, _>("created_on").and_utc(),
+        updated_at: row.get::<NaiveDateTime, _>("updated_on").and_utc(),
+        author: author_from_row(&row),
+    }
+}
+
+/// The table CHECK guarantees exactly one of the two columns is set.
+fn comment_target_from_row(row: &PgRow) -> CommentTarget {
+    match row.get::<Option<i32>, _>("question_id") {
+        Some(id) => CommentTarget::Question(QuestionId(id)),
+        None => CommentTarget::Answer(AnswerId(row.get("answer_id"))),
+    }
+}
+
+fn deleted_at_from_row(row: &PgRow) -> Option<DateTime<Utc>> {
+    row.get::<Option<NaiveDateTime>, _>("deleted_at")
+        .map(|deleted_at| deleted_at.and_utc())
+}
+
+fn question_revision_from_row(row: PgRow) -> QuestionRevision {
+    QuestionRevision {
+        question_id: QuestionId(row.get("question_id")),
+        revision: row.get("revision"),
+        title: row.get("title"),
+        content: row.get("content"),
+        tags: row.get("tags"),
+        comment: row.get("comment"),
+        editor: author_from_row(&row),
+        created_at: row.get::<NaiveDateTime, _>("created_on").and_utc(),
+    }
+}
+
+fn answer_revision_from_row(row: PgRow) -> AnswerRevision {
+    AnswerRevision {
+        answer_id: AnswerId(row.get("answer_id")),
+        revision: row.get("revision"),
+        content: row.get("content"),
+        comment: row.get("comment"),
+        editor: author_from_row(&row),
+        created_at: row.get::<NaiveDateTime, _>("created_on").and_utc(),
+    }
+}
+
+fn tag_from_row(row: PgRow) -> Tag {
+    Tag {
+        slug: row.get("slug"),
+        description: row.get("description"),
+        question_count: row.get("question_count"),
+        synonyms: row.get("synonyms"),
+    }
+}
+
+/// Reads the `author_name` column joined in from accounts.
+fn author_from_row(row: &PgRow) -> Option<AuthorSummary> {
+    let id: Option<i32> = row.get("account_id");
+    let display_name: Option<String> = row.get("author_name");
+    Some(AuthorSummary {
+        id: AccountId(id?),
+        display_name: display_name?,
+    })
+}
+
+fn account_from_row(row: PgRow) -> Account {
+    Account {
+        id: Some(AccountId(row.get("id"))),
+        email: row.get("email"),
+        password: row.get("password"),
+        display_name: row.get("display_name"),
+        role: row_role(&row),
+        bio: row.get("bio"),
+        avatar_url: row.get("avatar_url"),
+        email_verified: row.get("email_verified"),
+        two_factor_enabled: row.get("totp_enabled"),
+        created_at: row.get::<NaiveDateTime, _>("created_on").and_utc(),
+    }
+}
+
+fn login_attempts_from_row(row: PgRow) -> LoginAttempts {
+    LoginAttempts {
+        failures: row.get("failures"),
+        last_failure_at: row.get("last_failure_on"),
+        locked_until: row.get("locked_until"),
+    }
+}
+
+fn api_key_from_row(row: PgRow) -> ApiKey {
+    ApiKey {
+        id: ApiKeyId(row.get("id")),
+        account_id: AccountId(row.get("account_id")),
+        name: row.get("name"),
+        // guarded by the CHECK constraint
+        scope: row
+            .get::<&str, _>("scope")
+            .parse()
+            .unwrap_or(ApiKeyScope::Read),
+        secret_hash: row.get("secret_hash"),
+        created_at: row.get::<NaiveDateTime, _>("created_on").and_utc(),
+        last_used_at: row.get("last_used_on"),
+        revoked_at: row.get("revoked_on"),
+    }
+}
+
+fn row_role(row: &PgRow) -> Role {
+    row.get::<String, _>("role").parse().unwrap_or_default()
+}
+
+fn is_unique_violation(error: &sqlx::Error) -> bool {
+    error
+        .as_database_error()
+        .map(|e| e.is_unique_violation())
+        .unwrap_or(false)
+}
+
+fn is_foreign_key_violation(error: &sqlx::Error) -> bool {
+    error
+        .as_database_error()
+        .map(|e| e.is_foreign_key_violation())
+        .unwrap_or(false)
+}
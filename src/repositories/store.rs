@@ -1,238 +1,371 @@
-use axum::Json;
-use sqlx::{
-    postgres::{PgPoolOptions, PgRow},
-    PgPool, Row,
-};
-use tracing::event;
+use std::{fmt::Debug, sync::Arc};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 
 use crate::{
     common::error::Error,
     models::{
-        account::{Account, AccountId},
-        answer::{Answer, AnswerId, NewAnswer},
-        question::{NewQuestion, Question, QuestionId},
+        account::{Account, AccountId, AccountInfo, ProfileUpdate, Role},
+        account_token::{AccountToken, AccountTokenId, TokenPurpose},
+        answer::{Answer, AnswerUpdate, NewAnswer},
+        api_key::{ApiKey, ApiKeyId},
+        comment::{Comment, CommentTarget, CommentUpdate, NewComment},
+        login_attempt::LoginAttempts,
+        question::{NewQuestion, Question, QuestionFilter, QuestionUpdate},
+        revision::{AnswerRevision, QuestionRevision},
+        search::{SearchQuery, SearchResult},
+        session::{AuthSession, SessionId},
+        tag::{Tag, TagQuery},
+        two_factor::{RecoveryCode, TwoFactor},
+        vote::{VoteDirection, VoteTarget},
+        Page, PageRequest,
     },
 };
 
-#[derive(Debug, Clone)]
-pub struct Store {
-    pub connection: PgPool,
-}
-
-impl Store {
-    pub async fn new(db_url: &str) -> Self {
-        let db_pool = match PgPoolOptions::new()
-            .max_connections(5)
-            .connect(db_url)
-            .await
-        {
-            Ok(pool) => pool,
-            Err(e) => panic!("Couldn't establish database connection: {}", e),
-        };
-
-        Store {
-            connection: db_pool,
-        }
-    }
-
-    pub async fn add_question(&self, new_question: NewQuestion) -> Result<Question, Error> {
-        match sqlx::query(
-            "INSERT INTO questions (title, content, tags) VALUES ($1, $2, $3)
-                 RETURNING *",
-        )
-        .bind(new_question.title)
-        .bind(new_question.content)
-        .bind(new_question.tags)
-        .map(|row: PgRow| Question {
-            id: QuestionId(row.get("id")),
-            title: row.get("title"),
-            content: row.get("content"),
-            tags: row.get("tags"),
-        })
-        .fetch_one(&self.connection)
-        .await
-        {
-            Ok(question) => Ok(question),
-            Err(e) => {
-                tracing::event!(tracing::Level::ERROR, "{:?}", e);
-                Err(Error::DatabaseQueryError(e))
-            }
-        }
-    }
-
-    pub async fn get_questions(&self, offset: i64, limit: i64) -> Result<Vec<Question>, Error> {
-        match sqlx::query("SELECT * from questions offset $1 limit $2")
-            .bind(offset)
-            .bind(limit)
-            .map(|row: PgRow| Question {
-                id: QuestionId(row.get("id")),
-                title: row.get("title"),
-                content: row.get("content"),
-                tags: row.get("tags"),
-            })
-            .fetch_all(&self.connection)
-            .await
-        {
-            Ok(questions) => Ok(questions),
-            Err(e) => {
-                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
-                Err(Error::DatabaseQueryError(e))
-            }
-        }
-    }
-
-    pub async fn get_question_byid(&self, id: i64) -> Result<Question, Error> {
-        match sqlx::query("SELECT * from questions where id=$1")
-            .bind(id)
-            .map(|row: PgRow| Question {
-                id: QuestionId(row.get("id")),
-                title: row.get("title"),
-                content: row.get("content"),
-                tags: row.get("tags"),
-            })
-            .fetch_one(&self.connection)
-            .await
-        {
-            Ok(question) => Ok(question),
-            Err(e) => {
-                event!(target:"axum-web-dev", tracing::Level::ERROR, "{:?}", e);
-                Err(Error::DatabaseQueryError(e))
-            }
-        }
-    }
-
-    pub async fn update_question(
-        &self,
-        question: Question,
+/// Shared handle to the storage backend selected at startup.
+pub type Store = Arc<dyn Repository>;
+
+#[async_trait]
+pub trait QuestionRepository {
+    async fn add_question(
+        &self,
+        new_question: NewQuestion,
+        account_id: &AccountId,
+    ) -> Result<Question, Error>;
+    async fn get_questions(
+        &self,
+        filter: &QuestionFilter,
+        page: &PageRequest,
+    ) -> Result<Page<Question>, Error>;
+    async fn count_questions(&self, filter: &QuestionFilter) -> Result<i64, Error>;
+    async fn get_question_byid(&self, id: i64) -> Result<Question, Error>;
+    /// Applies the edit and records it as the next revision.
+    async fn update_question(
+        &self,
+        question: QuestionUpdate,
+        question_id: i64,
+        editor: &AccountId,
+    ) -> Result<Question, Error>;
+    /// Moves the question to the trash, hiding its answers along with it.
+    async fn delete_question(
+        &self,
+        question_id: i64,
+        deleted_by: &AccountId,
+    ) -> Result<bool, Error>;
+    /// `None` clears the accepted answer.
+    async fn set_accepted_answer(
+        &self,
+        question_id: i64,
+        answer_id: Option<i64>,
+    ) -> Result<Question, Error>;
+    async fn is_question_owner(
+        &self,
         question_id: i64,
-    ) -> Result<Question, Error> {
-        match sqlx::query(
-            "UPDATE questions
-        SET title = $1, content = $2, tags = $3
-        WHERE id = $4
-        RETURNING id, title, content, tags",
-        )
-        .bind(question.title)
-        .bind(question.content)
-        .bind(question.tags)
-        .bind(question_id)
-        .map(|row: PgRow| Question {
-            id: QuestionId(row.get("id")),
-            title: row.get("title"),
-            content: row.get("content"),
-            tags: row.get("tags"),
-        })
-        .fetch_one(&self.connection)
-        .await
-        {
-            Ok(question) => Ok(question),
-            Err(e) => {
-                tracing::event!(tracing::Level::ERROR, "{:?}", e);
-                Err(Error::DatabaseQueryError(e))
-            }
-        }
-    }
-
-    pub async fn delete_question(&self, question_id: i64) -> Result<bool, Error> {
-        match sqlx::query("DELETE FROM questions WHERE id = $1")
-            .bind(question_id)
-            .execute(&self.connection)
-            .await
-        {
-            Ok(_) => Ok(true),
-            Err(e) => {
-                tracing::event!(tracing::Level::ERROR, "{:?}", e);
-                Err(Error::DatabaseQueryError(e))
-            }
-        }
-    }
-
-    pub async fn add_answer(&self, new_answer: NewAnswer) -> Result<Answer, Error> {
-        match sqlx::query(
-            "INSERT INTO answers (content, corresponding_question) VALUES ($1, $2) RETURNING *",
-        )
-        .bind(new_answer.content)
-        .bind(new_answer.question_id.0)
-        .map(|row: PgRow| Answer {
-            id: AnswerId(row.get("id")),
-            content: row.get("content"),
-            question_id: QuestionId(row.get("corresponding_question")),
-        })
-        .fetch_one(&self.connection)
-        .await
-        {
-            Ok(answer) => Ok(answer),
-            Err(e) => {
-                tracing::event!(tracing::Level::ERROR, "{:?}", e);
-                Err(Error::DatabaseQueryError(e))
-            }
-        }
-    }
-
-    pub async fn add_account(self, account: Account) -> Result<bool, Error> {
-        match sqlx::query("INSERT INTO accounts (email, password) VALUES($1, $2)")
-            .bind(account.email)
-            .bind(account.password)
-            .execute(&self.connection)
-            .await
-        {
-            Ok(_) => Ok(true),
-            Err(error) => {
-                let err_code = error
-                    .as_database_error()
-                    .unwrap()
-                    .code()
-                    .unwrap()
-                    .parse::<i32>()
-                    .unwrap();
-                let err_message = error.as_database_error().unwrap().message();
-                let err_constraint = error.as_database_error().unwrap().constraint().unwrap();
-                event!(
-                    tracing::Level::ERROR,
-                    code = err_code,
-                    message = err_message,
-                    constraint = err_constraint
-                );
-                Err(Error::DatabaseQueryError(error))
-            }
-        }
-    }
-
-    pub async fn get_account(self, email: String) -> Result<Account, Error> {
-        match sqlx::query("SELECT * from accounts where email = $1")
-            .bind(email)
-            .map(|row: PgRow| Account {
-                id: Some(AccountId(row.get("id"))),
-                email: row.get("email"),
-                password: row.get("password"),
-            })
-            .fetch_one(&self.connection)
-            .await
-        {
-            Ok(account) => Ok(account),
-            Err(error) => {
-                event!(tracing::Level::ERROR, "{:?}", error);
-                Err(Error::DatabaseQueryError(error))
-            }
-        }
-    }
-
-    pub async fn is_question_owner(
+        account_id: &AccountId,
+    ) -> Result<bool, Error>;
+}
+
+#[async_trait]
+pub trait AnswerRepository {
+    async fn add_answer(
+        &self,
+        new_answer: NewAnswer,
+        account_id: &AccountId,
+    ) -> Result<Answer, Error>;
+    /// Answers in id order.
+    async fn get_answers(
         &self,
         question_id: i64,
+        page: &PageRequest,
+    ) -> Result<Page<Answer>, Error>;
+    async fn count_answers(&self, question_id: i64) -> Result<i64, Error>;
+    async fn get_answer_byid(&self, id: i64) -> Result<Answer, Error>;
+    /// Applies the edit and records it as the next revision.
+    async fn update_answer(
+        &self,
+        answer: AnswerUpdate,
+        answer_id: i64,
+        editor: &AccountId,
+    ) -> Result<Answer, Error>;
+    /// Moves the answer to the trash and clears it as accepted answer.
+    async fn delete_answer(&self, answer_id: i64, deleted_by: &AccountId) -> Result<bool, Error>;
+    async fn is_answer_owner(&self, answer_id: i64, account_id: &AccountId) -> Result<bool, Error>;
+}
+
+/// Comments on questions and answers. Comments of posts in the trash are
+/// treated as missing.
+#[async_trait]
+pub trait CommentRepository {
+    async fn add_comment(
+        &self,
+        target: &CommentTarget,
+        new_comment: NewComment,
+        account_id: &AccountId,
+    ) -> Result<Comment, Error>;
+    /// Comments in id order, replies are linked through `parent_id`.
+    async fn get_comments(
+        &self,
+        target: &CommentTarget,
+        page: &PageRequest,
+    ) -> Result<Page<Comment>, Error>;
+    async fn get_comment(&self, comment_id: i64) -> Result<Comment, Error>;
+    async fn update_comment(
+        &self,
+        comment: CommentUpdate,
+        comment_id: i64,
+    ) -> Result<Comment, Error>;
+    /// Removes the comment along with the replies to it.
+    async fn delete_comment(&self, comment_id: i64) -> Result<bool, Error>;
+    async fn is_comment_owner(
+        &self,
+        comment_id: i64,
+        account_id: &AccountId,
+    ) -> Result<bool, Error>;
+}
+
+/// Revision history, kept in sync by `update_question` and `update_answer`.
+#[async_trait]
+pub trait RevisionRepository {
+    /// Revisions in order, oldest first.
+    async fn get_question_revisions(
+        &self,
+        question_id: i64,
+        page: &PageRequest,
+    ) -> Result<Page<QuestionRevision>, Error>;
+    async fn get_question_revision(
+        &self,
+        question_id: i64,
+        revision: i32,
+    ) -> Result<QuestionRevision, Error>;
+    async fn get_answer_revisions(
+        &self,
+        answer_id: i64,
+        page: &PageRequest,
+    ) -> Result<Page<AnswerRevision>, Error>;
+    async fn get_answer_revision(
+        &self,
+        answer_id: i64,
+        revision: i32,
+    ) -> Result<AnswerRevision, Error>;
+}
+
+/// Deleted posts. Everything outside this trait treats them as missing.
+#[async_trait]
+pub trait TrashRepository {
+    /// Most recently deleted first.
+    async fn get_deleted_questions(&self, page: &PageRequest) -> Result<Page<Question>, Error>;
+    async fn get_deleted_answers(&self, page: &PageRequest) -> Result<Page<Answer>, Error>;
+    async fn restore_question(&self, question_id: i64) -> Result<Question, Error>;
+    async fn restore_answer(&self, answer_id: i64) -> Result<Answer, Error>;
+    /// Removes posts deleted before `deleted_before` for good, returns the
+    /// number of trashed posts removed.
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<u64, Error>;
+}
+
+/// Tags are stored as slugs on the questions, this keeps the catalogue of
+/// known tags next to them.
+#[async_trait]
+pub trait TagRepository {
+    /// Replaces synonyms with their tag, other slugs are kept as they are.
+    async fn resolve_tags(&self, slugs: Vec<String>) -> Result<Vec<String>, Error>;
+    /// Adds the slugs missing from the catalogue.
+    async fn ensure_tags(&self, slugs: &[String]) -> Result<(), Error>;
+    async fn get_tags(&self, query: &TagQuery, offset: i64, limit: i64) -> Result<Vec<Tag>, Error>;
+    /// Also finds a tag by one of its synonyms.
+    async fn get_tag(&self, slug: &str) -> Result<Tag, Error>;
+    async fn update_tag_description(&self, slug: &str, description: String) -> Result<Tag, Error>;
+    /// Makes `synonym` an alias of `slug`. Questions tagged `synonym` are
+    /// retagged and a tag of that name is merged into `slug`.
+    async fn add_tag_synonym(&self, slug: &str, synonym: &str) -> Result<Tag, Error>;
+    async fn remove_tag_synonym(&self, slug: &str, synonym: &str) -> Result<Tag, Error>;
+}
+
+/// Deleted accounts are treated as missing.
+#[async_trait]
+pub trait AccountRepository {
+    async fn add_account(&self, account: Account) -> Result<bool, Error>;
+    async fn get_account(&self, email: String) -> Result<Account, Error>;
+    async fn get_account_byid(&self, account_id: &AccountId) -> Result<Account, Error>;
+    async fn get_accounts(&self, offset: i64, limit: i64) -> Result<Vec<AccountInfo>, Error>;
+    async fn update_account_role(
+        &self,
+        account_id: &AccountId,
+        role: Role,
+    ) -> Result<AccountInfo, Error>;
+    async fn update_profile(
+        &self,
+        account_id: &AccountId,
+        update: ProfileUpdate,
+    ) -> Result<Account, Error>;
+    /// Also marks the account as unverified.
+    async fn update_email(&self, account_id: &AccountId, email: String) -> Result<Account, Error>;
+    async fn update_password(
+        &self,
+        account_id: &AccountId,
+        password_hash: String,
+    ) -> Result<(), Error>;
+    /// Only if `email` is still the account's address, returns whether it was.
+    async fn mark_email_verified(&self, account_id: &AccountId, email: &str)
+        -> Result<bool, Error>;
+    /// Scrubs the credentials and profile and revokes every session. The row
+    /// stays so posts, votes and revisions keep pointing at it.
+    async fn delete_account(&self, account_id: &AccountId) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait SessionRepository {
+    async fn add_session(&self, session: AuthSession) -> Result<(), Error>;
+    async fn get_session(&self, session_id: &SessionId) -> Result<Option<AuthSession>, Error>;
+    async fn rotate_refresh_token(
+        &self,
+        session_id: &SessionId,
+        refresh_token_hash: String,
+        expires_on: DateTime<Utc>,
+    ) -> Result<(), Error>;
+    async fn revoke_session(&self, session_id: &SessionId) -> Result<(), Error>;
+    async fn revoke_account_sessions(&self, account_id: &AccountId) -> Result<(), Error>;
+}
+
+/// Single use tokens for email verification and password reset.
+#[async_trait]
+pub trait AccountTokenRepository {
+    async fn add_account_token(&self, token: AccountToken) -> Result<(), Error>;
+    async fn get_account_token(
+        &self,
+        token_id: &AccountTokenId,
+    ) -> Result<Option<AccountToken>, Error>;
+    /// Marks the token used, false when it already was.
+    async fn use_account_token(&self, token_id: &AccountTokenId) -> Result<bool, Error>;
+    /// Invalidates every unused token of the account for `purpose`.
+    async fn revoke_account_tokens(
+        &self,
         account_id: &AccountId,
-    ) -> Result<bool, Error> {
-        match sqlx::query("SELECT * from questions where id = $1 and account_id = $2")
-            .bind(question_id)
-            .bind(account_id.0)
-            .fetch_optional(&self.connection)
-            .await
-        {
-            Ok(question) => Ok(question.is_some()),
-            Err(e) => {
-                event!(tracing::Level::ERROR, "{:?}", e);
-                Err(Error::DatabaseQueryError(e))
-            }
-        }
-    }
+        purpose: TokenPurpose,
+    ) -> Result<(), Error>;
+}
+
+/// Failed login bookkeeping, see `handlers::lockout`.
+#[async_trait]
+pub trait LoginAttemptRepository {
+    async fn get_login_attempts(&self, key: &str) -> Result<Option<LoginAttempts>, Error>;
+    /// Counts a failure, starting over when the last one is older than
+    /// `window_start`.
+    async fn record_login_failure(
+        &self,
+        key: &str,
+        window_start: DateTime<Utc>,
+    ) -> Result<LoginAttempts, Error>;
+    async fn lock_login(&self, key: &str, until: DateTime<Utc>) -> Result<(), Error>;
+    async fn clear_login_attempts(&self, key: &str) -> Result<(), Error>;
+    /// Drops unlocked entries whose last failure is older than `before`.
+    async fn purge_login_attempts(&self, before: DateTime<Utc>) -> Result<u64, Error>;
+}
+
+/// Revoked keys are kept for auditing but never returned.
+#[async_trait]
+pub trait ApiKeyRepository {
+    async fn add_api_key(&self, key: ApiKey) -> Result<ApiKey, Error>;
+    async fn get_api_key(&self, key_id: &ApiKeyId) -> Result<Option<ApiKey>, Error>;
+    async fn get_api_keys(&self, account_id: &AccountId) -> Result<Vec<ApiKey>, Error>;
+    /// Returns false when the account has no such active key.
+    async fn revoke_api_key(
+        &self,
+        account_id: &AccountId,
+        key_id: &ApiKeyId,
+    ) -> Result<bool, Error>;
+    async fn touch_api_key(&self, key_id: &ApiKeyId) -> Result<(), Error>;
+}
+
+/// TOTP secrets and recovery codes.
+#[async_trait]
+pub trait TwoFactorRepository {
+    async fn get_two_factor(&self, account_id: &AccountId) -> Result<TwoFactor, Error>;
+    /// Stores a pending secret, replacing an unconfirmed one.
+    async fn set_totp_secret(&self, account_id: &AccountId, secret: String) -> Result<(), Error>;
+    /// Turns on 2FA for the pending secret, `step` being the confirmed code.
+    async fn enable_two_factor(
+        &self,
+        account_id: &AccountId,
+        step: i64,
+        recovery_code_hashes: Vec<String>,
+    ) -> Result<(), Error>;
+    /// Drops the secret and every recovery code.
+    async fn disable_two_factor(&self, account_id: &AccountId) -> Result<(), Error>;
+    /// Records `step` as used, false when it or a later one already was.
+    async fn use_totp_step(&self, account_id: &AccountId, step: i64) -> Result<bool, Error>;
+    /// Unused codes only.
+    async fn get_recovery_codes(&self, account_id: &AccountId) -> Result<Vec<RecoveryCode>, Error>;
+    /// Marks the code used, false when it already was.
+    async fn use_recovery_code(&self, code_id: i32) -> Result<bool, Error>;
+    async fn replace_recovery_codes(
+        &self,
+        account_id: &AccountId,
+        code_hashes: Vec<String>,
+    ) -> Result<(), Error>;
+}
+
+/// Casting a vote replaces the account's previous vote on the same target.
+#[async_trait]
+pub trait VoteRepository {
+    async fn cast_vote(
+        &self,
+        target: &VoteTarget,
+        account_id: &AccountId,
+        direction: VoteDirection,
+    ) -> Result<(), Error>;
+    async fn retract_vote(&self, target: &VoteTarget, account_id: &AccountId) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait SearchRepository {
+    /// Questions and answers matching `query.q`, best match first.
+    async fn search(
+        &self,
+        query: &SearchQuery,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<SearchResult>, Error>;
+}
+
+pub trait Repository:
+    QuestionRepository
+    + AnswerRepository
+    + CommentRepository
+    + RevisionRepository
+    + TrashRepository
+    + TagRepository
+    + AccountRepository
+    + SessionRepository
+    + AccountTokenRepository
+    + TwoFactorRepository
+    + ApiKeyRepository
+    + LoginAttemptRepository
+    + VoteRepository
+    + SearchRepository
+    + Debug
+    + Send
+    + Sync
+{
+}
+
+impl<T> Repository for T where
+    T: QuestionRepository
+        + AnswerRepository
+        + CommentRepository
+        + RevisionRepository
+        + TrashRepository
+        + TagRepository
+        + AccountRepository
+        + SessionRepository
+        + AccountTokenRepository
+        + TwoFactorRepository
+        + ApiKeyRepository
+        + LoginAttemptRepository
+        + VoteRepository
+        + SearchRepository
+        + Debug
+        + Send
+        + Sync
+{
 }
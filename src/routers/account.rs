@@ -1,13 +1,54 @@
-use axum::{routing::post, Router};
+use axum::{
+    routing::{delete, get, post, put},
+    Router,
+};
 
 use crate::{
-    handlers::account::{login, register},
-    repositories::store::Store,
+    common::state::AppState,
+    handlers::{
+        account::{
+            change_email, change_password, delete_me, get_account_profile, get_accounts, get_me,
+            login, logout, logout_all, refresh_token, register, update_account_role, update_me,
+        },
+        api_key::{create_api_key, get_api_keys, revoke_api_key},
+        lockout::unlock_account,
+        two_factor::{
+            confirm_two_factor, disable_two_factor, enroll_two_factor, login_two_factor,
+            regenerate_recovery_codes,
+        },
+        verification::{forgot_password, resend_verification, reset_password, verify_email},
+    },
 };
 
-pub fn create_router(store: Store) -> Router {
+pub fn create_router(state: AppState) -> Router {
     Router::new()
         .route("/api/registration", post(register))
         .route("/api/login", post(login))
-        .with_state(store)
+        .route("/api/login/2fa", post(login_two_factor))
+        .route("/api/token/refresh", post(refresh_token))
+        .route("/api/logout", post(logout))
+        .route("/api/logout/all", post(logout_all))
+        .route("/api/me", get(get_me).patch(update_me).delete(delete_me))
+        .route("/api/me/email", put(change_email))
+        .route("/api/me/email/verification", post(resend_verification))
+        .route("/api/me/password", put(change_password))
+        .route(
+            "/api/me/2fa",
+            post(enroll_two_factor).delete(disable_two_factor),
+        )
+        .route("/api/me/2fa/confirm", post(confirm_two_factor))
+        .route("/api/me/api-keys", get(get_api_keys).post(create_api_key))
+        .route("/api/me/api-keys/:id", delete(revoke_api_key))
+        .route(
+            "/api/me/2fa/recovery-codes",
+            post(regenerate_recovery_codes),
+        )
+        .route("/api/email/verify", post(verify_email))
+        .route("/api/password/forgot", post(forgot_password))
+        .route("/api/password/reset", post(reset_password))
+        .route("/api/accounts", get(get_accounts))
+        .route("/api/accounts/:id", get(get_account_profile))
+        .route("/api/accounts/:id/role", put(update_account_role))
+        .route("/api/accounts/:id/lockout", delete(unlock_account))
+        .with_state(state)
 }
@@ -1,9 +1,38 @@
-use axum::{routing::post, Router};
+use axum::{
+    routing::{delete, get, post, put},
+    Router,
+};
 
-use crate::{handlers::answer::add_answer, repositories::store::Store};
+use crate::{
+    common::state::AppState,
+    handlers::answer::{
+        add_answer, delete_answer, diff_answer_revisions, get_answer_byid, get_answer_revision,
+        get_answer_revisions, get_answers, retract_answer_vote, rollback_answer, update_answer,
+        vote_answer,
+    },
+};
 
-pub fn create_router(store: Store) -> Router {
+pub fn create_router(state: AppState) -> Router {
     Router::new()
         .route("/api/answers", post(add_answer))
-        .with_state(store)
+        .route("/api/questions/:id/answers", get(get_answers))
+        .route("/api/answers/:id", get(get_answer_byid))
+        .route("/api/answers/:id", put(update_answer))
+        .route("/api/answers/:id", delete(delete_answer))
+        .route("/api/answers/:id/vote", put(vote_answer))
+        .route("/api/answers/:id/vote", delete(retract_answer_vote))
+        .route("/api/answers/:id/revisions", get(get_answer_revisions))
+        .route(
+            "/api/answers/:id/revisions/diff",
+            get(diff_answer_revisions),
+        )
+        .route(
+            "/api/answers/:id/revisions/:revision",
+            get(get_answer_revision),
+        )
+        .route(
+            "/api/answers/:id/revisions/:revision/rollback",
+            post(rollback_answer),
+        )
+        .with_state(state)
 }
@@ -0,0 +1,26 @@
+use axum::{routing::get, Router};
+
+use crate::{
+    common::state::AppState,
+    handlers::comment::{
+        add_answer_comment, add_question_comment, delete_comment, get_answer_comments, get_comment,
+        get_question_comments, update_comment,
+    },
+};
+
+pub fn create_router(state: AppState) -> Router {
+    Router::new()
+        .route(
+            "/api/questions/:id/comments",
+            get(get_question_comments).post(add_question_comment),
+        )
+        .route(
+            "/api/answers/:id/comments",
+            get(get_answer_comments).post(add_answer_comment),
+        )
+        .route(
+            "/api/comments/:id",
+            get(get_comment).put(update_comment).delete(delete_comment),
+        )
+        .with_state(state)
+}
@@ -1,25 +1,31 @@
-use axum::{
-    middleware,
-    routing::{get, post},
-    Router,
-};
+use axum::{middleware, routing::get, Router};
 use tower_http::trace::TraceLayer;
 
 use crate::{
+    common::{rate_limit::rate_limit, request_id::request_id, state::AppState},
     handlers::{account::auth, health_check_handler},
-    repositories::store::Store,
 };
 
 pub mod account;
 pub mod answer;
+pub mod comment;
 pub mod question;
+pub mod search;
+pub mod tag;
+pub mod trash;
 
-pub fn create_router(store: Store) -> Router {
+pub fn create_router(state: AppState) -> Router {
     Router::new()
         .route("/api/healthcheck", get(health_check_handler))
-        .merge(question::create_router(store.clone()))
-        .merge(answer::create_router(store.clone()))
-        .merge(account::create_router(store.clone()))
-        .layer(middleware::from_fn(auth))
+        .merge(question::create_router(state.clone()))
+        .merge(answer::create_router(state.clone()))
+        .merge(comment::create_router(state.clone()))
+        .merge(account::create_router(state.clone()))
+        .merge(search::create_router(state.clone()))
+        .merge(tag::create_router(state.clone()))
+        .merge(trash::create_router(state.clone()))
+        .layer(middleware::from_fn_with_state(state.clone(), rate_limit))
+        .layer(middleware::from_fn_with_state(state, auth))
+        .layer(middleware::from_fn(request_id))
         .layer(TraceLayer::new_for_http())
 }
@@ -4,18 +4,40 @@ use axum::{
 };
 
 use crate::{
+    common::state::AppState,
     handlers::question::{
-        add_question, delete_question, get_question_byid, get_questions, update_question,
+        accept_answer, add_question, delete_question, diff_question_revisions, get_question_byid,
+        get_question_revision, get_question_revisions, get_questions, retract_question_vote,
+        rollback_question, unaccept_answer, update_question, vote_question,
     },
-    repositories::store::Store,
 };
 
-pub fn create_router(store: Store) -> Router {
+pub fn create_router(state: AppState) -> Router {
     Router::new()
         .route("/api/questions", post(add_question))
         .route("/api/questions", get(get_questions))
         .route("/api/questions/:id", get(get_question_byid))
         .route("/api/questions/:id", put(update_question))
         .route("/api/questions/:id", delete(delete_question))
-        .with_state(store)
+        .route("/api/questions/:id/accepted-answer", put(accept_answer))
+        .route(
+            "/api/questions/:id/accepted-answer",
+            delete(unaccept_answer),
+        )
+        .route("/api/questions/:id/vote", put(vote_question))
+        .route("/api/questions/:id/vote", delete(retract_question_vote))
+        .route("/api/questions/:id/revisions", get(get_question_revisions))
+        .route(
+            "/api/questions/:id/revisions/diff",
+            get(diff_question_revisions),
+        )
+        .route(
+            "/api/questions/:id/revisions/:revision",
+            get(get_question_revision),
+        )
+        .route(
+            "/api/questions/:id/revisions/:revision/rollback",
+            post(rollback_question),
+        )
+        .with_state(state)
 }
@@ -0,0 +1,9 @@
+use axum::{routing::get, Router};
+
+use crate::{common::state::AppState, handlers::search::search};
+
+pub fn create_router(state: AppState) -> Router {
+    Router::new()
+        .route("/api/search", get(search))
+        .with_state(state)
+}
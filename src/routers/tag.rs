@@ -0,0 +1,23 @@
+use axum::{
+    routing::{get, put},
+    Router,
+};
+
+use crate::{
+    common::state::AppState,
+    handlers::tag::{
+        add_tag_synonym, get_tag, get_tag_questions, get_tags, remove_tag_synonym, update_tag,
+    },
+};
+
+pub fn create_router(state: AppState) -> Router {
+    Router::new()
+        .route("/api/tags", get(get_tags))
+        .route("/api/tags/:slug", get(get_tag).put(update_tag))
+        .route("/api/tags/:slug/questions", get(get_tag_questions))
+        .route(
+            "/api/tags/:slug/synonyms/:synonym",
+            put(add_tag_synonym).delete(remove_tag_synonym),
+        )
+        .with_state(state)
+}
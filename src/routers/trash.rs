@@ -0,0 +1,20 @@
+use axum::{
+    routing::{get, post},
+    Router,
+};
+
+use crate::{
+    common::state::AppState,
+    handlers::trash::{
+        get_deleted_answers, get_deleted_questions, restore_answer, restore_question,
+    },
+};
+
+pub fn create_router(state: AppState) -> Router {
+    Router::new()
+        .route("/api/trash/questions", get(get_deleted_questions))
+        .route("/api/trash/answers", get(get_deleted_answers))
+        .route("/api/questions/:id/restore", post(restore_question))
+        .route("/api/answers/:id/restore", post(restore_answer))
+        .with_state(state)
+}
@@ -0,0 +1,518 @@
+//! Drives the full router over the in-memory store, without a database or a
+//! listening socket.
+
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use axum_web_demo::{
+    common::{
+        config::Config, content_filter::ContentPolicy, mailer::MemoryMailer,
+        rate_limit::MemoryRateLimitStore, state::AppState,
+    },
+    repositories::memory::MemoryStore,
+    routers::create_router,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+const PASSWORD: &str = "passw0rd1";
+const TOKEN_KEY: &str = "0123456789abcdef0123456789abcdef";
+
+struct TestApp {
+    router: Router,
+    mailer: Arc<MemoryMailer>,
+}
+
+impl TestApp {
+    fn new() -> Self {
+        TestApp::with_config(|_| {})
+    }
+
+    fn with_config(configure: impl FnOnce(&mut Config)) -> Self {
+        let mut config = Config::default();
+        config.auth.token_key = String::from(TOKEN_KEY);
+        config.auth.require_verified_email = false;
+        configure(&mut config);
+        let mailer = Arc::new(MemoryMailer::default());
+        let state = AppState {
+            store: Arc::new(MemoryStore::new()),
+            content_policy: ContentPolicy::from_config(&config.content_filter),
+            mailer: mailer.clone(),
+            rate_limits: Arc::new(MemoryRateLimitStore::default()),
+            config: Arc::new(config),
+        };
+        TestApp {
+            router: create_router(state),
+            mailer,
+        }
+    }
+
+    /// Sends one request, the answer body as JSON or as a JSON string when
+    /// it is plain text.
+    async fn send(
+        &self,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        self.send_from(None, method, uri, token, body).await
+    }
+
+    /// `send` with an `X-Forwarded-For` client address.
+    async fn send_from(
+        &self,
+        client: Option<&str>,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(client) = client {
+            request = request.header("x-forwarded-for", client);
+        }
+        if let Some(token) = token {
+            // access tokens go as bearer tokens, `ApiKey ...` as it is
+            let credential = match token.contains(' ') {
+                true => token.to_string(),
+                false => format!("Bearer {}", token),
+            };
+            request = request.header(header::AUTHORIZATION, credential);
+        }
+        let request = match body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .unwrap();
+
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = serde_json::from_slice(&bytes)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()));
+        (status, body)
+    }
+
+    async fn register(&self, email: &str) -> StatusCode {
+        let body = json!({ "email": email, "password": PASSWORD });
+        let (status, _) = self
+            .send(Method::POST, "/api/registration", None, Some(body))
+            .await;
+        status
+    }
+
+    async fn login(&self, email: &str, password: &str) -> (StatusCode, Value) {
+        let body = json!({ "email": email, "password": password });
+        self.send(Method::POST, "/api/login", None, Some(body))
+            .await
+    }
+
+    /// Registers a fresh account and returns its access token.
+    async fn signed_in(&self, email: &str) -> String {
+        assert_eq!(self.register(email).await, StatusCode::OK);
+        let (status, body) = self.login(email, PASSWORD).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body["access_token"].as_str().unwrap().to_string()
+    }
+}
+
+/// Re-encrypts the claims of `token` with `changes` applied, as anyone who
+/// holds the token key could.
+fn forge_token(token: &str, changes: Value) -> String {
+    let mut claims = paseto::tokens::validate_local_token(
+        token,
+        None,
+        TOKEN_KEY.as_bytes(),
+        &paseto::tokens::TimeBackend::Chrono,
+    )
+    .unwrap();
+    for (claim, value) in changes.as_object().unwrap() {
+        claims[claim] = value.clone();
+    }
+    paseto::v2::local_paseto(&claims.to_string(), None, TOKEN_KEY.as_bytes()).unwrap()
+}
+
+fn new_question(title: &str) -> Value {
+    json!({
+        "title": title,
+        "content": "How do I share state between axum handlers?",
+        "tags": ["rust", "axum"],
+    })
+}
+
+#[tokio::test]
+async fn register_and_login() {
+    let app = TestApp::new();
+
+    assert_eq!(app.register("ann@example.com").await, StatusCode::OK);
+    assert_eq!(app.register("ann@example.com").await, StatusCode::CONFLICT);
+
+    let (status, body) = app.login("ann@example.com", PASSWORD).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["access_token"].is_string());
+    assert!(body["refresh_token"].is_string());
+
+    let token = body["access_token"].as_str().unwrap();
+    let (status, me) = app.send(Method::GET, "/api/me", Some(token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(me["email"], "ann@example.com");
+    assert_eq!(me["role"], "user");
+}
+
+#[tokio::test]
+async fn unknown_email_and_wrong_password_look_alike() {
+    let app = TestApp::new();
+    app.register("ann@example.com").await;
+
+    let (wrong_status, wrong) = app.login("ann@example.com", "wrongpass1").await;
+    let (unknown_status, unknown) = app.login("bob@example.com", PASSWORD).await;
+    assert_eq!(wrong_status, StatusCode::UNAUTHORIZED);
+    assert_eq!(unknown_status, StatusCode::UNAUTHORIZED);
+    assert_eq!(wrong["code"], unknown["code"]);
+}
+
+#[tokio::test]
+async fn overlong_login_emails_are_refused() {
+    let app = TestApp::new();
+
+    let email = format!("{}@example.com", "a".repeat(400));
+    let (status, body) = app.login(&email, PASSWORD).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+    assert_eq!(body["errors"][0]["field"], "email");
+}
+
+#[tokio::test]
+async fn password_resets_answer_alike_for_unknown_emails() {
+    let app = TestApp::new();
+    app.register("ann@example.com").await;
+    let sent_before = app.mailer.outbox().len();
+
+    let forgot = |email: &str| {
+        let body = json!({ "email": email });
+        app.send(Method::POST, "/api/password/forgot", None, Some(body))
+    };
+    let known = forgot("ann@example.com").await;
+    let unknown = forgot("bob@example.com").await;
+    assert_eq!(known.0, StatusCode::OK);
+    assert_eq!(known, unknown);
+
+    // the mail goes out in the background
+    for _ in 0..50 {
+        if app.mailer.outbox().len() > sent_before {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let outbox = app.mailer.outbox();
+    assert_eq!(outbox.len(), sent_before + 1);
+    assert_eq!(outbox[sent_before].to, "ann@example.com");
+}
+
+#[tokio::test]
+async fn requests_without_a_token_are_rejected() {
+    let app = TestApp::new();
+
+    let (status, body) = app.send(Method::GET, "/api/me", None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "invalid_token");
+}
+
+#[tokio::test]
+async fn tokens_only_hold_for_the_account_of_their_session() {
+    let app = TestApp::new();
+    let ann = app.signed_in("ann@example.com").await;
+    let bob = app.signed_in("bob@example.com").await;
+    let (_, bob_profile) = app.send(Method::GET, "/api/me", Some(&bob), None).await;
+
+    let as_bob = forge_token(&ann, json!({ "account_id": bob_profile["id"] }));
+    let (status, _) = app.send(Method::GET, "/api/me", Some(&as_bob), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let as_admin = forge_token(&ann, json!({ "role": "admin" }));
+    let uri = format!("/api/accounts/{}/lockout", bob_profile["id"]);
+    let (status, _) = app.send(Method::DELETE, &uri, Some(&as_admin), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn api_keys_authenticate_until_revoked() {
+    let app = TestApp::new();
+    let token = app.signed_in("ann@example.com").await;
+
+    let (status, created) = app
+        .send(
+            Method::POST,
+            "/api/me/api-keys",
+            Some(&token),
+            Some(json!({ "name": "ci", "scope": "read" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", created);
+    let key = format!("ApiKey {}", created["key"].as_str().unwrap());
+    let (status, me) = app.send(Method::GET, "/api/me", Some(&key), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(me["email"], "ann@example.com");
+
+    let wrong_secret = format!("{}0", key);
+    let (status, _) = app
+        .send(Method::GET, "/api/me", Some(&wrong_secret), None)
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let uri = format!("/api/me/api-keys/{}", created["id"].as_str().unwrap());
+    let (status, _) = app.send(Method::DELETE, &uri, Some(&token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.send(Method::GET, "/api/me", Some(&key), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn rejected_credentials_are_throttled_per_address() {
+    let app = TestApp::with_config(|config| {
+        config.server.trust_forwarded_for = true;
+        config.rate_limit.failed_auth_requests = 2;
+        config.rate_limit.failed_auth_window_secs = 3600;
+    });
+    let token = app.signed_in("ann@example.com").await;
+    let attacker = Some("203.0.113.7");
+
+    for _ in 0..2 {
+        let (status, _) = app
+            .send_from(attacker, Method::GET, "/api/me", Some("forged"), None)
+            .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+    let (status, body) = app
+        .send_from(attacker, Method::GET, "/api/me", Some(&token), None)
+        .await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["code"], "rate_limited");
+
+    let (status, _) = app
+        .send_from(
+            Some("198.51.100.1"),
+            Method::GET,
+            "/api/me",
+            Some(&token),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn public_path_suffixes_still_need_a_token() {
+    let app = TestApp::new();
+
+    for uri in ["/api/tags/login", "/api/tags/registration", "/api/tags/2fa"] {
+        let (status, body) = app.send(Method::GET, uri, None, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{}: {}", uri, body);
+    }
+}
+
+#[tokio::test]
+async fn question_lifecycle() {
+    let app = TestApp::new();
+    let token = app.signed_in("ann@example.com").await;
+
+    let (status, question) = app
+        .send(
+            Method::POST,
+            "/api/questions",
+            Some(&token),
+            Some(new_question("Sharing state in axum")),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", question);
+    assert_eq!(question["title"], "Sharing state in axum");
+    let uri = format!("/api/questions/{}", question["id"]);
+
+    let (status, fetched) = app.send(Method::GET, &uri, Some(&token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(fetched["id"], question["id"]);
+    assert_eq!(fetched["content"], question["content"]);
+
+    let update = json!({
+        "title": "Sharing state between axum handlers",
+        "content": "Is State or Extension the better fit?",
+        "tags": ["rust"],
+    });
+    let (status, updated) = app
+        .send(Method::PUT, &uri, Some(&token), Some(update))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", updated);
+    assert_eq!(updated["title"], "Sharing state between axum handlers");
+
+    let (_, fetched) = app.send(Method::GET, &uri, Some(&token), None).await;
+    assert_eq!(fetched["content"], "Is State or Extension the better fit?");
+    assert_eq!(fetched["tags"], json!(["rust"]));
+
+    let (status, _) = app.send(Method::DELETE, &uri, Some(&token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = app.send(Method::GET, &uri, Some(&token), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "question_not_found");
+}
+
+#[tokio::test]
+async fn questions_can_only_be_changed_by_their_author() {
+    let app = TestApp::new();
+    let ann = app.signed_in("ann@example.com").await;
+    let bob = app.signed_in("bob@example.com").await;
+
+    let (_, question) = app
+        .send(
+            Method::POST,
+            "/api/questions",
+            Some(&ann),
+            Some(new_question("Sharing state in axum")),
+        )
+        .await;
+    let uri = format!("/api/questions/{}", question["id"]);
+
+    let update = new_question("Taken over");
+    let (status, _) = app.send(Method::PUT, &uri, Some(&bob), Some(update)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app.send(Method::DELETE, &uri, Some(&bob), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn revisions_are_shown_to_the_author_only() {
+    let app = TestApp::new();
+    let ann = app.signed_in("ann@example.com").await;
+    let bob = app.signed_in("bob@example.com").await;
+
+    let (_, question) = app
+        .send(
+            Method::POST,
+            "/api/questions",
+            Some(&ann),
+            Some(new_question("Sharing state in axum")),
+        )
+        .await;
+    let uri = format!("/api/questions/{}", question["id"]);
+    let update = new_question("Sharing state between handlers");
+    app.send(Method::PUT, &uri, Some(&ann), Some(update)).await;
+
+    let answer = json!({ "content": "Use State", "question_id": question["id"] });
+    let (_, answer) = app
+        .send(Method::POST, "/api/answers", Some(&ann), Some(answer))
+        .await;
+    let answer_uri = format!("/api/answers/{}", answer["id"]);
+    let update = json!({ "content": "Use State or Extension", "question_id": question["id"] });
+    app.send(Method::PUT, &answer_uri, Some(&ann), Some(update))
+        .await;
+
+    for base in [&uri, &answer_uri] {
+        for path in ["revisions", "revisions/1", "revisions/diff?from=1&to=2"] {
+            let uri = format!("{}/{}", base, path);
+            let (status, body) = app.send(Method::GET, &uri, Some(&ann), None).await;
+            assert_eq!(status, StatusCode::OK, "{}: {}", uri, body);
+            let (status, _) = app.send(Method::GET, &uri, Some(&bob), None).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{}", uri);
+        }
+    }
+}
+
+#[tokio::test]
+async fn malformed_parameters_are_problems() {
+    let app = TestApp::new();
+    let token = app.signed_in("ann@example.com").await;
+
+    for uri in [
+        "/api/questions/abc",
+        "/api/questions/1/answers?limit=many",
+        "/api/search?q=axum&offset=-",
+    ] {
+        let (status, body) = app.send(Method::GET, uri, Some(&token), None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}: {}", uri, body);
+        assert_eq!(body["code"], "invalid_parameter", "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn cursors_only_resume_their_own_sort_order() {
+    let app = TestApp::new();
+    let token = app.signed_in("ann@example.com").await;
+    for title in ["First question", "Second question"] {
+        let question = new_question(title);
+        app.send(Method::POST, "/api/questions", Some(&token), Some(question))
+            .await;
+    }
+
+    let (status, page) = app
+        .send(
+            Method::GET,
+            "/api/questions?sort=newest&limit=1",
+            Some(&token),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", page);
+    assert_eq!(page["items"][0]["title"], "Second question");
+    let cursor = page["next_cursor"].as_str().unwrap();
+
+    let uri = format!("/api/questions?sort=newest&limit=1&cursor={}", cursor);
+    let (status, page) = app.send(Method::GET, &uri, Some(&token), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", page);
+    assert_eq!(page["items"][0]["title"], "First question");
+
+    for sort in ["oldest", "activity"] {
+        let uri = format!("/api/questions?sort={}&limit=1&cursor={}", sort, cursor);
+        let (status, body) = app.send(Method::GET, &uri, Some(&token), None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}: {}", sort, body);
+        assert_eq!(body["code"], "invalid_parameter");
+    }
+}
+
+#[tokio::test]
+async fn invalid_questions_are_refused() {
+    let app = TestApp::new();
+    let token = app.signed_in("ann@example.com").await;
+
+    let (status, body) = app
+        .send(
+            Method::POST,
+            "/api/questions",
+            Some(&token),
+            Some(new_question(" ")),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+    assert_eq!(body["errors"][0]["field"], "title");
+}
+
+#[tokio::test]
+async fn search_snippets_escape_the_content() {
+    let app = TestApp::new();
+    let token = app.signed_in("ann@example.com").await;
+
+    let question = json!({
+        "title": "Rendering markup",
+        "content": "Does axum run <script>alert(1)</script> when it is shown?",
+        "tags": ["html"],
+    });
+    let (status, _) = app
+        .send(Method::POST, "/api/questions", Some(&token), Some(question))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = app
+        .send(Method::GET, "/api/search?q=axum", Some(&token), None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let snippet = body[0]["snippet"].as_str().unwrap();
+    assert!(!snippet.contains("<script>"), "{}", snippet);
+    assert!(snippet.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+    assert!(snippet.contains("<mark>axum</mark>"));
+}